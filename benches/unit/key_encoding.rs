@@ -0,0 +1,72 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rocksmap::{KeyCodec, OrderedCodec, OrderedKey, RocksMap, VarU64};
+use serde::{de::DeserializeOwned, Serialize};
+use tempfile::TempDir;
+
+/// Compares fixed-width `u64` keys with `VarU64` keys: encode/decode cost, and put/get
+/// throughput on composite `(id, seq)` keys with mostly small ids. The codec benchmarks report
+/// their throughput in encoded bytes, so each key's encoded size shows next to its timing.
+const IDS: u64 = 1_000;
+
+fn encoded_len<K: OrderedKey>(key: &K) -> usize {
+    <OrderedCodec<K> as KeyCodec<K>>::encode(key).unwrap().len()
+}
+
+fn benchmark_codec(c: &mut Criterion) {
+    let mut group = c.benchmark_group("key_codec");
+    for id in [42u64, 1 << 40] {
+        group.throughput(Throughput::Bytes(encoded_len(&id) as u64));
+        group.bench_with_input(BenchmarkId::new("encode_u64", id), &id, |b, &id| {
+            b.iter(|| <OrderedCodec<u64> as KeyCodec<u64>>::encode(black_box(&id)).unwrap())
+        });
+        let fixed = <OrderedCodec<u64> as KeyCodec<u64>>::encode(&id).unwrap();
+        group.bench_with_input(BenchmarkId::new("decode_u64", id), &fixed, |b, bytes| {
+            b.iter(|| <OrderedCodec<u64> as KeyCodec<u64>>::decode(black_box(bytes)).unwrap())
+        });
+        group.throughput(Throughput::Bytes(encoded_len(&VarU64(id)) as u64));
+        group.bench_with_input(BenchmarkId::new("encode_varu64", id), &id, |b, &id| {
+            let key = VarU64(id);
+            b.iter(|| <OrderedCodec<VarU64> as KeyCodec<VarU64>>::encode(black_box(&key)).unwrap())
+        });
+        let var = <OrderedCodec<VarU64> as KeyCodec<VarU64>>::encode(&VarU64(id)).unwrap();
+        group.bench_with_input(BenchmarkId::new("decode_varu64", id), &var, |b, bytes| {
+            b.iter(|| <OrderedCodec<VarU64> as KeyCodec<VarU64>>::decode(black_box(bytes)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_map<K>(c: &mut Criterion, label: &str, key: fn(u64, u64) -> K)
+where
+    K: OrderedKey + Serialize + DeserializeOwned + Clone,
+{
+    let temp_dir = TempDir::new().unwrap();
+    let db = RocksMap::<K, u64>::open(temp_dir.path()).unwrap();
+    for i in 0..IDS {
+        db.put(key(i, 0), &i).unwrap();
+    }
+
+    c.bench_function(&format!("put_{label}"), |b| {
+        let mut counter = 0u64;
+        b.iter(|| {
+            counter += 1;
+            db.put(black_box(key(counter % IDS, counter)), &counter)
+                .unwrap();
+        })
+    });
+    c.bench_function(&format!("get_{label}"), |b| {
+        let mut counter = 0u64;
+        b.iter(|| {
+            counter += 1;
+            black_box(db.get(black_box(&key(counter % IDS, 0))).unwrap());
+        })
+    });
+}
+
+fn benchmark_map_operations(c: &mut Criterion) {
+    bench_map(c, "fixed_u64_keys", |id, seq| (id, seq));
+    bench_map(c, "varu64_keys", |id, seq| (VarU64(id), VarU64(seq)));
+}
+
+criterion_group!(key_encoding, benchmark_codec, benchmark_map_operations);
+criterion_main!(key_encoding);
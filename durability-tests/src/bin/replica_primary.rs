@@ -0,0 +1,44 @@
+//! Test helper: a replication primary for the `RocksMap<u64, String>` at `argv[1]`. Prints
+//! `LISTENING <addr>` once the server is up, then applies commands from stdin, answering each
+//! with `OK`: `put <key> <value>`, `delete <key>`, `flush` (flush every column family, letting RocksDB
+//! purge the WAL). Exits at end of input. Used by `tests/replication.rs`.
+
+use rocksmap::{ReplicationServer, RocksMap};
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+fn main() {
+    let path = std::env::args()
+        .nth(1)
+        .expect("usage: replica_primary <db_path>");
+    let map = Arc::new(RocksMap::<u64, String>::open(&path).expect("open"));
+    let server = ReplicationServer::start(map.clone(), "127.0.0.1:0").expect("start server");
+
+    let mut out = std::io::stdout().lock();
+    writeln!(out, "LISTENING {}", server.local_addr()).unwrap();
+    out.flush().unwrap();
+
+    for line in std::io::stdin().lock().lines() {
+        let line = line.expect("read command");
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("put"), Some(key), Some(value)) => {
+                map.put(key.parse().expect("key"), &value.to_string())
+                    .expect("put");
+            }
+            (Some("delete"), Some(key), None) => {
+                map.delete(&key.parse().expect("key")).expect("delete")
+            }
+            (Some("flush"), None, None) => {
+                // The WAL is shared, so it is only purged once every column family is flushed.
+                for cf in ["default", "__rocksmap_meta"] {
+                    let handle = map.db().cf_handle(cf).expect("column family");
+                    map.db().flush_cf(handle).expect("flush");
+                }
+            }
+            _ => panic!("unknown command `{line}`"),
+        }
+        writeln!(out, "OK").unwrap();
+        out.flush().unwrap();
+    }
+}
@@ -1,7 +1,7 @@
 //! `rocksmap-cli` — inspect and operate rocksmap databases.
 //!
-//! Safe by default: it reads/inspects any rocksmap database (plain / TTL / indexed) but only
-//! *mutates* plain databases — writing into a TTL or indexed database via the CLI would bypass
+//! Safe by default: it reads/inspects any rocksmap database (plain / TTL / indexed / versioned)
+//! but only *mutates* plain databases — writing into any other kind via the CLI would bypass
 //! envelope/index maintenance and corrupt invariants, so those are refused.
 
 use anyhow::{anyhow, bail, Context, Result};
@@ -10,9 +10,11 @@ use rocksdb::{
     checkpoint::Checkpoint, ColumnFamilyDescriptor, IteratorMode, Options, ReadOptions, DB,
 };
 use rocksmap::{
-    inspect, strip_ttl_envelope, BincodeCodec, KeyCodec, MapKind, OrderedCodec, RocksMap,
-    ValueCodec,
+    convert, inspect, plan_format_upgrade, strip_ttl_envelope, strip_version_envelope,
+    upgrade_format, BincodeCodec, KeyCodec, MapKind, OpenOptions, OrderedCodec, OrderedKey,
+    RocksMap, ValueCodec,
 };
+use serde::{de::DeserializeOwned, Serialize};
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -31,7 +33,7 @@ struct Cli {
     #[arg(short, long, value_enum, default_value = "table", global = true)]
     format: Format,
 
-    /// How to interpret keys (ordered-keyed databases only)
+    /// How to interpret keys (reads and writes need an ordered-keyed database)
     #[arg(long, value_enum, default_value = "string", global = true)]
     key_type: KeyType,
 
@@ -99,6 +101,12 @@ enum IoFormat {
     Csv { file: PathBuf },
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum KeyCodecArg {
+    Ordered,
+    Bincode,
+}
+
 #[derive(Subcommand)]
 enum AdminCmd {
     /// Approximate key count and column families
@@ -109,10 +117,25 @@ enum AdminCmd {
     Backup { path: PathBuf },
     /// List column families
     ListCf {
-        /// Include internal column families (`__rocksmap_meta`, `__idx_*`)
+        /// Include internal column families (`__rocksmap_meta`, `__idx_*`, `__ttl_expiry`)
         #[arg(long)]
         internal: bool,
     },
+    /// Convert the key codec of a plain database with string values (keys per `--key-type`)
+    ConvertKeys {
+        /// Key codec to convert to
+        #[arg(long, value_enum)]
+        to: KeyCodecArg,
+        /// Write the converted database here instead of converting in place
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Upgrade the database to the on-disk format this build writes
+    UpgradeFormat {
+        /// Only report the steps that would run
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 fn main() -> Result<()> {
@@ -162,7 +185,9 @@ fn codec_label(id: Option<u8>) -> String {
 }
 
 fn is_internal_cf(name: &str) -> bool {
-    name == "__rocksmap_meta" || name.starts_with("__idx_")
+    name.starts_with("__rocksmap_")
+        || name.starts_with("__idx_")
+        || name.starts_with("__ttl_expiry")
 }
 
 /// Encode a CLI key string to its stored (ordered) bytes.
@@ -205,6 +230,7 @@ fn render_value(kind: MapKind, raw: &[u8], now: u64) -> Result<Option<String>> {
             None => return Ok(None),
             Some(p) => p,
         },
+        MapKind::Versioned => strip_version_envelope(raw).map_err(anyerr)?.1,
         _ => raw.to_vec(),
     };
     let shown = <BincodeCodec<String> as ValueCodec<String>>::decode(&payload)
@@ -212,6 +238,30 @@ fn render_value(kind: MapKind, raw: &[u8], now: u64) -> Result<Option<String>> {
     Ok(Some(shown))
 }
 
+/// Convert the key codec of a plain `K -> String` database, into `out` or in place.
+fn convert_keys<K>(
+    db: &Path,
+    to: KeyCodecArg,
+    out: Option<&Path>,
+) -> rocksmap::Result<convert::RekeyReport>
+where
+    K: Serialize + DeserializeOwned + OrderedKey,
+{
+    fn run<K: DeserializeOwned, FromKC: KeyCodec<K>, ToKC: KeyCodec<K>>(
+        db: &Path,
+        out: Option<&Path>,
+    ) -> rocksmap::Result<convert::RekeyReport> {
+        match out {
+            Some(out) => convert::rekey::<K, String, FromKC, ToKC>(db, out),
+            None => convert::rekey_in_place::<K, String, FromKC, ToKC>(db),
+        }
+    }
+    match to {
+        KeyCodecArg::Ordered => run::<K, BincodeCodec<K>, OrderedCodec<K>>(db, out),
+        KeyCodecArg::Bincode => run::<K, OrderedCodec<K>, BincodeCodec<K>>(db, out),
+    }
+}
+
 fn open_raw_read_only(path: &Path) -> Result<DB> {
     let opts = Options::default();
     let cfs = DB::list_cf(&opts, path).unwrap_or_else(|_| vec!["default".to_string()]);
@@ -223,6 +273,12 @@ fn open_raw_read_only(path: &Path) -> Result<DB> {
         .map_err(|e| anyhow!("failed to open database read-only: {e}"))
 }
 
+/// Open a plain database for a write with stand-in `String -> String` types, without recording
+/// them: the application owning the database records its own.
+fn open_plain(db: &Path) -> rocksmap::Result<RocksMap<String, String>> {
+    RocksMap::open_with(db, &OpenOptions::new().check_types_only())
+}
+
 fn require_plain(db: &Path) -> Result<()> {
     // A fresh (not-yet-created) database will be opened as plain by `RocksMap::open`.
     if !db.join("CURRENT").exists() {
@@ -274,6 +330,35 @@ fn cmd_info(db: &Path) -> Result<()> {
     if info.kind == MapKind::Indexed {
         println!("indexes:   {:?}", info.indexes);
     }
+    if let Some(types) = &info.types {
+        println!("types:     {types}");
+    }
+    if info.kind == MapKind::Ttl {
+        let layout = if info.ttl_expiry_index {
+            "__ttl_expiry (deadline, key)"
+        } else {
+            "none (built on next open)"
+        };
+        println!("expiry-index: {layout}");
+        if !info.ttl_tables.is_empty() {
+            println!("ttl-tables: {:?}", info.ttl_tables);
+        }
+    }
+    if let Some(version) = info.value_version {
+        println!("value-version: {version}");
+        let raw = open_raw_read_only(db)?;
+        let mut counts = std::collections::BTreeMap::<u16, u64>::new();
+        for item in raw.iterator(IteratorMode::Start) {
+            let (_, value) = item.map_err(|e| anyhow!("{e}"))?;
+            let (row_version, _) = strip_version_envelope(&value).map_err(anyerr)?;
+            *counts.entry(row_version).or_default() += 1;
+        }
+        let counts: Vec<String> = counts
+            .iter()
+            .map(|(row_version, rows)| format!("v{row_version}={rows}"))
+            .collect();
+        println!("rows-by-version: {}", counts.join(" "));
+    }
     let user_cfs: Vec<&String> = info
         .column_families
         .iter()
@@ -302,7 +387,7 @@ fn cmd_put(cli: &Cli, key: &str, value: &str) -> Result<()> {
     if cli.key_type != KeyType::String {
         bail!("writes currently support only --key-type string");
     }
-    let db = RocksMap::<String, String>::open(&cli.db).map_err(anyerr)?;
+    let db = open_plain(&cli.db).map_err(anyerr)?;
     db.put(key.to_string(), &value.to_string())
         .map_err(anyerr)?;
     eprintln!("stored `{key}`");
@@ -314,7 +399,7 @@ fn cmd_delete(cli: &Cli, key: &str) -> Result<()> {
     if cli.key_type != KeyType::String {
         bail!("deletes currently support only --key-type string");
     }
-    let db = RocksMap::<String, String>::open(&cli.db).map_err(anyerr)?;
+    let db = open_plain(&cli.db).map_err(anyerr)?;
     db.delete(&key.to_string()).map_err(anyerr)?;
     eprintln!("deleted `{key}`");
     Ok(())
@@ -402,7 +487,7 @@ fn cmd_import(cli: &Cli, source: &IoFormat) -> Result<()> {
     if cli.key_type != KeyType::String {
         bail!("import currently supports only --key-type string");
     }
-    let db = RocksMap::<String, String>::open(&cli.db).map_err(anyerr)?;
+    let db = open_plain(&cli.db).map_err(anyerr)?;
     let mut count = 0usize;
     match source {
         IoFormat::Json { file } => {
@@ -487,5 +572,46 @@ fn cmd_admin(cli: &Cli, cmd: &AdminCmd) -> Result<()> {
             }
             Ok(())
         }
+        AdminCmd::ConvertKeys { to, out } => {
+            let out = out.as_deref();
+            let report = match cli.key_type {
+                KeyType::String => convert_keys::<String>(&cli.db, *to, out),
+                KeyType::U64 => convert_keys::<u64>(&cli.db, *to, out),
+                KeyType::I64 => convert_keys::<i64>(&cli.db, *to, out),
+            }
+            .map_err(anyerr)?;
+            if report.resumed {
+                eprintln!("resumed an interrupted conversion");
+            }
+            eprintln!(
+                "converted {} rows in {:?}",
+                report.rows, report.column_families
+            );
+            Ok(())
+        }
+        AdminCmd::UpgradeFormat { dry_run } => {
+            let report = if *dry_run {
+                plan_format_upgrade(&cli.db)
+            } else {
+                upgrade_format(&cli.db)
+            }
+            .map_err(anyerr)?;
+            if report.is_current() {
+                println!("format version {} is current", report.to);
+                return Ok(());
+            }
+            if report.resumed {
+                println!("an interrupted upgrade is pending");
+            }
+            for step in &report.steps {
+                println!("step {step}");
+            }
+            if report.applied {
+                println!("upgraded format {} -> {}", report.from, report.to);
+            } else {
+                println!("dry run: format {} -> {}", report.from, report.to);
+            }
+            Ok(())
+        }
     }
 }
@@ -3,7 +3,10 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
 use predicates::str::contains;
-use rocksmap::{IndexedRocksMap, RocksMap, TtlRocksMap};
+use rocksmap::{
+    BincodeCodec, IndexedRocksMap, RocksMap, TtlRocksMap, Versioned, VersionedRocksMap,
+};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use tempfile::TempDir;
 
@@ -28,7 +31,7 @@ fn plain_put_get_list_info_roundtrip() {
         .arg("info")
         .assert()
         .success()
-        .stdout(contains("kind:      plain"));
+        .stdout(contains("kind:      plain").and(contains("types:").not()));
     cli(db)
         .arg("list")
         .assert()
@@ -36,6 +39,14 @@ fn plain_put_get_list_info_roundtrip() {
         .stdout(contains("hello\tworld"));
     // missing key exits non-zero
     cli(db).args(["get", "nope"]).assert().failure();
+
+    // The CLI's stand-in types were not recorded; the application's are.
+    drop(RocksMap::<String, String>::open(db).unwrap());
+    cli(db)
+        .arg("info")
+        .assert()
+        .success()
+        .stdout(contains("types:     str -> str"));
 }
 
 #[test]
@@ -52,7 +63,7 @@ fn refuses_writes_on_ttl_database() {
         .arg("info")
         .assert()
         .success()
-        .stdout(contains("kind:      ttl"));
+        .stdout(contains("kind:      ttl").and(contains("expiry-index: __ttl_expiry")));
 
     // A raw write must be refused (it would bypass envelope maintenance).
     cli(db)
@@ -140,3 +151,118 @@ fn export_then_import_roundtrip() {
         .success()
         .stdout(contains("v2"));
 }
+
+#[derive(Clone, Serialize, Deserialize)]
+struct NoteV1(String);
+
+impl Versioned for NoteV1 {
+    const VERSION: u16 = 1;
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct NoteV2(String);
+
+impl Versioned for NoteV2 {
+    const VERSION: u16 = 2;
+}
+
+#[test]
+fn info_reports_value_version_and_rows_per_version() {
+    let dir = TempDir::new().unwrap();
+    let db = dir.path();
+    {
+        let v1 = VersionedRocksMap::<String, NoteV1>::open(db).unwrap();
+        v1.put("a".into(), &NoteV1("old".into())).unwrap();
+        v1.put("b".into(), &NoteV1("old".into())).unwrap();
+    }
+    {
+        let v2 = VersionedRocksMap::<String, NoteV2>::builder(db)
+            .migrate(|old: NoteV1| NoteV2(old.0))
+            .open()
+            .unwrap();
+        v2.put("c".into(), &NoteV2("new".into())).unwrap();
+    }
+
+    cli(db).arg("info").assert().success().stdout(
+        contains("kind:      versioned")
+            .and(contains("value-version: 2"))
+            .and(contains("rows-by-version: v1=2 v2=1")),
+    );
+    cli(db)
+        .args(["get", "c"])
+        .assert()
+        .success()
+        .stdout(contains("new"));
+    cli(db).args(["put", "d", "x"]).assert().failure();
+}
+
+#[test]
+fn upgrade_format_reports_a_current_database() {
+    let dir = TempDir::new().unwrap();
+    let db = dir.path();
+    cli(db).args(["put", "k", "v"]).assert().success();
+
+    cli(db)
+        .args(["admin", "upgrade-format", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(contains("format version 1 is current"));
+    cli(db)
+        .args(["admin", "upgrade-format"])
+        .assert()
+        .success()
+        .stdout(contains("is current"));
+    cli(db).args(["get", "k"]).assert().success();
+}
+
+#[test]
+fn convert_keys_makes_a_bincode_keyed_table_ordered() {
+    let dir = TempDir::new().unwrap();
+    let db = dir.path().join("db");
+    {
+        let map = RocksMap::<u64, String, BincodeCodec<u64>>::open(&db).unwrap();
+        for n in [300u64, 2, 45] {
+            map.put(n, &format!("v{n}")).unwrap();
+        }
+    }
+
+    cli(&db)
+        .args([
+            "--key-type",
+            "u64",
+            "admin",
+            "convert-keys",
+            "--to",
+            "ordered",
+        ])
+        .assert()
+        .success()
+        .stderr(contains("converted 3 rows"));
+    cli(&db)
+        .arg("info")
+        .assert()
+        .success()
+        .stdout(contains("key-codec: ordered"));
+    cli(&db)
+        .args(["--key-type", "u64", "list"])
+        .assert()
+        .success()
+        .stdout(contains("2\tv2\n45\tv45\n300\tv300"));
+
+    // And back again, into a new database.
+    let out = dir.path().join("out");
+    cli(&db)
+        .args([
+            "--key-type",
+            "u64",
+            "admin",
+            "convert-keys",
+            "--to",
+            "bincode",
+            "--out",
+        ])
+        .arg(&out)
+        .assert()
+        .success();
+    assert!(RocksMap::<u64, String, BincodeCodec<u64>>::open(&out).is_ok());
+}
@@ -0,0 +1,316 @@
+//! `#[derive(OrderedKey)]` for rocksmap key types.
+//!
+//! Use it through `rocksmap` with the `derive` feature, which re-exports the macro next to the
+//! trait of the same name:
+//!
+//! ```ignore
+//! use rocksmap::OrderedKey;
+//!
+//! #[derive(OrderedKey)]
+//! struct OrderKey {
+//!     tenant: u32,
+//!     #[ordered_key(desc)]
+//!     created: u64,
+//!     id: u128,
+//! }
+//! ```
+//!
+//! - **Structs** encode their fields in declaration order, exactly like a tuple of the same
+//!   fields, so keys sort by the first field, then the second, and so on.
+//! - **Enums** encode the variant's index (one byte, in declaration order) followed by its
+//!   fields, so keys sort by variant first. At most 256 variants are supported.
+//! - `#[ordered_key(desc)]` on a field sorts that field in descending order (its encoding with
+//!   every byte inverted). A derived `Ord` does not know about this; a field of type
+//!   `rocksmap::Desc<T>` gets the same encoding and an `Ord` that agrees with it.
+//!
+//! `#[ordered_key(prefixes)]` on a non-generic struct with `n > 1` fields also generates prefix
+//! types `{Name}Prefix1` ..= `{Name}Prefix{n-1}`, with the struct's visibility, holding the
+//! leading fields. Their encoding is a byte prefix of the full key's, so
+//! `scan_prefix_fields(&OrderKeyPrefix1 { tenant: 7 })` visits every key of tenant 7
+//! (descending fields included).
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Field, Fields, Generics, Ident};
+
+/// Derive `rocksmap::OrderedKey`. See the crate documentation for the encoding.
+#[proc_macro_derive(OrderedKey, attributes(ordered_key))]
+pub fn derive_ordered_key(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// A field and whether it sorts descending.
+struct KeyField<'a> {
+    field: &'a Field,
+    desc: bool,
+}
+
+fn key_fields(fields: &Fields) -> syn::Result<Vec<KeyField<'_>>> {
+    fields
+        .iter()
+        .map(|field| {
+            let mut desc = false;
+            for attr in field
+                .attrs
+                .iter()
+                .filter(|a| a.path().is_ident("ordered_key"))
+            {
+                attr.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("desc") {
+                        desc = true;
+                        Ok(())
+                    } else {
+                        Err(meta.error("unsupported `ordered_key` attribute (expected `desc`)"))
+                    }
+                })?;
+            }
+            Ok(KeyField { field, desc })
+        })
+        .collect()
+}
+
+/// Whether the type asks for prefix types with `#[ordered_key(prefixes)]`.
+fn wants_prefixes(input: &DeriveInput) -> syn::Result<bool> {
+    let mut prefixes = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|a| a.path().is_ident("ordered_key"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("prefixes") {
+                prefixes = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported `ordered_key` attribute (expected `prefixes`)"))
+            }
+        })?;
+    }
+    if prefixes && !(matches!(input.data, Data::Struct(_)) && input.generics.params.is_empty()) {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "`#[ordered_key(prefixes)]` is only supported on non-generic structs",
+        ));
+    }
+    Ok(prefixes)
+}
+
+/// The binding for the `i`th field when destructuring.
+fn binding(index: usize, field: &Field) -> Ident {
+    match &field.ident {
+        Some(ident) => ident.clone(),
+        None => format_ident!("__field{}", index),
+    }
+}
+
+/// Statements appending each field (bound by reference under its [`binding`]) to `out`, with
+/// its ordered encoding or, if `collation`, its collation encoding.
+fn encode_fields(keys: &[KeyField<'_>], collation: bool) -> TokenStream2 {
+    let statements = keys.iter().enumerate().map(|(i, key)| {
+        let value = binding(i, key.field);
+        match (key.desc, collation) {
+            (true, false) => quote!(::rocksmap::__private::encode_desc(#value, out);),
+            (true, true) => quote!(::rocksmap::__private::encode_collation_desc(#value, out);),
+            (false, false) => quote!(::rocksmap::OrderedKey::encode_into(#value, out);),
+            (false, true) => quote!(::rocksmap::OrderedKey::encode_collation_into(#value, out);),
+        }
+    });
+    quote!(#(#statements)*)
+}
+
+/// A pattern destructuring `path` into its field bindings.
+fn pattern(path: TokenStream2, fields: &Fields, keys: &[KeyField<'_>]) -> TokenStream2 {
+    let bindings = keys
+        .iter()
+        .enumerate()
+        .map(|(i, key)| binding(i, key.field));
+    match fields {
+        Fields::Named(_) => quote!(#path { #(#bindings),* }),
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => path,
+    }
+}
+
+/// An expression building `path` from fields decoded in order (struct expression fields are
+/// evaluated in the order written).
+fn construct(path: TokenStream2, fields: &Fields, keys: &[KeyField<'_>]) -> TokenStream2 {
+    let values = keys.iter().map(|key| {
+        let ty = &key.field.ty;
+        if key.desc {
+            quote!(::rocksmap::__private::decode_desc::<#ty>(input)?)
+        } else {
+            quote!(<#ty as ::rocksmap::OrderedKey>::decode_from(input)?)
+        }
+    });
+    match fields {
+        Fields::Named(_) => {
+            let names = keys.iter().map(|key| &key.field.ident);
+            quote!(#path { #(#names: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#values),*)),
+        Fields::Unit => path,
+    }
+}
+
+fn ordered_key_impl(
+    name: &Ident,
+    generics: &Generics,
+    encode: TokenStream2,
+    collate: TokenStream2,
+    decode: TokenStream2,
+) -> TokenStream2 {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::rocksmap::OrderedKey));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics ::rocksmap::OrderedKey for #name #ty_generics #where_clause {
+            fn encode_into(&self, out: &mut ::std::vec::Vec<u8>) {
+                #encode
+            }
+
+            fn decode_from(input: &mut &[u8]) -> ::rocksmap::Result<Self> {
+                #decode
+            }
+
+            fn encode_collation_into(&self, out: &mut ::std::vec::Vec<u8>) {
+                #collate
+            }
+        }
+    }
+}
+
+fn struct_impl(
+    name: &Ident,
+    generics: &Generics,
+    fields: &Fields,
+    keys: &[KeyField<'_>],
+) -> TokenStream2 {
+    let destructure = pattern(quote!(Self), fields, keys);
+    let [encode, collate] = [false, true].map(|collation| {
+        let encode_fields = encode_fields(keys, collation);
+        quote! {
+            let #destructure = self;
+            #encode_fields
+        }
+    });
+    let built = construct(quote!(Self), fields, keys);
+    ordered_key_impl(
+        name,
+        generics,
+        encode,
+        collate,
+        quote!(::std::result::Result::Ok(#built)),
+    )
+}
+
+/// The `{Name}Prefix{k}` types of a struct, each holding its first `k` fields.
+fn prefix_types(input: &DeriveInput, fields: &Fields, keys: &[KeyField<'_>]) -> TokenStream2 {
+    let name = &input.ident;
+    let vis = &input.vis;
+    (1..keys.len())
+        .map(|len| {
+            let prefix_name = format_ident!("{}Prefix{}", name, len);
+            let doc = format!("The first {len} field(s) of [`{name}`], for prefix scans.");
+            let leading = &keys[..len];
+            let declared = leading.iter().map(|key| {
+                let docs = key.field.attrs.iter().filter(|a| a.path().is_ident("doc"));
+                let field_vis = &key.field.vis;
+                let ty = &key.field.ty;
+                match &key.field.ident {
+                    Some(ident) => quote!(#(#docs)* #field_vis #ident: #ty),
+                    None => quote!(#(#docs)* #field_vis #ty),
+                }
+            });
+            let body = match fields {
+                Fields::Named(_) => quote!({ #(#declared),* }),
+                _ => quote!((#(#declared),*);),
+            };
+            let implementation = struct_impl(&prefix_name, &Generics::default(), fields, leading);
+            quote! {
+                #[doc = #doc]
+                #vis struct #prefix_name #body
+
+                #implementation
+            }
+        })
+        .collect()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let prefixes = wants_prefixes(input)?;
+    match &input.data {
+        Data::Struct(data) => {
+            let keys = key_fields(&data.fields)?;
+            let mut tokens = struct_impl(name, &input.generics, &data.fields, &keys);
+            if prefixes {
+                tokens.extend(prefix_types(input, &data.fields, &keys));
+            }
+            Ok(tokens)
+        }
+        Data::Enum(data) => {
+            if data.variants.len() > 256 {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "#[derive(OrderedKey)] supports at most 256 enum variants",
+                ));
+            }
+            let mut encode_arms = Vec::new();
+            let mut collate_arms = Vec::new();
+            let mut decode_arms = Vec::new();
+            for (index, variant) in data.variants.iter().enumerate() {
+                let tag = index as u8;
+                let ident = &variant.ident;
+                let keys = key_fields(&variant.fields)?;
+                let destructure = pattern(quote!(Self::#ident), &variant.fields, &keys);
+                for (arms, collation) in [(&mut encode_arms, false), (&mut collate_arms, true)] {
+                    let encode_fields = encode_fields(&keys, collation);
+                    arms.push(quote! {
+                        #destructure => {
+                            out.push(#tag);
+                            #encode_fields
+                        }
+                    });
+                }
+                let built = construct(quote!(Self::#ident), &variant.fields, &keys);
+                decode_arms.push(quote!(#tag => ::std::result::Result::Ok(#built),));
+            }
+            let message = format!("invalid variant tag {{}} for `{name}` in ordered key");
+            let encode = quote! {
+                match self {
+                    #(#encode_arms)*
+                }
+            };
+            let collate = quote! {
+                match self {
+                    #(#collate_arms)*
+                }
+            };
+            let decode = quote! {
+                match <u8 as ::rocksmap::OrderedKey>::decode_from(input)? {
+                    #(#decode_arms)*
+                    other => ::std::result::Result::Err(
+                        ::rocksmap::Error::Deserialization(::std::format!(#message, other)),
+                    ),
+                }
+            };
+            Ok(ordered_key_impl(
+                name,
+                &input.generics,
+                encode,
+                collate,
+                decode,
+            ))
+        }
+        Data::Union(data) => Err(syn::Error::new_spanned(
+            data.union_token,
+            "#[derive(OrderedKey)] does not support unions",
+        )),
+    }
+}
@@ -0,0 +1,185 @@
+use rocksmap::{CaseInsensitive, OrderedCodec, OrderedKey, RocksMap};
+use serde::{Deserialize, Serialize};
+use tempfile::TempDir;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, OrderedKey)]
+#[ordered_key(prefixes)]
+struct EventKey {
+    tenant: u32,
+    #[ordered_key(desc)]
+    at: u64,
+    name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, OrderedKey)]
+struct Pair(String, i64);
+
+/// Without `#[ordered_key(prefixes)]` the derive generates no prefix types, so this name is free.
+#[allow(dead_code)]
+struct PairPrefix1;
+
+#[derive(Debug, Clone, PartialEq, OrderedKey)]
+struct Unit;
+
+#[derive(Debug, Clone, PartialEq, OrderedKey)]
+enum Shape {
+    Point,
+    Circle { radius: u32 },
+    Line(i32, #[ordered_key(desc)] i32),
+}
+
+#[derive(Debug, Clone, PartialEq, OrderedKey)]
+struct Wrapper<T> {
+    inner: T,
+    seq: u8,
+}
+
+fn encode<T: OrderedKey>(value: &T) -> Vec<u8> {
+    let mut out = Vec::new();
+    value.encode_into(&mut out);
+    out
+}
+
+fn roundtrip<T: OrderedKey + PartialEq + std::fmt::Debug>(value: T) {
+    let bytes = encode(&value);
+    let mut input = bytes.as_slice();
+    assert_eq!(T::decode_from(&mut input).unwrap(), value);
+    assert!(input.is_empty(), "{value:?} left {input:?}");
+}
+
+fn assert_sorted<T: OrderedKey + std::fmt::Debug>(values: &[T]) {
+    for pair in values.windows(2) {
+        assert!(
+            encode(&pair[0]) < encode(&pair[1]),
+            "{:?} should sort before {:?}",
+            pair[0],
+            pair[1]
+        );
+    }
+}
+
+#[test]
+fn every_shape_roundtrips() {
+    roundtrip(EventKey {
+        tenant: 3,
+        at: 42,
+        name: "login".to_string(),
+    });
+    roundtrip(Pair("a\0b".to_string(), -7));
+    roundtrip(Unit);
+    roundtrip(Shape::Point);
+    roundtrip(Shape::Circle { radius: 9 });
+    roundtrip(Shape::Line(-1, 5));
+    roundtrip(Wrapper {
+        inner: Pair("x".to_string(), 1),
+        seq: 2,
+    });
+}
+
+#[test]
+fn structs_sort_field_by_field_and_desc_fields_reverse() {
+    let key = |tenant, at, name: &str| EventKey {
+        tenant,
+        at,
+        name: name.to_string(),
+    };
+    assert_sorted(&[
+        key(1, 300, "b"),
+        key(1, 200, "a"),
+        key(1, 200, "b"),
+        key(1, 0, ""),
+        key(2, u64::MAX, ""),
+    ]);
+    assert_sorted(&[
+        Pair("a".to_string(), 5),
+        Pair("a".to_string(), 6),
+        Pair("ab".to_string(), -100),
+        Pair("b".to_string(), i64::MIN),
+    ]);
+}
+
+#[test]
+fn enums_sort_by_variant_then_fields() {
+    assert_sorted(&[
+        Shape::Point,
+        Shape::Circle { radius: 0 },
+        Shape::Circle { radius: 10 },
+        Shape::Line(-5, 9),
+        Shape::Line(-5, -9),
+        Shape::Line(3, 0),
+    ]);
+    let mut bad = [9u8].as_slice();
+    assert!(Shape::decode_from(&mut bad).is_err());
+}
+
+#[test]
+fn prefix_types_scan_leading_fields() {
+    let dir = TempDir::new().unwrap();
+    let map = RocksMap::<EventKey, u32>::open(dir.path()).unwrap();
+    for (tenant, at, name) in [(1, 10, "a"), (1, 20, "b"), (2, 30, "c"), (1, 20, "c")] {
+        let key = EventKey {
+            tenant,
+            at,
+            name: name.to_string(),
+        };
+        map.put(key, &tenant).unwrap();
+    }
+
+    let tenant1: Vec<(u64, String)> = map
+        .scan_prefix_fields(&EventKeyPrefix1 { tenant: 1 })
+        .unwrap()
+        .map(|r| {
+            let key = r.unwrap().0;
+            (key.at, key.name)
+        })
+        .collect();
+    assert_eq!(
+        tenant1,
+        vec![
+            (20, "b".to_string()),
+            (20, "c".to_string()),
+            (10, "a".to_string())
+        ]
+    );
+
+    let names: Vec<String> = map
+        .scan_prefix_fields(&EventKeyPrefix2 { tenant: 1, at: 20 })
+        .unwrap()
+        .map(|r| r.unwrap().0.name)
+        .collect();
+    assert_eq!(names, vec!["b", "c"]);
+
+    // The generated prefix encodes exactly like the leading fields of the key.
+    let key = EventKey {
+        tenant: 1,
+        at: 20,
+        name: "b".to_string(),
+    };
+    let full = <OrderedCodec<EventKey> as rocksmap::KeyCodec<EventKey>>::encode(&key).unwrap();
+    assert!(full.starts_with(&encode(&EventKeyPrefix2 { tenant: 1, at: 20 })));
+}
+
+#[derive(Debug, Clone, PartialEq, OrderedKey)]
+struct Handle {
+    domain: CaseInsensitive,
+    #[ordered_key(desc)]
+    user: CaseInsensitive,
+}
+
+#[test]
+fn collation_encoding_follows_the_fields() {
+    let handle = |domain: &str, user: &str| Handle {
+        domain: CaseInsensitive(domain.to_string()),
+        user: CaseInsensitive(user.to_string()),
+    };
+    let collate = |h: &Handle| {
+        let mut out = Vec::new();
+        h.encode_collation_into(&mut out);
+        out
+    };
+    let (a, b) = (handle("Example.com", "Ann"), handle("example.COM", "ann"));
+    assert_ne!(encode(&a), encode(&b));
+    assert_eq!(collate(&a), collate(&b));
+    assert!(collate(&handle("x", "b")) < collate(&handle("x", "a")));
+    roundtrip(a);
+}
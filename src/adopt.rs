@@ -0,0 +1,202 @@
+//! Bringing an existing, unmanaged RocksDB database under rocksmap.
+//!
+//! A typed open records its metadata in a fresh database, but refuses a database that already
+//! holds data without any: nothing would check that the existing bytes are `K -> V` under the
+//! declared key codec, and the first bad row would only surface in a later read. [`adopt`]
+//! makes that check explicit. It decodes the existing rows (all of them, or the first few of
+//! each column family), reports those that do not decode, and only when every checked row does,
+//! tags the database as a plain map so that [`RocksMap`](crate::RocksMap) opens it.
+//!
+//! ```no_run
+//! use rocksmap::{adopt, AdoptScan, OrderedCodec, RocksMap};
+//!
+//! let report = adopt::<u64, String, OrderedCodec<u64>, _>("./legacy.db", AdoptScan::Full)?;
+//! if report.adopted {
+//!     let map = RocksMap::<u64, String>::open("./legacy.db")?;
+//! }
+//! # Ok::<(), rocksmap::Error>(())
+//! ```
+
+use crate::codec::KeyCodec;
+use crate::error::{Error, Result};
+use crate::meta::{self, MapKind};
+use crate::schema::TypeFingerprint;
+use crate::verify::{decode_value_strict, ProblemKind, VerifyProblem};
+use rocksdb::{ColumnFamilyDescriptor, IteratorMode, Options, DB};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// How many existing rows [`adopt`] checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdoptScan {
+    /// Every row of every column family.
+    Full,
+    /// The first `n` rows in key order of each column family: a quick check that the database
+    /// holds the declared types, not a sample. Rows past the first `n` are not looked at, so a
+    /// bad row further on still adopts.
+    FirstRows(usize),
+}
+
+/// The outcome of [`adopt`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdoptReport {
+    /// Column families that were checked.
+    pub column_families: Vec<String>,
+    /// Rows examined.
+    pub rows_checked: u64,
+    /// Rows whose key or value does not decode.
+    pub problems: Vec<VerifyProblem>,
+    /// Whether the metadata was written (only if there were no problems).
+    pub adopted: bool,
+}
+
+/// Check that the existing rows of the unmanaged database at `path` decode as `K -> V` with key
+/// codec `KC`, and if they all do, record it as a plain map of those types.
+///
+/// Errors if `path` holds no database, or one already managed by rocksmap. Undecodable rows are
+/// not an error: they are listed in the report, and the database is left untouched.
+pub fn adopt<K, V, KC, P>(path: P, scan: AdoptScan) -> Result<AdoptReport>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    KC: KeyCodec<K>,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    // Only the existing column families: the metadata one is created once the rows check out.
+    let opts = Options::default();
+    let names = meta::existing_cfs(&opts, path);
+    let descriptors: Vec<ColumnFamilyDescriptor> = names
+        .iter()
+        .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+        .collect();
+    let mut db = DB::open_cf_descriptors(&opts, path, descriptors).map_err(Error::from)?;
+    if db.cf_handle(meta::META_CF).is_some() && meta::read_kind(&db)?.is_some() {
+        return Err(Error::FormatMismatch(format!(
+            "{} is already managed by rocksmap",
+            path.display()
+        )));
+    }
+
+    let limit = match scan {
+        AdoptScan::Full => usize::MAX,
+        AdoptScan::FirstRows(n) => n,
+    };
+    let mut report = AdoptReport {
+        column_families: Vec::new(),
+        rows_checked: 0,
+        problems: Vec::new(),
+        adopted: false,
+    };
+    for name in names.iter().filter(|name| !meta::is_internal_cf(name)) {
+        let cf = db
+            .cf_handle(name)
+            .ok_or_else(|| Error::ColumnFamilyNotFound(name.clone()))?;
+        report.column_families.push(name.clone());
+        for item in db.iterator_cf(cf, IteratorMode::Start).take(limit) {
+            let (key, value) = item.map_err(Error::from)?;
+            report.rows_checked += 1;
+            let kind = match (KC::decode(&key), decode_value_strict::<V>(&value)) {
+                (Err(e), _) => ProblemKind::UndecodableKey(e.to_string()),
+                (_, Err(e)) => ProblemKind::UndecodableValue(e.to_string()),
+                (Ok(_), Ok(_)) => continue,
+            };
+            report.problems.push(VerifyProblem {
+                column_family: name.clone(),
+                key: key.to_vec(),
+                kind,
+                quarantined: false,
+            });
+        }
+    }
+
+    if report.problems.is_empty() {
+        if db.cf_handle(meta::META_CF).is_none() {
+            db.create_cf(meta::META_CF, &Options::default())
+                .map_err(Error::from)?;
+        }
+        meta::write_adopted(&db, MapKind::Plain, KC::ID, &TypeFingerprint::of::<K, V>())?;
+        report.adopted = true;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BincodeCodec, OrderedCodec, RocksMap, ValueCodec};
+    use tempfile::TempDir;
+
+    fn raw_db(path: &Path, rows: &[(u64, &str)]) -> DB {
+        let db = DB::open_default(path).unwrap();
+        for (key, value) in rows {
+            let key = <OrderedCodec<u64> as KeyCodec<u64>>::encode(key).unwrap();
+            let value =
+                <BincodeCodec<String> as ValueCodec<String>>::encode(&value.to_string()).unwrap();
+            db.put(key, value).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn unmanaged_data_must_be_adopted_before_opening() {
+        let dir = TempDir::new().unwrap();
+        drop(raw_db(dir.path(), &[(1, "a"), (2, "b")]));
+
+        let err = RocksMap::<u64, String>::open(dir.path()).err().unwrap();
+        assert!(
+            matches!(err, Error::FormatMismatch(ref m) if m.contains("rocksmap::adopt")),
+            "{err}"
+        );
+
+        let report =
+            adopt::<u64, String, OrderedCodec<u64>, _>(dir.path(), AdoptScan::Full).unwrap();
+        assert!(report.adopted);
+        assert_eq!(report.rows_checked, 2);
+        assert_eq!(report.column_families, vec!["default"]);
+
+        let map = RocksMap::<u64, String>::open(dir.path()).unwrap();
+        assert_eq!(map.get(&2).unwrap(), Some("b".to_string()));
+        drop(map);
+        assert!(
+            adopt::<u64, String, OrderedCodec<u64>, _>(dir.path(), AdoptScan::Full).is_err(),
+            "already managed"
+        );
+    }
+
+    #[test]
+    fn rows_that_do_not_decode_block_adoption() {
+        let dir = TempDir::new().unwrap();
+        {
+            let db = raw_db(dir.path(), &[(1, "a"), (2, "b")]);
+            db.put(b"x", b"not a string").unwrap();
+        }
+
+        // Checking only the rows before the bad one would adopt; a full scan does not.
+        let report =
+            adopt::<u64, String, OrderedCodec<u64>, _>(dir.path(), AdoptScan::Full).unwrap();
+        assert!(!report.adopted);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].key, b"x".to_vec());
+        assert!(matches!(
+            report.problems[0].kind,
+            ProblemKind::UndecodableKey(_)
+        ));
+        let cfs = DB::list_cf(&Options::default(), dir.path()).unwrap();
+        assert_eq!(cfs, vec!["default"], "a failed adoption adds nothing");
+        assert!(RocksMap::<u64, String>::open(dir.path()).is_err());
+
+        let report =
+            adopt::<u64, String, OrderedCodec<u64>, _>(dir.path(), AdoptScan::FirstRows(2))
+                .unwrap();
+        assert!(report.adopted);
+        assert_eq!(report.rows_checked, 2);
+    }
+
+    #[test]
+    fn empty_databases_open_without_adoption() {
+        let dir = TempDir::new().unwrap();
+        drop(raw_db(dir.path(), &[]));
+        assert!(RocksMap::<u64, String>::open(dir.path()).is_ok());
+    }
+}
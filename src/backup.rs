@@ -0,0 +1,484 @@
+//! Incremental backups of rocksmap databases, built on RocksDB's `BackupEngine`.
+//!
+//! A backup directory holds any number of backups; files shared between them (immutable SST
+//! files) are stored once, so each new backup only copies what changed. Every backup also
+//! records the database's [`MapLayout`] — its kind and declared index set, as reported by
+//! [`inspect`](crate::inspect) — and restoring checks it against the layout the application
+//! expects, so a TTL backup cannot be restored under a plain map (or an indexed one with a
+//! different index set) by mistake.
+//!
+//! ```no_run
+//! use rocksmap::backup::{BackupEngine, MapLayout};
+//! use rocksmap::RocksMap;
+//!
+//! let map = RocksMap::<u64, String>::open("./app.db")?;
+//! let mut backups = BackupEngine::open("./app.backups")?;
+//! backups.create_backup(map.db())?;
+//! backups.purge_old_backups(5)?;
+//! drop(map);
+//!
+//! backups.restore_latest("./app.db", &MapLayout::new(rocksmap::MapKind::Plain))?;
+//! # Ok::<(), rocksmap::Error>(())
+//! ```
+
+use crate::error::{Error, Result};
+use crate::meta::{self, MapKind};
+use rocksdb::backup::{
+    BackupEngine as RawBackupEngine, BackupEngineOptions, RestoreOptions as RawRestoreOptions,
+};
+use rocksdb::{ColumnFamilyDescriptor, Env, Options, DB};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Subdirectory of the backup directory holding one layout record per backup id.
+const LAYOUT_DIR: &str = "rocksmap_layouts";
+
+/// The layout record of a backup being taken, renamed to the backup's id once it is committed.
+const PENDING_LAYOUT: &str = "pending";
+
+/// What kind of rocksmap database a backup holds: its map kind and declared index set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapLayout {
+    /// Plain, TTL, or indexed.
+    pub kind: MapKind,
+    /// Declared secondary-index names, sorted (empty unless `kind` is indexed).
+    pub indexes: Vec<String>,
+}
+
+impl MapLayout {
+    /// A layout of the given kind with no indexes.
+    pub fn new(kind: MapKind) -> Self {
+        MapLayout {
+            kind,
+            indexes: Vec::new(),
+        }
+    }
+
+    /// The layout of an [`IndexedRocksMap`](crate::IndexedRocksMap) declaring `names`.
+    pub fn indexed<S: AsRef<str>>(names: &[S]) -> Self {
+        let mut indexes: Vec<String> = names.iter().map(|n| n.as_ref().to_string()).collect();
+        indexes.sort();
+        MapLayout {
+            kind: MapKind::Indexed,
+            indexes,
+        }
+    }
+
+    fn read(db: &DB) -> Result<Self> {
+        let kind = meta::read_kind(db)?.ok_or_else(|| {
+            Error::FormatMismatch("not a rocksmap-managed database (no schema record)".to_string())
+        })?;
+        let mut indexes = meta::read_indexes(db)?;
+        indexes.sort();
+        Ok(MapLayout { kind, indexes })
+    }
+
+    fn check(&self, expected: &MapLayout) -> Result<()> {
+        if self == expected {
+            return Ok(());
+        }
+        Err(Error::FormatMismatch(format!(
+            "backup holds a {} map with indexes {:?}, but a {} map with indexes {:?} was expected",
+            self.kind, self.indexes, expected.kind, expected.indexes
+        )))
+    }
+}
+
+/// The on-disk form of a [`MapLayout`].
+#[derive(Serialize, Deserialize)]
+struct LayoutRecord {
+    kind: String,
+    indexes: Vec<String>,
+}
+
+/// One backup in a backup directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// Backup id; ids are assigned in increasing order.
+    pub id: u32,
+    /// When the backup was taken.
+    pub timestamp: SystemTime,
+    /// Total size of the backup's files in bytes. Files shared with other backups count toward
+    /// each of them, so sizes do not add up to the directory's size.
+    pub size: u64,
+    /// Number of files in the backup.
+    pub num_files: u32,
+    /// The map kind and index set the backed-up database had.
+    pub layout: MapLayout,
+}
+
+/// A directory of incremental backups.
+pub struct BackupEngine {
+    dir: PathBuf,
+    inner: RawBackupEngine,
+}
+
+impl BackupEngine {
+    /// Open the backup directory `dir`, creating it if needed.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(dir.join(LAYOUT_DIR))
+            .map_err(|_| Error::InvalidPath(dir.clone()))?;
+        let opts = BackupEngineOptions::new(&dir).map_err(Error::from)?;
+        let env = Env::new().map_err(Error::from)?;
+        let inner = RawBackupEngine::open(&opts, &env).map_err(Error::from)?;
+        let engine = BackupEngine { dir, inner };
+        engine.recover_pending()?;
+        Ok(engine)
+    }
+
+    /// Settle a layout record left pending by an interrupted backup: if the newest backup has no
+    /// record, the backup was committed and the pending record is its own; otherwise the backup
+    /// never happened and the record is dropped.
+    fn recover_pending(&self) -> Result<()> {
+        let pending = self.record_path(PENDING_LAYOUT);
+        if !pending.exists() {
+            return Ok(());
+        }
+        match self.latest_id() {
+            Some(id) if !self.layout_path(id).exists() => {
+                std::fs::rename(&pending, self.layout_path(id))
+            }
+            _ => std::fs::remove_file(&pending),
+        }
+        .map_err(|e| Error::Other(e.to_string()))
+    }
+
+    fn latest_id(&self) -> Option<u32> {
+        self.inner
+            .get_backup_info()
+            .iter()
+            .map(|info| info.backup_id)
+            .max()
+    }
+
+    /// Back up the open database `db` — e.g. [`RocksMap::db`](crate::RocksMap::db) or
+    /// [`TtlRocksMap::db`](crate::TtlRocksMap::db) — flushing memtables first. Only files not
+    /// already in the backup directory are copied.
+    pub fn create_backup(&mut self, db: &DB) -> Result<BackupInfo> {
+        self.write_pending(MapLayout::read(db)?)?;
+        self.inner
+            .create_new_backup_flush(db, true)
+            .map_err(Error::from)?;
+        self.record_latest()
+    }
+
+    /// Back up the database at `path` without opening it for writing. This is how to back up an
+    /// [`IndexedRocksMap`](crate::IndexedRocksMap), whose transactional handle the backup engine
+    /// cannot use; writes still in that database's memtables are captured through its WAL.
+    pub fn create_backup_from_path<P: AsRef<Path>>(&mut self, path: P) -> Result<BackupInfo> {
+        let path = path.as_ref();
+        let opts = Options::default();
+        let descriptors: Vec<ColumnFamilyDescriptor> = meta::existing_cfs(&opts, path)
+            .iter()
+            .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+            .collect();
+        let db = DB::open_cf_descriptors_read_only(&opts, path, descriptors, false)
+            .map_err(Error::from)?;
+        if db.cf_handle(meta::META_CF).is_none() {
+            return Err(Error::FormatMismatch(
+                "not a rocksmap-managed database (no metadata column family)".to_string(),
+            ));
+        }
+        self.write_pending(MapLayout::read(&db)?)?;
+        self.inner.create_new_backup(&db).map_err(Error::from)?;
+        self.record_latest()
+    }
+
+    /// Write the layout record of the backup about to be taken, atomically, so that a crash
+    /// leaves either no record or a whole one for [`recover_pending`](Self::recover_pending).
+    fn write_pending(&self, layout: MapLayout) -> Result<()> {
+        let record = LayoutRecord {
+            kind: layout.kind.as_str().to_string(),
+            indexes: layout.indexes,
+        };
+        let bytes = bincode::serialize(&record).map_err(|e| Error::Serialization(e.to_string()))?;
+        let tmp = self.record_path("pending.tmp");
+        std::fs::write(&tmp, bytes)
+            .and_then(|()| std::fs::rename(&tmp, self.record_path(PENDING_LAYOUT)))
+            .map_err(|e| Error::Other(e.to_string()))
+    }
+
+    /// Hand the pending layout record to the backup just committed.
+    fn record_latest(&mut self) -> Result<BackupInfo> {
+        let id = self
+            .latest_id()
+            .ok_or_else(|| Error::Other("backup engine reported no backups".to_string()))?;
+        std::fs::rename(self.record_path(PENDING_LAYOUT), self.layout_path(id))
+            .map_err(|e| Error::Other(e.to_string()))?;
+        self.info(id)
+    }
+
+    fn record_path(&self, name: &str) -> PathBuf {
+        self.dir.join(LAYOUT_DIR).join(name)
+    }
+
+    fn layout_path(&self, id: u32) -> PathBuf {
+        self.record_path(&id.to_string())
+    }
+
+    fn read_layout(&self, id: u32) -> Result<MapLayout> {
+        let bytes = std::fs::read(self.layout_path(id)).map_err(|_| {
+            Error::FormatMismatch(format!("backup {id} has no rocksmap layout record"))
+        })?;
+        let record: LayoutRecord =
+            bincode::deserialize(&bytes).map_err(|e| Error::Deserialization(e.to_string()))?;
+        let kind = MapKind::parse(&record.kind).ok_or_else(|| {
+            Error::FormatMismatch(format!(
+                "backup {id} records unknown map kind `{}`",
+                record.kind
+            ))
+        })?;
+        Ok(MapLayout {
+            kind,
+            indexes: record.indexes,
+        })
+    }
+
+    fn info(&self, id: u32) -> Result<BackupInfo> {
+        self.list_backups()?
+            .into_iter()
+            .find(|b| b.id == id)
+            .ok_or_else(|| Error::Other(format!("no backup with id {id}")))
+    }
+
+    /// Every backup in the directory, oldest first.
+    pub fn list_backups(&self) -> Result<Vec<BackupInfo>> {
+        let mut backups = self
+            .inner
+            .get_backup_info()
+            .into_iter()
+            .map(|info| {
+                Ok(BackupInfo {
+                    id: info.backup_id,
+                    timestamp: UNIX_EPOCH + Duration::from_secs(info.timestamp.max(0) as u64),
+                    size: info.size,
+                    num_files: info.num_files,
+                    layout: self.read_layout(info.backup_id)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        backups.sort_by_key(|b| b.id);
+        Ok(backups)
+    }
+
+    /// Check that backup `id` is intact: every file exists with the expected size, and its
+    /// layout record is readable.
+    pub fn verify_backup(&self, id: u32) -> Result<()> {
+        self.inner.verify_backup(id).map_err(Error::from)?;
+        self.read_layout(id).map(|_| ())
+    }
+
+    /// Delete all but the `keep_n` most recent backups. Files still shared with a kept backup
+    /// are retained.
+    pub fn purge_old_backups(&mut self, keep_n: usize) -> Result<()> {
+        self.inner.purge_old_backups(keep_n).map_err(Error::from)?;
+        let live: Vec<String> = self
+            .inner
+            .get_backup_info()
+            .iter()
+            .map(|info| info.backup_id.to_string())
+            .collect();
+        let entries = std::fs::read_dir(self.dir.join(LAYOUT_DIR))
+            .map_err(|e| Error::Other(e.to_string()))?;
+        for entry in entries.flatten() {
+            if !live.iter().any(|id| entry.file_name() == id.as_str()) {
+                std::fs::remove_file(entry.path()).map_err(|e| Error::Other(e.to_string()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Restore the most recent backup into `db_path`, replacing what is there. Fails with
+    /// [`Error::FormatMismatch`], before touching `db_path`, if the backup's layout differs from
+    /// `expected`. The database must not be open while it is restored.
+    pub fn restore_latest<P: AsRef<Path>>(
+        &mut self,
+        db_path: P,
+        expected: &MapLayout,
+    ) -> Result<BackupInfo> {
+        let latest = self
+            .list_backups()?
+            .pop()
+            .ok_or_else(|| Error::Other("backup directory holds no backups".to_string()))?;
+        self.restore(latest.id, db_path, expected)
+    }
+
+    /// Restore backup `id` into `db_path`; see [`restore_latest`](Self::restore_latest).
+    pub fn restore<P: AsRef<Path>>(
+        &mut self,
+        id: u32,
+        db_path: P,
+        expected: &MapLayout,
+    ) -> Result<BackupInfo> {
+        let info = self.info(id)?;
+        info.layout.check(expected)?;
+        let db_path = db_path.as_ref();
+        self.inner
+            .restore_from_backup(db_path, db_path, &RawRestoreOptions::default(), id)
+            .map_err(Error::from)?;
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IndexedRocksMap, RocksMap, TtlRocksMap};
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+
+    #[test]
+    fn incremental_backups_restore_by_id() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("db");
+        let mut engine = BackupEngine::open(dir.path().join("backups")).unwrap();
+
+        let map = RocksMap::<u64, String>::open(&db_path).unwrap();
+        map.put(1, &"one".to_string()).unwrap();
+        let first = engine.create_backup(map.db()).unwrap();
+        map.put(2, &"two".to_string()).unwrap();
+        let second = engine.create_backup(map.db()).unwrap();
+        drop(map);
+
+        assert!(second.id > first.id);
+        let listed = engine.list_backups().unwrap();
+        assert_eq!(listed, vec![first.clone(), second.clone()]);
+        assert_eq!(listed[0].layout, MapLayout::new(MapKind::Plain));
+        engine.verify_backup(first.id).unwrap();
+
+        let plain = MapLayout::new(MapKind::Plain);
+        engine.restore(first.id, &db_path, &plain).unwrap();
+        let map = RocksMap::<u64, String>::open(&db_path).unwrap();
+        assert_eq!(map.get(&1).unwrap(), Some("one".to_string()));
+        assert_eq!(map.get(&2).unwrap(), None);
+        drop(map);
+
+        let restored = engine.restore_latest(&db_path, &plain).unwrap();
+        assert_eq!(restored.id, second.id);
+        let map = RocksMap::<u64, String>::open(&db_path).unwrap();
+        assert_eq!(map.get(&2).unwrap(), Some("two".to_string()));
+    }
+
+    #[test]
+    fn purge_keeps_the_newest_backups() {
+        let dir = TempDir::new().unwrap();
+        let map = RocksMap::<u64, u64>::open(dir.path().join("db")).unwrap();
+        let mut engine = BackupEngine::open(dir.path().join("backups")).unwrap();
+        for i in 0..4 {
+            map.put(i, &i).unwrap();
+            engine.create_backup(map.db()).unwrap();
+        }
+
+        engine.purge_old_backups(2).unwrap();
+        let ids: Vec<u32> = engine
+            .list_backups()
+            .unwrap()
+            .iter()
+            .map(|b| b.id)
+            .collect();
+        assert_eq!(ids, vec![3, 4]);
+        assert_eq!(
+            std::fs::read_dir(dir.path().join("backups").join(LAYOUT_DIR))
+                .unwrap()
+                .count(),
+            2
+        );
+        assert!(engine.verify_backup(1).is_err());
+    }
+
+    #[test]
+    fn interrupted_backups_keep_the_directory_readable() {
+        let dir = TempDir::new().unwrap();
+        let backups = dir.path().join("backups");
+        let map = RocksMap::<u64, u64>::open(dir.path().join("db")).unwrap();
+        map.put(1, &1).unwrap();
+        let mut engine = BackupEngine::open(&backups).unwrap();
+        engine.create_backup(map.db()).unwrap();
+        let taken = engine.create_backup(map.db()).unwrap();
+        drop(engine);
+
+        // A crash after the backup was committed, before its record was renamed into place.
+        let records = backups.join(LAYOUT_DIR);
+        std::fs::rename(
+            records.join(taken.id.to_string()),
+            records.join(PENDING_LAYOUT),
+        )
+        .unwrap();
+        let engine = BackupEngine::open(&backups).unwrap();
+        assert_eq!(engine.list_backups().unwrap().last(), Some(&taken));
+        drop(engine);
+
+        // A crash before the backup was committed leaves a record for no backup.
+        std::fs::write(records.join(PENDING_LAYOUT), b"stale").unwrap();
+        let engine = BackupEngine::open(&backups).unwrap();
+        assert!(!records.join(PENDING_LAYOUT).exists());
+        assert_eq!(engine.list_backups().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn mismatched_layout_is_refused_before_restoring() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("db");
+        let mut engine = BackupEngine::open(dir.path().join("backups")).unwrap();
+        {
+            let ttl = TtlRocksMap::<u64, String>::open(&db_path).unwrap();
+            ttl.put(1, &"x".to_string()).unwrap();
+            engine.create_backup(ttl.db()).unwrap();
+        }
+        let target = dir.path().join("restored");
+
+        let err = engine
+            .restore_latest(&target, &MapLayout::new(MapKind::Plain))
+            .unwrap_err();
+        assert!(matches!(err, Error::FormatMismatch(_)), "{err}");
+        assert!(!target.exists());
+
+        engine
+            .restore_latest(&target, &MapLayout::new(MapKind::Ttl))
+            .unwrap();
+        let ttl = TtlRocksMap::<u64, String>::open(&target).unwrap();
+        assert_eq!(ttl.get(&1).unwrap(), Some("x".to_string()));
+    }
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct User {
+        email: String,
+    }
+
+    #[test]
+    fn indexed_maps_back_up_from_their_path() {
+        let dir = TempDir::new().unwrap();
+        let db_path = dir.path().join("db");
+        let open = |path: &Path| {
+            let mut builder = IndexedRocksMap::<u64, User>::builder(path);
+            builder.index("by_email", |u: &User| Some(u.email.clone()));
+            builder.open().unwrap()
+        };
+        let map = open(&db_path);
+        map.put(
+            1,
+            &User {
+                email: "a@example.com".to_string(),
+            },
+        )
+        .unwrap();
+
+        let mut engine = BackupEngine::open(dir.path().join("backups")).unwrap();
+        let info = engine.create_backup_from_path(&db_path).unwrap();
+        assert_eq!(info.layout, MapLayout::indexed(&["by_email"]));
+        drop(map);
+
+        let target = dir.path().join("restored");
+        let err = engine
+            .restore_latest(&target, &MapLayout::indexed(&["by_name"]))
+            .unwrap_err();
+        assert!(matches!(err, Error::FormatMismatch(_)), "{err}");
+        engine
+            .restore_latest(&target, &MapLayout::indexed(&["by_email"]))
+            .unwrap();
+        let map = open(&target);
+        assert_eq!(map.get(&1).unwrap().unwrap().email, "a@example.com");
+    }
+}
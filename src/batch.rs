@@ -1,9 +1,13 @@
+use crate::cache::CacheDelta;
 use crate::codec::{BincodeCodec, KeyCodec, ValueCodec};
 use crate::error::{Error, Result};
-use crate::ordered::OrderedCodec;
+use crate::ordered::{OrderedCodec, OrderedKey};
+use crate::ttl::{deadline_after, TtlRocksMap};
 use rocksdb::{WriteBatch, WriteOptions, DB};
 use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::time::Duration;
 
 /// A batch of write operations that can be committed atomically.
 ///
@@ -91,10 +95,121 @@ where
     }
 }
 
+/// A TTL row to be written: its expiry deadline and encoded value.
+type TtlEntry = (Option<u64>, Vec<u8>);
+
+/// A batch of writes to a [`TtlRocksMap`] that is committed atomically.
+///
+/// The clock is read once, when the batch is created, and every relative TTL in the batch is
+/// measured from that reading. Committing writes the rows and their expiry-index entries in one
+/// RocksDB write batch, so either all of them land or none do.
+pub struct TtlRocksMapBatch<'a, K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Serialize + DeserializeOwned + Clone,
+{
+    map: &'a TtlRocksMap<K, V>,
+    now: u64,
+    // Encoded key and, for a put, the expiry and payload; `None` is a delete.
+    ops: Vec<(Vec<u8>, Option<TtlEntry>)>,
+}
+
+impl<'a, K, V> TtlRocksMapBatch<'a, K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Serialize + DeserializeOwned + Clone,
+{
+    pub(crate) fn new(map: &'a TtlRocksMap<K, V>) -> Self {
+        Self {
+            map,
+            now: map.now(),
+            ops: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, key: &K, value: &V, expire_at: Option<u64>) -> Result<&mut Self> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        let payload = <BincodeCodec<V> as ValueCodec<V>>::encode(value)?;
+        self.ops.push((key_bytes, Some((expire_at, payload))));
+        Ok(self)
+    }
+
+    /// Add a put using the map's `default_ttl` (no expiry if none was configured).
+    pub fn put(&mut self, key: &K, value: &V) -> Result<&mut Self> {
+        let expire_at = self.map.default_expiry(self.now);
+        self.push(key, value, expire_at)
+    }
+
+    /// Add a put that expires `ttl` after the batch was created.
+    pub fn put_with_ttl(&mut self, key: &K, value: &V, ttl: Duration) -> Result<&mut Self> {
+        let expire_at = deadline_after(self.now, ttl);
+        self.push(key, value, Some(expire_at))
+    }
+
+    /// Add a put that expires at an absolute UNIX-millis deadline.
+    pub fn put_with_expiry(
+        &mut self,
+        key: &K,
+        value: &V,
+        expire_at_unix_millis: u64,
+    ) -> Result<&mut Self> {
+        self.push(key, value, Some(expire_at_unix_millis))
+    }
+
+    /// Add a delete operation to the batch
+    pub fn delete(&mut self, key: &K) -> Result<&mut Self> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        self.ops.push((key_bytes, None));
+        Ok(self)
+    }
+
+    /// Commit all operations in the batch atomically
+    pub fn commit(self) -> Result<()> {
+        // Only the last operation on each key reaches the database.
+        let last: HashMap<&[u8], usize> = self
+            .ops
+            .iter()
+            .enumerate()
+            .map(|(i, (key_bytes, _))| (key_bytes.as_slice(), i))
+            .collect();
+        let written: Vec<&[u8]> = last.keys().copied().collect();
+        {
+            let mut batch = WriteBatch::default();
+            let mut delta = CacheDelta::default();
+            let _guard = self.map.lock();
+            for (i, (key_bytes, entry)) in self.ops.iter().enumerate() {
+                if last[key_bytes.as_slice()] != i {
+                    continue;
+                }
+                let previous = self.map.stored_expiry(key_bytes)?;
+                let entry = entry
+                    .as_ref()
+                    .map(|(expire_at, payload)| (*expire_at, payload.as_slice()));
+                delta.add(self.map.stage(&mut batch, key_bytes, previous, entry)?);
+            }
+            self.map.stage_clock_mark(&mut batch);
+            let write_opts = WriteOptions::default();
+            self.map
+                .db()
+                .write_opt(batch, &write_opts)
+                .map_err(Error::from)?;
+            self.map.commit_cache_delta(delta);
+        }
+        self.map.enforce_capacity(&written)
+    }
+
+    /// Clears all operations in the batch without committing them
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::RocksMap;
+    use crate::{ManualClock, RocksMap, TtlRocksMap};
     use serde::{Deserialize, Serialize};
+    use std::sync::Arc;
+    use std::time::Duration;
     use tempfile::TempDir;
 
     #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -155,4 +270,62 @@ mod tests {
         assert!(db.get(&3).unwrap().is_some());
         assert!(db.get(&4).unwrap().is_some());
     }
+
+    #[test]
+    fn test_ttl_batch_uses_one_clock_reading() {
+        let temp_dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(1_000);
+        let db = TtlRocksMap::<String, String>::open_with_clock_and_default_ttl(
+            temp_dir.path(),
+            Arc::new(clock.clone()),
+            Duration::from_secs(60),
+        )
+        .unwrap();
+        db.put_with_ttl(
+            "stale".to_string(),
+            &"x".to_string(),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+
+        let mut batch = db.batch();
+        clock.advance(500);
+        batch
+            .put(&"session".to_string(), &"s".to_string())
+            .unwrap()
+            .put_with_ttl(
+                &"token".to_string(),
+                &"t".to_string(),
+                Duration::from_secs(5),
+            )
+            .unwrap()
+            .put_with_expiry(&"token".to_string(), &"t2".to_string(), 9_000)
+            .unwrap()
+            .delete(&"stale".to_string())
+            .unwrap();
+        assert_eq!(db.get(&"session".to_string()).unwrap(), None);
+        batch.commit().unwrap();
+
+        assert_eq!(db.expires_at(&"session".to_string()).unwrap(), Some(61_000));
+        assert_eq!(
+            db.get_with_expiry(&"token".to_string()).unwrap(),
+            Some(("t2".to_string(), Some(9_000)))
+        );
+        assert_eq!(db.get(&"stale".to_string()).unwrap(), None);
+        // The expiry index saw only the final state of each key.
+        let due: Vec<(String, u64)> = db.expiring_before(u64::MAX).map(|r| r.unwrap()).collect();
+        assert_eq!(
+            due,
+            vec![
+                ("token".to_string(), 9_000),
+                ("session".to_string(), 61_000)
+            ]
+        );
+
+        let mut batch = db.batch();
+        batch.delete(&"session".to_string()).unwrap();
+        batch.clear();
+        batch.commit().unwrap();
+        assert!(db.contains(&"session".to_string()).unwrap());
+    }
 }
@@ -0,0 +1,739 @@
+//! Size-bounded cache mode for [`TtlRocksMap`].
+//!
+//! Opened with [`TtlRocksMapBuilder::cache`](crate::TtlRocksMapBuilder::cache), a TTL map keeps
+//! its entries within an entry and/or byte budget: once a write takes it over budget, expired
+//! entries are removed first (handed to the expiry listeners, as the sweeper would), and only
+//! if that does not make room are live entries evicted, least recently used
+//! ([`EvictionPolicy::Lru`]) or least frequently used ([`EvictionPolicy::Lfu`]) first. The entry
+//! whose write triggered the eviction is evicted last.
+//!
+//! Access metadata lives in the internal `__rocksmap_cache` column family:
+//!
+//! ```text
+//! 'k' ++ key                  -> rank (u64 BE) ++ size (u64 BE)
+//! 'q' ++ rank (u64 BE) ++ key -> ()        the eviction queue, lowest rank first
+//! ```
+//!
+//! The rank is the time of last use (LRU) or the number of reads (LFU). Writes update it in the
+//! same atomic write as the row. Reads are counted in memory and written in batches of
+//! [`CacheOptions::access_batch`] keys, and before each eviction, so reads not yet written are
+//! lost in a crash. Sizes are the logical bytes of each key and stored value, not the on-disk
+//! footprint after compression. The budget is enforced after each write, so concurrent writers
+//! may overshoot it briefly.
+
+use crate::error::{Error, Result};
+use crate::meta;
+use crate::ordered::OrderedKey;
+use crate::ttl::TtlRocksMap;
+use rocksdb::{ColumnFamily, IteratorMode, Options, ReadOptions, WriteBatch, DB};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+const RANK_PREFIX: u8 = b'k';
+const QUEUE_PREFIX: u8 = b'q';
+
+/// Which entries a size-bounded [`TtlRocksMap`] evicts first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Least recently used: the entry read or written longest ago.
+    Lru,
+    /// Least frequently used: the entry read the fewest times since it was first stored.
+    Lfu,
+}
+
+impl EvictionPolicy {
+    fn tag(self) -> u8 {
+        match self {
+            EvictionPolicy::Lru => 1,
+            EvictionPolicy::Lfu => 2,
+        }
+    }
+}
+
+/// Budget and eviction policy of a [`TtlRocksMap`] in cache mode, for
+/// [`TtlRocksMapBuilder::cache`](crate::TtlRocksMapBuilder::cache).
+#[derive(Debug, Clone)]
+pub struct CacheOptions {
+    policy: EvictionPolicy,
+    max_entries: Option<u64>,
+    max_bytes: Option<u64>,
+    access_batch: usize,
+}
+
+impl CacheOptions {
+    /// Evict the least recently used entries first. Without a budget nothing is evicted.
+    pub fn lru() -> Self {
+        Self::with_policy(EvictionPolicy::Lru)
+    }
+
+    /// Evict the least frequently used entries first. Without a budget nothing is evicted.
+    pub fn lfu() -> Self {
+        Self::with_policy(EvictionPolicy::Lfu)
+    }
+
+    fn with_policy(policy: EvictionPolicy) -> Self {
+        Self {
+            policy,
+            max_entries: None,
+            max_bytes: None,
+            access_batch: 256,
+        }
+    }
+
+    /// Keep at most `max_entries` entries.
+    pub fn max_entries(mut self, max_entries: u64) -> Self {
+        self.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Keep the stored keys and values at most `max_bytes` long in total.
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Write the reads counted in memory to the access metadata once `keys` distinct keys have
+    /// been read (at least one). Defaults to 256.
+    pub fn access_batch(mut self, keys: usize) -> Self {
+        self.access_batch = keys.max(1);
+        self
+    }
+}
+
+/// Counters of a [`TtlRocksMap`] in cache mode, from [`TtlRocksMap::cache_stats`]. Hits, misses,
+/// and evictions are counted since the map was opened; entries and bytes are its current size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// `get` calls that found a live entry.
+    pub hits: u64,
+    /// `get` calls that found no entry, or an expired one.
+    pub misses: u64,
+    /// Live entries evicted to stay within budget (expired entries removed are not counted).
+    pub evictions: u64,
+    /// Entries stored, including expired ones not yet removed.
+    pub entries: u64,
+    /// Total size of the stored keys and values.
+    pub bytes: u64,
+}
+
+/// The running state of cache mode.
+pub(crate) struct CacheState {
+    options: CacheOptions,
+    entries: AtomicU64,
+    bytes: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+    // Reads not yet written: the latest read time (LRU) or the number of reads (LFU) per key.
+    pending: Mutex<HashMap<Vec<u8>, u64>>,
+}
+
+/// The change to the cache totals of writes staged in a batch, applied with
+/// [`TtlRocksMap::commit_cache_delta`] once the batch is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[must_use]
+pub(crate) struct CacheDelta {
+    entries: i64,
+    bytes: i64,
+}
+
+impl CacheDelta {
+    pub(crate) fn add(&mut self, other: CacheDelta) {
+        self.entries += other.entries;
+        self.bytes += other.bytes;
+    }
+}
+
+fn rank_key(key_bytes: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + key_bytes.len());
+    key.push(RANK_PREFIX);
+    key.extend_from_slice(key_bytes);
+    key
+}
+
+fn rank_record(rank: u64, size: u64) -> [u8; 16] {
+    let mut record = [0; 16];
+    record[..8].copy_from_slice(&rank.to_be_bytes());
+    record[8..].copy_from_slice(&size.to_be_bytes());
+    record
+}
+
+fn decode_rank_record(record: &[u8]) -> Result<(u64, u64)> {
+    let record: [u8; 16] = record
+        .try_into()
+        .map_err(|_| Error::FormatMismatch("corrupt cache rank record".to_string()))?;
+    let (rank, size) = record.split_at(8);
+    Ok((
+        u64::from_be_bytes(rank.try_into().expect("8 bytes")),
+        u64::from_be_bytes(size.try_into().expect("8 bytes")),
+    ))
+}
+
+fn queue_key(rank: u64, key_bytes: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(9 + key_bytes.len());
+    key.push(QUEUE_PREFIX);
+    key.extend_from_slice(&rank.to_be_bytes());
+    key.extend_from_slice(key_bytes);
+    key
+}
+
+fn split_queue_key(queue_key: &[u8]) -> Result<(u64, &[u8])> {
+    match queue_key {
+        [QUEUE_PREFIX, rest @ ..] if rest.len() >= 8 => {
+            let (rank, key_bytes) = rest.split_at(8);
+            Ok((
+                u64::from_be_bytes(rank.try_into().expect("8 bytes")),
+                key_bytes,
+            ))
+        }
+        _ => Err(Error::FormatMismatch(
+            "corrupt cache queue entry".to_string(),
+        )),
+    }
+}
+
+fn cache_cf(db: &DB) -> &ColumnFamily {
+    db.cf_handle(meta::CACHE_CF)
+        .expect("the cache metadata is created at open")
+}
+
+/// The records of `cf` whose key starts with `prefix`.
+fn scan_prefix<'a>(db: &'a DB, cf: &ColumnFamily, prefix: u8) -> rocksdb::DBIterator<'a> {
+    let mut readopts = ReadOptions::default();
+    readopts.set_iterate_lower_bound([prefix]);
+    readopts.set_iterate_upper_bound([prefix + 1]);
+    db.iterator_cf_opt(cf, readopts, IteratorMode::Start)
+}
+
+/// Prepare cache mode when a TTL map is opened with `options`, or retire it when opened without.
+/// The access metadata is rebuilt, ranking every stored row lowest, unless it was kept up to
+/// date for the same policy by the last open; the totals are counted from it.
+pub(crate) fn open_cache(db: &mut DB, options: Option<CacheOptions>) -> Result<Option<CacheState>> {
+    let Some(options) = options else {
+        // Writes from here on do not maintain the metadata, so a later open must rebuild it.
+        if meta::read_cache_policy(&*db)?.is_some() {
+            meta::clear_cache_policy(&*db)?;
+        }
+        if db.cf_handle(meta::CACHE_CF).is_some() {
+            db.drop_cf(meta::CACHE_CF).map_err(Error::from)?;
+        }
+        return Ok(None);
+    };
+
+    if meta::read_cache_policy(&*db)? != Some(options.policy.tag()) {
+        if db.cf_handle(meta::CACHE_CF).is_some() {
+            db.drop_cf(meta::CACHE_CF).map_err(Error::from)?;
+        }
+        db.create_cf(meta::CACHE_CF, &Options::default())
+            .map_err(Error::from)?;
+        let cf = cache_cf(db);
+        let mut batch = WriteBatch::default();
+        for item in db.iterator(IteratorMode::Start) {
+            let (key_bytes, value_bytes) = item.map_err(Error::from)?;
+            let size = (key_bytes.len() + value_bytes.len()) as u64;
+            batch.put_cf(cf, rank_key(&key_bytes), rank_record(0, size));
+            batch.put_cf(cf, queue_key(0, &key_bytes), []);
+            if batch.len() >= 10_000 {
+                db.write(std::mem::take(&mut batch)).map_err(Error::from)?;
+            }
+        }
+        db.write(batch).map_err(Error::from)?;
+        meta::write_cache_policy(&*db, options.policy.tag())?;
+    }
+
+    let (mut entries, mut bytes) = (0, 0);
+    for item in scan_prefix(db, cache_cf(db), RANK_PREFIX) {
+        let (_, record) = item.map_err(Error::from)?;
+        let (_, size) = decode_rank_record(&record)?;
+        entries += 1;
+        bytes += size;
+    }
+    Ok(Some(CacheState {
+        options,
+        entries: AtomicU64::new(entries),
+        bytes: AtomicU64::new(bytes),
+        hits: AtomicU64::new(0),
+        misses: AtomicU64::new(0),
+        evictions: AtomicU64::new(0),
+        pending: Mutex::new(HashMap::new()),
+    }))
+}
+
+fn adjust(counter: &AtomicU64, amount: i64) {
+    let _ = counter.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+        Some(n.saturating_add_signed(amount))
+    });
+}
+
+impl CacheState {
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::SeqCst),
+            misses: self.misses.load(Ordering::SeqCst),
+            evictions: self.evictions.load(Ordering::SeqCst),
+            entries: self.entries.load(Ordering::SeqCst),
+            bytes: self.bytes.load(Ordering::SeqCst),
+        }
+    }
+
+    fn over_budget(&self) -> bool {
+        let over = |limit: Option<u64>, counter: &AtomicU64| {
+            limit.is_some_and(|limit| counter.load(Ordering::SeqCst) > limit)
+        };
+        over(self.options.max_entries, &self.entries) || over(self.options.max_bytes, &self.bytes)
+    }
+
+    /// Add to `batch` the metadata changes of the row under `key_bytes` becoming `size` bytes
+    /// long at `now`, or being deleted if `None`, returning the change to the totals. The caller
+    /// holds the write lock.
+    pub(crate) fn stage(
+        &self,
+        db: &DB,
+        batch: &mut WriteBatch,
+        key_bytes: &[u8],
+        size: Option<u64>,
+        now: u64,
+    ) -> Result<CacheDelta> {
+        let cf = cache_cf(db);
+        let record_key = rank_key(key_bytes);
+        let previous = match db.get_cf(cf, &record_key).map_err(Error::from)? {
+            Some(record) => Some(decode_rank_record(&record)?),
+            None => None,
+        };
+        if let Some((rank, _)) = previous {
+            batch.delete_cf(cf, queue_key(rank, key_bytes));
+        }
+        match (size, previous) {
+            (Some(size), previous) => {
+                // A write is a use for LRU; LFU counts reads, starting from one.
+                let rank = match (self.options.policy, previous) {
+                    (EvictionPolicy::Lru, Some((rank, _))) => rank.max(now),
+                    (EvictionPolicy::Lru, None) => now,
+                    (EvictionPolicy::Lfu, Some((rank, _))) => rank,
+                    (EvictionPolicy::Lfu, None) => 1,
+                };
+                batch.put_cf(cf, &record_key, rank_record(rank, size));
+                batch.put_cf(cf, queue_key(rank, key_bytes), []);
+                Ok(match previous {
+                    Some((_, old)) => CacheDelta {
+                        entries: 0,
+                        bytes: size as i64 - old as i64,
+                    },
+                    None => CacheDelta {
+                        entries: 1,
+                        bytes: size as i64,
+                    },
+                })
+            }
+            (None, Some((_, old))) => {
+                batch.delete_cf(cf, &record_key);
+                Ok(CacheDelta {
+                    entries: -1,
+                    bytes: -(old as i64),
+                })
+            }
+            (None, None) => Ok(CacheDelta::default()),
+        }
+    }
+
+    /// Count a read of `key_bytes` at `now`. Returns `true` once enough keys are pending that
+    /// the reads should be written.
+    fn record_read(&self, key_bytes: &[u8], now: u64) -> bool {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let uses = pending.entry(key_bytes.to_vec()).or_insert(0);
+        *uses = match self.options.policy {
+            EvictionPolicy::Lru => (*uses).max(now),
+            EvictionPolicy::Lfu => *uses + 1,
+        };
+        pending.len() >= self.options.access_batch
+    }
+
+    fn take_pending(&self) -> HashMap<Vec<u8>, u64> {
+        std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Add to `batch` the new rank of a key read `uses` (see `pending`), if it is still stored.
+    fn stage_reads(
+        &self,
+        db: &DB,
+        batch: &mut WriteBatch,
+        key_bytes: &[u8],
+        uses: u64,
+    ) -> Result<()> {
+        let cf = cache_cf(db);
+        let record_key = rank_key(key_bytes);
+        let Some(record) = db.get_cf(cf, &record_key).map_err(Error::from)? else {
+            return Ok(());
+        };
+        let (rank, size) = decode_rank_record(&record)?;
+        let updated = match self.options.policy {
+            EvictionPolicy::Lru => rank.max(uses),
+            EvictionPolicy::Lfu => rank.saturating_add(uses),
+        };
+        if updated != rank {
+            batch.delete_cf(cf, queue_key(rank, key_bytes));
+            batch.put_cf(cf, queue_key(updated, key_bytes), []);
+            batch.put_cf(cf, &record_key, rank_record(updated, size));
+        }
+        Ok(())
+    }
+}
+
+impl<K, V> TtlRocksMap<K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Serialize + DeserializeOwned + Clone,
+{
+    /// Hit, miss, and eviction counters and the current size, if the map was opened in cache
+    /// mode (see [`TtlRocksMapBuilder::cache`](crate::TtlRocksMapBuilder::cache)).
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.cache().map(CacheState::stats)
+    }
+
+    /// Apply the change to the cache totals of a batch of writes, once it is written.
+    pub(crate) fn commit_cache_delta(&self, delta: CacheDelta) {
+        if let Some(cache) = self.cache() {
+            adjust(&cache.entries, delta.entries);
+            adjust(&cache.bytes, delta.bytes);
+        }
+    }
+
+    /// Count a `get` of `key_bytes` at `now` that found a live entry or not.
+    pub(crate) fn note_read(&self, key_bytes: &[u8], hit: bool, now: u64) -> Result<()> {
+        let Some(cache) = self.cache() else {
+            return Ok(());
+        };
+        if !hit {
+            cache.misses.fetch_add(1, Ordering::SeqCst);
+            return Ok(());
+        }
+        cache.hits.fetch_add(1, Ordering::SeqCst);
+        if cache.record_read(key_bytes, now) {
+            self.flush_reads(cache)?;
+        }
+        Ok(())
+    }
+
+    /// Write the reads counted in memory to the access metadata.
+    fn flush_reads(&self, cache: &CacheState) -> Result<()> {
+        let pending = cache.take_pending();
+        if pending.is_empty() {
+            return Ok(());
+        }
+        let _guard = self.lock();
+        let mut batch = WriteBatch::default();
+        for (key_bytes, uses) in &pending {
+            cache.stage_reads(self.db(), &mut batch, key_bytes, *uses)?;
+        }
+        self.db().write(batch).map_err(Error::from)
+    }
+
+    /// Bring the map back within its budget after a write of the keys `written`: remove expired
+    /// entries, then evict live ones by policy, `written` last.
+    pub(crate) fn enforce_capacity(&self, written: &[&[u8]]) -> Result<()> {
+        let Some(cache) = self.cache() else {
+            return Ok(());
+        };
+        if !cache.over_budget() {
+            return Ok(());
+        }
+        let mut cursor = None;
+        loop {
+            let (_, done) = self.sweep_batch(&mut cursor, 256)?;
+            if done || !cache.over_budget() {
+                break;
+            }
+        }
+        if cache.over_budget() {
+            self.flush_reads(cache)?;
+            while self.evict_one(cache, written)? {}
+        }
+        Ok(())
+    }
+
+    /// Evict the lowest-ranked entry, preferring any not in `spared`, if the map is still over
+    /// budget. Returns whether there may be more to do. Queue entries left behind by a row the
+    /// compaction filter already dropped are cleaned up along the way.
+    fn evict_one(&self, cache: &CacheState, spared: &[&[u8]]) -> Result<bool> {
+        let _guard = self.lock();
+        if !cache.over_budget() {
+            return Ok(false);
+        }
+        let db = self.db();
+        let cf = cache_cf(db);
+        let mut fallback = None;
+        let mut victim = None;
+        for item in scan_prefix(db, cf, QUEUE_PREFIX) {
+            let (queue_key, _) = item.map_err(Error::from)?;
+            let (_, key_bytes) = split_queue_key(&queue_key)?;
+            if !spared.contains(&key_bytes) {
+                victim = Some(queue_key.into_vec());
+                break;
+            }
+            if fallback.is_none() {
+                fallback = Some(queue_key.into_vec());
+            }
+        }
+        let Some(queue_key) = victim.or(fallback) else {
+            return Ok(false);
+        };
+
+        let (rank, key_bytes) = split_queue_key(&queue_key)?;
+        let current = match db.get_cf(cf, rank_key(key_bytes)).map_err(Error::from)? {
+            Some(record) => Some(decode_rank_record(&record)?.0),
+            None => None,
+        };
+        if current != Some(rank) {
+            db.delete_cf(cf, &queue_key).map_err(Error::from)?;
+            return Ok(true);
+        }
+        if db
+            .get_cf(self.data_cf(), key_bytes)
+            .map_err(Error::from)?
+            .is_none()
+        {
+            let mut batch = WriteBatch::default();
+            let delta = cache.stage(db, &mut batch, key_bytes, None, self.now())?;
+            db.write(batch).map_err(Error::from)?;
+            self.commit_cache_delta(delta);
+            return Ok(true);
+        }
+        let previous = self.stored_expiry(key_bytes)?;
+        self.write(key_bytes, previous, None)?;
+        cache.evictions.fetch_add(1, Ordering::SeqCst);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::{KeyCodec, OrderedCodec};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn cache_map(
+        dir: &TempDir,
+        clock: &ManualClock,
+        options: CacheOptions,
+    ) -> TtlRocksMap<String, String> {
+        TtlRocksMap::builder(dir.path())
+            .clock(Arc::new(clock.clone()))
+            .cache(options)
+            .open()
+            .unwrap()
+    }
+
+    fn keys(map: &TtlRocksMap<String, String>) -> Vec<String> {
+        map.iter().map(|item| item.unwrap().0).collect()
+    }
+
+    #[test]
+    fn lru_evicts_the_least_recently_used() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = cache_map(&dir, &clock, CacheOptions::lru().max_entries(3));
+        for name in ["a", "b", "c"] {
+            clock.advance(1);
+            map.put(name.to_string(), &name.to_string()).unwrap();
+        }
+        clock.advance(1);
+        assert_eq!(map.get(&"a".to_string()).unwrap(), Some("a".to_string()));
+        assert_eq!(map.get(&"z".to_string()).unwrap(), None);
+        clock.advance(1);
+        map.put("d".to_string(), &"d".to_string()).unwrap();
+
+        assert_eq!(keys(&map), ["a", "c", "d"]);
+        assert_eq!(
+            map.cache_stats().unwrap(),
+            CacheStats {
+                hits: 1,
+                misses: 1,
+                evictions: 1,
+                entries: 3,
+                // Each a 3-byte encoded key and a 10-byte envelope around a bincode string.
+                bytes: 3 * 13,
+            }
+        );
+    }
+
+    #[test]
+    fn lfu_evicts_the_least_frequently_read_but_not_the_new_entry() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = cache_map(
+            &dir,
+            &clock,
+            CacheOptions::lfu().max_entries(2).access_batch(8),
+        );
+        map.put("hot".to_string(), &"h".to_string()).unwrap();
+        map.put("cold".to_string(), &"c".to_string()).unwrap();
+        for _ in 0..3 {
+            map.get(&"hot".to_string()).unwrap();
+        }
+        map.get(&"cold".to_string()).unwrap();
+        map.put("new".to_string(), &"n".to_string()).unwrap();
+        assert_eq!(keys(&map), ["hot", "new"]);
+
+        // With the budget full of frequently read entries, the new one is still kept.
+        map.put("newer".to_string(), &"n".to_string()).unwrap();
+        assert_eq!(keys(&map), ["hot", "newer"]);
+        assert_eq!(map.cache_stats().unwrap().evictions, 2);
+    }
+
+    #[test]
+    fn totals_change_only_once_a_write_is_committed() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = cache_map(&dir, &clock, CacheOptions::lru().max_entries(10));
+        map.put("a".to_string(), &"x".to_string()).unwrap();
+        let before = map.cache_stats().unwrap();
+
+        let key = |k: &str| OrderedCodec::<String>::encode(&k.to_string()).unwrap();
+        let _guard = map.lock();
+        let mut batch = WriteBatch::default();
+        let mut delta = map
+            .stage(&mut batch, &key("b"), None, Some((None, b"payload")))
+            .unwrap();
+        delta.add(map.stage(&mut batch, &key("a"), None, None).unwrap());
+        assert_eq!(map.cache_stats().unwrap(), before, "nothing is written yet");
+
+        map.db().write(batch).unwrap();
+        map.commit_cache_delta(delta);
+        let after = map.cache_stats().unwrap();
+        assert_eq!(after.entries, 1);
+        assert_ne!(after.bytes, before.bytes);
+    }
+
+    #[test]
+    fn rows_the_compaction_filter_dropped_are_not_evicted_in_place_of_live_ones() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = cache_map(&dir, &clock, CacheOptions::lru().max_entries(2));
+        clock.advance(1);
+        map.put("a".to_string(), &"a".to_string()).unwrap();
+        clock.advance(1);
+        map.put_with_ttl("b".to_string(), &"b".to_string(), Duration::from_millis(5))
+            .unwrap();
+        clock.advance(10);
+        map.db().flush().unwrap();
+        map.compact();
+        assert_eq!(map.cache_stats().unwrap().entries, 2);
+
+        map.put("c".to_string(), &"c".to_string()).unwrap();
+        assert_eq!(keys(&map), ["a", "c"]);
+        let stats = map.cache_stats().unwrap();
+        assert_eq!((stats.entries, stats.evictions), (2, 0));
+    }
+
+    #[test]
+    fn compact_expired_drops_the_cache_metadata_of_reclaimed_rows() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = cache_map(&dir, &clock, CacheOptions::lru().max_entries(10));
+        map.put("a".to_string(), &"a".to_string()).unwrap();
+        for name in ["b", "c"] {
+            map.put_with_ttl(
+                name.to_string(),
+                &name.to_string(),
+                Duration::from_millis(5),
+            )
+            .unwrap();
+        }
+        clock.advance(10);
+        map.db().flush().unwrap();
+        assert_eq!(map.compact_expired(1).unwrap(), 1);
+        let stats = map.cache_stats().unwrap();
+        assert_eq!((stats.entries, stats.bytes), (1, 13));
+    }
+
+    #[test]
+    fn byte_budget_counts_keys_and_values() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = cache_map(&dir, &clock, CacheOptions::lru().max_bytes(1_000));
+        for i in 0..20 {
+            clock.advance(1);
+            map.put(format!("{i:02}"), &"x".repeat(100)).unwrap();
+        }
+        // Each entry takes 113 bytes: a 4-byte key and a 109-byte envelope.
+        let stats = map.cache_stats().unwrap();
+        assert_eq!((stats.entries, stats.bytes), (8, 8 * 113));
+        assert_eq!(keys(&map).first().map(String::as_str), Some("12"));
+
+        // Overwriting with a smaller value frees room without evicting.
+        map.put("19".to_string(), &String::new()).unwrap();
+        map.put("20".to_string(), &"x".repeat(10)).unwrap();
+        assert_eq!(map.cache_stats().unwrap().entries, 9);
+    }
+
+    #[test]
+    fn expired_entries_make_room_before_live_ones_are_evicted() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let (sender, expired) = std::sync::mpsc::channel();
+        let map = TtlRocksMap::<String, String>::builder(dir.path())
+            .clock(Arc::new(clock.clone()))
+            .notify_expired(sender)
+            .cache(CacheOptions::lru().max_entries(2))
+            .open()
+            .unwrap();
+        map.put("old".to_string(), &"o".to_string()).unwrap();
+        clock.advance(1);
+        map.put_with_ttl(
+            "lease".to_string(),
+            &"l".to_string(),
+            Duration::from_millis(10),
+        )
+        .unwrap();
+        clock.advance(10);
+        map.put("new".to_string(), &"n".to_string()).unwrap();
+
+        // The least recently used entry is `old`, but the expired lease goes instead.
+        assert_eq!(keys(&map), ["new", "old"]);
+        assert_eq!(
+            expired.try_recv().unwrap(),
+            ("lease".to_string(), "l".to_string())
+        );
+        assert_eq!(map.cache_stats().unwrap().evictions, 0);
+        assert_eq!(map.get(&"lease".to_string()).unwrap(), None);
+        assert_eq!(map.cache_stats().unwrap().misses, 1);
+    }
+
+    #[test]
+    fn access_metadata_survives_reopen_and_is_rebuilt_when_stale() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        {
+            let map = cache_map(&dir, &clock, CacheOptions::lru().max_entries(3));
+            for name in ["a", "b", "c"] {
+                clock.advance(1);
+                map.put(name.to_string(), &name.to_string()).unwrap();
+            }
+        }
+        {
+            let map = cache_map(&dir, &clock, CacheOptions::lru().max_entries(3));
+            assert_eq!(map.cache_stats().unwrap().entries, 3);
+            clock.advance(1);
+            map.put("d".to_string(), &"d".to_string()).unwrap();
+            assert_eq!(keys(&map), ["b", "c", "d"]);
+        }
+        {
+            // Written without cache mode: the metadata is dropped ...
+            let map = TtlRocksMap::<String, String>::open(dir.path()).unwrap();
+            assert!(map.cache_stats().is_none());
+            assert!(map.db().cf_handle(meta::CACHE_CF).is_none());
+            map.put("e".to_string(), &"e".to_string()).unwrap();
+        }
+        // ... and rebuilt on the next open in cache mode, before enforcing the budget.
+        let map = cache_map(&dir, &clock, CacheOptions::lfu().max_entries(3));
+        assert_eq!(map.cache_stats().unwrap().entries, 4);
+        clock.advance(1);
+        map.put("f".to_string(), &"f".to_string()).unwrap();
+        assert_eq!(keys(&map), ["d", "e", "f"]);
+    }
+}
@@ -0,0 +1,450 @@
+//! Typed change-data-capture (CDC) by tailing the write-ahead log.
+//!
+//! Every write RocksDB applies is assigned a monotonically increasing **sequence number** and
+//! logged in the WAL. [`RocksMap::changes_since`](crate::RocksMap::changes_since) (and the
+//! [`TtlRocksMap`](crate::TtlRocksMap) equivalent) replays the writes made after a given
+//! sequence number as typed [`Change`] records, so a consumer can mirror a database into another
+//! system (a search index, a cache, a follower) without scanning it.
+//!
+//! - Internal writes (`__rocksmap_meta`, `__idx_*`, `__ttl_expiry*`) are skipped; every other
+//!   column family is decoded with the map's key/value types and tagged with its name.
+//! - TTL envelopes are unwrapped: a put carries the payload and its expiry deadline.
+//! - [`ChangeStream::checkpoint`] is a resumable position. Persist it (it is `Serialize`, and
+//!   has a compact [`to_bytes`](ChangeCheckpoint::to_bytes) form) and pass its
+//!   [`sequence`](ChangeCheckpoint::sequence) to `changes_since` after a restart.
+//!
+//! **WAL retention.** RocksDB deletes WAL files once their data is flushed, unless told to
+//! archive them. A consumer that may fall behind must open the database with a retention policy
+//! (`Options::set_wal_ttl_seconds` / `set_wal_size_limit_mb`); resuming from a sequence whose
+//! WAL has been purged fails with [`Error::ChangesUnavailable`] rather than silently skipping
+//! changes.
+
+use crate::codec::{BincodeCodec, KeyCodec, ValueCodec};
+use crate::error::{Error, Result};
+use crate::meta;
+use crate::ordered::OrderedCodec;
+use crate::ttl::decode_envelope;
+use crate::wal::{self, WalOp, WalTail};
+use rocksdb::{ColumnFamily, WriteBatch, DB};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+
+/// One typed change read from the write-ahead log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<K, V> {
+    /// The sequence number RocksDB assigned to this write.
+    pub sequence: u64,
+    /// Name of the column family written (`"default"` for the map's own data).
+    pub column_family: String,
+    /// What happened to the key.
+    pub op: ChangeOp<K, V>,
+}
+
+/// The operation carried by a [`Change`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChangeOp<K, V> {
+    /// `key` was set to `value`.
+    Put {
+        /// The key written.
+        key: K,
+        /// The value written (for a TTL map, the payload inside the envelope).
+        value: V,
+        /// UNIX-millis expiry deadline for TTL maps; `None` for no expiry or a plain map.
+        expires_at: Option<u64>,
+    },
+    /// `key` was deleted.
+    Delete {
+        /// The key deleted.
+        key: K,
+    },
+}
+
+impl<K, V> ChangeOp<K, V> {
+    /// The key this operation applies to.
+    pub fn key(&self) -> &K {
+        match self {
+            ChangeOp::Put { key, .. } | ChangeOp::Delete { key } => key,
+        }
+    }
+}
+
+/// A resumable position in the change stream: every change with a sequence number up to and
+/// including [`sequence`](Self::sequence) has been delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ChangeCheckpoint {
+    sequence: u64,
+}
+
+impl ChangeCheckpoint {
+    /// A checkpoint positioned after `sequence`.
+    pub fn new(sequence: u64) -> Self {
+        ChangeCheckpoint { sequence }
+    }
+
+    /// The last sequence number delivered; pass it to `changes_since` to resume.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Compact 8-byte big-endian encoding for persisting the checkpoint.
+    pub fn to_bytes(&self) -> [u8; 8] {
+        self.sequence.to_be_bytes()
+    }
+
+    /// Decode a checkpoint written by [`to_bytes`](Self::to_bytes).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let arr: [u8; 8] = bytes.try_into().map_err(|_| {
+            Error::Deserialization(format!(
+                "change checkpoint must be 8 bytes, got {}",
+                bytes.len()
+            ))
+        })?;
+        Ok(ChangeCheckpoint::new(u64::from_be_bytes(arr)))
+    }
+}
+
+/// RocksDB's numeric id for a column family.
+///
+/// The `rocksdb` crate does not expose it, but a write batch records it: encode a probe put
+/// against the handle (never committed) and read the id back out of the batch.
+pub(crate) fn cf_id(cf: &ColumnFamily) -> Result<u32> {
+    let mut probe = WriteBatch::default();
+    probe.put_cf(cf, b"", b"");
+    let records = wal::decode_batch(probe.data())?;
+    records
+        .first()
+        .map(|r| r.cf_id)
+        .ok_or_else(|| Error::Other("empty column-family probe batch".to_string()))
+}
+
+/// Map every column family currently open on `db` from its numeric id to its name.
+pub(crate) fn cf_names_by_id(db: &DB) -> Result<HashMap<u32, String>> {
+    let mut names = HashMap::new();
+    for name in meta::existing_cfs(&rocksdb::Options::default(), db.path()) {
+        if let Some(cf) = db.cf_handle(&name) {
+            names.insert(cf_id(cf)?, name);
+        }
+    }
+    Ok(names)
+}
+
+/// Iterator over the typed changes made after a sequence number. Created by
+/// [`RocksMap::changes_since`](crate::RocksMap::changes_since) or
+/// [`TtlRocksMap::changes_since`](crate::TtlRocksMap::changes_since).
+///
+/// Internal writes (`__rocksmap_meta`, `__idx_*`, `__ttl_expiry*`) are skipped; every other
+/// column family is decoded with the map's key/value types. The stream covers the writes that
+/// existed when each batch was read; once it returns `None` it is caught up, and a new stream from
+/// [`checkpoint`](Self::checkpoint) picks up later writes. A consumer that may fall behind needs
+/// the WAL retained; see [the module docs](crate::cdc).
+pub struct ChangeStream<'a, K, V, KC = OrderedCodec<K>> {
+    db: &'a DB,
+    inner: Option<WalTail>,
+    ttl: bool,
+    cf_names: HashMap<u32, String>,
+    /// Last sequence fully delivered (the checkpoint).
+    position: u64,
+    /// Last sequence of the batch currently being drained.
+    batch_end: u64,
+    pending: VecDeque<Change<K, V>>,
+    _marker: PhantomData<KC>,
+}
+
+impl<'a, K, V, KC> ChangeStream<'a, K, V, KC>
+where
+    KC: KeyCodec<K>,
+    V: Serialize + DeserializeOwned,
+{
+    pub(crate) fn new(db: &'a DB, since: u64, ttl: bool) -> Result<Self> {
+        Ok(ChangeStream {
+            db,
+            inner: Some(WalTail::open(db, since)?),
+            ttl,
+            cf_names: cf_names_by_id(db)?,
+            position: since,
+            batch_end: since,
+            pending: VecDeque::new(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// The position after the last change returned by this stream (or the starting position,
+    /// if none has been returned yet).
+    pub fn checkpoint(&self) -> ChangeCheckpoint {
+        ChangeCheckpoint::new(self.position)
+    }
+
+    fn cf_name(&mut self, id: u32) -> Result<String> {
+        if !self.cf_names.contains_key(&id) {
+            // A column family created after the stream was opened.
+            self.cf_names = cf_names_by_id(self.db)?;
+        }
+        self.cf_names
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| Error::ColumnFamilyNotFound(format!("column family id {id}")))
+    }
+
+    fn decode_change(
+        &self,
+        sequence: u64,
+        column_family: String,
+        op: WalOp<'_>,
+    ) -> Result<Change<K, V>> {
+        let op = match op {
+            WalOp::Put { key, value } => {
+                let (expires_at, payload) = if self.ttl {
+                    decode_envelope(value)?
+                } else {
+                    (None, value)
+                };
+                ChangeOp::Put {
+                    key: KC::decode(key)?,
+                    value: <BincodeCodec<V> as ValueCodec<V>>::decode(payload)?,
+                    expires_at,
+                }
+            }
+            WalOp::Delete { key } => ChangeOp::Delete {
+                key: KC::decode(key)?,
+            },
+            WalOp::Unsupported { tag } => {
+                return Err(Error::Deserialization(format!(
+                    "unsupported write-batch record 0x{tag:02X} in column family `{column_family}`"
+                )))
+            }
+        };
+        Ok(Change {
+            sequence,
+            column_family,
+            op,
+        })
+    }
+
+    /// Read the next WAL batch into `pending`. Returns `Ok(false)` at the end of the log.
+    fn fill(&mut self) -> Result<bool> {
+        let Some(inner) = self.inner.as_mut() else {
+            return Ok(false);
+        };
+        let Some(item) = inner.next() else {
+            return Ok(false);
+        };
+        let (batch_seq, batch) = item?;
+
+        let records = wal::decode_batch(batch.data())?;
+        for (i, record) in records.into_iter().enumerate() {
+            let sequence = batch_seq + i as u64;
+            // A resumed position can fall inside a batch; skip what was already delivered.
+            if sequence <= self.position {
+                continue;
+            }
+            self.batch_end = sequence;
+            let name = self.cf_name(record.cf_id)?;
+            if meta::is_internal_cf(&name) {
+                continue;
+            }
+            let change = self.decode_change(sequence, name, record.op)?;
+            self.pending.push_back(change);
+        }
+        Ok(true)
+    }
+}
+
+impl<'a, K, V, KC> Iterator for ChangeStream<'a, K, V, KC>
+where
+    KC: KeyCodec<K>,
+    V: Serialize + DeserializeOwned,
+{
+    type Item = Result<Change<K, V>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(change) = self.pending.pop_front() {
+                // Once a batch is drained, its trailing (skipped) internal writes count as
+                // delivered too.
+                self.position = if self.pending.is_empty() {
+                    self.batch_end
+                } else {
+                    change.sequence
+                };
+                return Some(Ok(change));
+            }
+            self.position = self.position.max(self.batch_end);
+            match self.fill() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(e) => {
+                    // A failed stream stays failed, and delivers nothing of the batch it failed
+                    // in: the checkpoint stays before it.
+                    self.inner = None;
+                    self.pending.clear();
+                    self.batch_end = self.position;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::{RocksMap, TtlRocksMap};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn collect<K, V, KC>(stream: ChangeStream<'_, K, V, KC>) -> Vec<Change<K, V>>
+    where
+        KC: KeyCodec<K>,
+        V: Serialize + DeserializeOwned,
+    {
+        stream.map(|c| c.unwrap()).collect()
+    }
+
+    #[test]
+    fn streams_puts_and_deletes_in_order() {
+        let dir = TempDir::new().unwrap();
+        let db = RocksMap::<u64, String>::open(dir.path()).unwrap();
+        let start = db.latest_sequence_number();
+
+        db.put(1, &"a".to_string()).unwrap();
+        db.put(2, &"b".to_string()).unwrap();
+        db.delete(&1).unwrap();
+
+        let changes = collect(db.changes_since(start).unwrap());
+        let ops: Vec<ChangeOp<u64, String>> = changes.iter().map(|c| c.op.clone()).collect();
+        assert_eq!(
+            ops,
+            vec![
+                ChangeOp::Put {
+                    key: 1,
+                    value: "a".to_string(),
+                    expires_at: None
+                },
+                ChangeOp::Put {
+                    key: 2,
+                    value: "b".to_string(),
+                    expires_at: None
+                },
+                ChangeOp::Delete { key: 1 },
+            ]
+        );
+        assert!(changes.iter().all(|c| c.column_family == "default"));
+        assert_eq!(changes[2].sequence, db.latest_sequence_number());
+    }
+
+    #[test]
+    fn tags_column_families_and_skips_metadata() {
+        let dir = TempDir::new().unwrap();
+        let mut db = RocksMap::<u64, u64>::open(dir.path()).unwrap();
+        db.column_family("users").unwrap();
+
+        // Everything from sequence 0 includes the metadata written at open, which is skipped.
+        db.put(1, &10).unwrap();
+        db.with_cf("users").put(2, &20).unwrap();
+
+        let changes = collect(db.changes_since(0).unwrap());
+        let tagged: Vec<(&str, u64)> = changes
+            .iter()
+            .map(|c| (c.column_family.as_str(), *c.op.key()))
+            .collect();
+        assert_eq!(tagged, vec![("default", 1), ("users", 2)]);
+    }
+
+    #[test]
+    fn checkpoint_resumes_inside_a_batch() {
+        let dir = TempDir::new().unwrap();
+        let db = RocksMap::<u64, u64>::open(dir.path()).unwrap();
+        let start = db.latest_sequence_number();
+
+        let mut batch = db.batch();
+        for k in 1..=4u64 {
+            batch.put(&k, &k).unwrap();
+        }
+        batch.commit().unwrap();
+
+        let mut stream = db.changes_since(start).unwrap();
+        assert_eq!(*stream.next().unwrap().unwrap().op.key(), 1);
+        assert_eq!(*stream.next().unwrap().unwrap().op.key(), 2);
+        let saved = ChangeCheckpoint::from_bytes(&stream.checkpoint().to_bytes()).unwrap();
+        drop(stream);
+
+        let rest: Vec<u64> = collect(db.changes_since(saved.sequence()).unwrap())
+            .iter()
+            .map(|c| *c.op.key())
+            .collect();
+        assert_eq!(rest, vec![3, 4]);
+    }
+
+    #[test]
+    fn a_batch_that_fails_to_decode_is_not_delivered_in_part() {
+        let dir = TempDir::new().unwrap();
+        let db = RocksMap::<u64, String>::open(dir.path()).unwrap();
+        let start = db.latest_sequence_number();
+
+        let mut batch = WriteBatch::default();
+        let key = |k: u64| <OrderedCodec<u64> as KeyCodec<u64>>::encode(&k).unwrap();
+        let value = <BincodeCodec<String> as ValueCodec<String>>::encode(&"a".to_string());
+        batch.put(key(1), value.unwrap());
+        batch.put(key(2), [0xFF]);
+        db.db().write(batch).unwrap();
+
+        let mut stream = db.changes_since(start).unwrap();
+        assert!(stream.next().unwrap().is_err());
+        assert!(stream.next().is_none());
+        assert_eq!(stream.checkpoint().sequence(), start);
+    }
+
+    #[test]
+    fn caught_up_stream_is_empty_and_checkpoint_advances() {
+        let dir = TempDir::new().unwrap();
+        let db = RocksMap::<u64, u64>::open(dir.path()).unwrap();
+        db.put(1, &1).unwrap();
+
+        let latest = db.latest_sequence_number();
+        assert_eq!(db.changes_since(latest).unwrap().count(), 0);
+
+        let mut stream = db.changes_since(0).unwrap();
+        while stream.next().is_some() {}
+        assert_eq!(stream.checkpoint().sequence(), latest);
+    }
+
+    #[test]
+    fn ttl_envelopes_are_unwrapped() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(1_000);
+        let map =
+            TtlRocksMap::<String, String>::open_with_clock(dir.path(), Arc::new(clock)).unwrap();
+        let start = map.latest_sequence_number();
+
+        map.put("plain".to_string(), &"p".to_string()).unwrap();
+        map.put_with_ttl(
+            "temp".to_string(),
+            &"t".to_string(),
+            Duration::from_millis(500),
+        )
+        .unwrap();
+
+        let ops: Vec<ChangeOp<String, String>> = collect(map.changes_since(start).unwrap())
+            .into_iter()
+            .map(|c| c.op)
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                ChangeOp::Put {
+                    key: "plain".to_string(),
+                    value: "p".to_string(),
+                    expires_at: None
+                },
+                ChangeOp::Put {
+                    key: "temp".to_string(),
+                    value: "t".to_string(),
+                    expires_at: Some(1_500)
+                },
+            ]
+        );
+    }
+}
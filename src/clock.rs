@@ -6,16 +6,36 @@
 //! deterministically testable via [`ManualClock`].
 //!
 //! Wall-clock caveat: TTL timing follows the system clock, so NTP adjustments or manual clock
-//! changes shift expiry. TTL is a retention/eviction mechanism, not a precise timer.
+//! changes shift expiry — a step backwards resurrects expired entries, a step forwards expires
+//! live ones early. TTL is a retention/eviction mechanism, not a precise timer. Two wrappers
+//! narrow the caveat:
+//!
+//! - [`MonotonicGuardClock`] never reports a time earlier than it already has, even across
+//!   restarts: a [`TtlRocksMap`](crate::TtlRocksMap) persists its high-water mark in the metadata
+//!   column family with each write and restores it at open. Steps either way are reported to an
+//!   [`on_skew`](MonotonicGuardClock::on_skew) callback.
+//! - [`HybridClock`] reads the wall clock once, as an anchor, and advances by monotonic
+//!   `Instant` time from there, so wall-clock steps while the process runs have no effect.
 
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 /// A source of "now" in UNIX epoch milliseconds.
 pub trait Clock: Send + Sync {
     /// Current time as milliseconds since the UNIX epoch.
     fn now_unix_millis(&self) -> u64;
+
+    /// The highest time reported so far, for a clock that must not move backward across
+    /// restarts (see [`MonotonicGuardClock`]); `None` for other clocks. A
+    /// [`TtlRocksMap`](crate::TtlRocksMap) persists it with its writes.
+    fn high_water_mark(&self) -> Option<u64> {
+        None
+    }
+
+    /// Resume from a high-water mark persisted by an earlier process. Ignored by clocks without
+    /// one.
+    fn restore_high_water_mark(&self, _millis: u64) {}
 }
 
 /// The default clock, backed by the system wall clock.
@@ -60,3 +80,228 @@ impl Clock for ManualClock {
         self.0.load(Ordering::SeqCst)
     }
 }
+
+/// A detected step of the wall clock, reported by [`MonotonicGuardClock::on_skew`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSkew {
+    /// The wall clock read `by_millis` earlier than the high-water mark (possibly one restored
+    /// from an earlier process). The guard holds at the mark until the wall clock catches up.
+    Backward {
+        /// How far behind the high-water mark the wall clock read.
+        by_millis: u64,
+    },
+    /// The wall clock advanced `by_millis` more than the monotonic time elapsed between two
+    /// readings, beyond the [`forward_tolerance`](MonotonicGuardClock::forward_tolerance). The
+    /// guard follows it; pair it with a [`HybridClock`] to ignore such steps instead.
+    Forward {
+        /// How far the wall clock ran ahead of monotonic time.
+        by_millis: u64,
+    },
+}
+
+/// A callback run with each detected clock step.
+type SkewListener = Box<dyn Fn(ClockSkew) + Send + Sync>;
+
+/// A clock that never moves backward: it reports the larger of the wrapped clock's time and the
+/// highest time it reported before (its high-water mark), and reports wall-clock steps to a
+/// callback.
+///
+/// Opened with a [`TtlRocksMap`](crate::TtlRocksMap), the high-water mark is persisted in the
+/// metadata column family alongside each write and restored at open, so a clock set back
+/// across a restart neither resurrects expired entries on the read path nor keeps the
+/// compaction filter from dropping them.
+pub struct MonotonicGuardClock {
+    inner: Arc<dyn Clock>,
+    high_water: AtomicU64,
+    // Whether the last reading was behind the mark, so a backward step is reported once.
+    behind: AtomicBool,
+    started: Instant,
+    // The wall and monotonic (since `started`) times of the last reading, taken together so
+    // concurrent readers compare matching pairs.
+    last_reading: Mutex<(u64, u64)>,
+    forward_tolerance_millis: u64,
+    on_skew: Option<SkewListener>,
+}
+
+impl MonotonicGuardClock {
+    /// Guard `inner`, reporting forward steps of more than a minute.
+    pub fn new(inner: Arc<dyn Clock>) -> Self {
+        let now = inner.now_unix_millis();
+        Self {
+            inner,
+            high_water: AtomicU64::new(now),
+            behind: AtomicBool::new(false),
+            started: Instant::now(),
+            last_reading: Mutex::new((now, 0)),
+            forward_tolerance_millis: 60_000,
+            on_skew: None,
+        }
+    }
+
+    /// Call `listener` with each detected step of the wrapped clock.
+    pub fn on_skew<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(ClockSkew) + Send + Sync + 'static,
+    {
+        self.on_skew = Some(Box::new(listener));
+        self
+    }
+
+    /// Report a forward step only once the wrapped clock runs ahead of monotonic time by more
+    /// than `tolerance` between two readings.
+    pub fn forward_tolerance(mut self, tolerance: Duration) -> Self {
+        self.forward_tolerance_millis = tolerance.as_millis() as u64;
+        self
+    }
+
+    fn report(&self, skew: ClockSkew) {
+        if let Some(listener) = &self.on_skew {
+            listener(skew);
+        }
+    }
+}
+
+impl Clock for MonotonicGuardClock {
+    fn now_unix_millis(&self) -> u64 {
+        let (wall, ran_ahead) = {
+            let mut last = self.last_reading.lock().unwrap_or_else(|e| e.into_inner());
+            let wall = self.inner.now_unix_millis();
+            let elapsed = self.started.elapsed().as_millis() as u64;
+            let (last_wall, last_elapsed) = std::mem::replace(&mut *last, (wall, elapsed));
+            let ran_ahead = wall
+                .saturating_sub(last_wall)
+                .saturating_sub(elapsed.saturating_sub(last_elapsed));
+            (wall, ran_ahead)
+        };
+        if ran_ahead > self.forward_tolerance_millis {
+            self.report(ClockSkew::Forward {
+                by_millis: ran_ahead,
+            });
+        }
+
+        let mark = self.high_water.fetch_max(wall, Ordering::SeqCst);
+        if wall < mark {
+            if !self.behind.swap(true, Ordering::SeqCst) {
+                self.report(ClockSkew::Backward {
+                    by_millis: mark - wall,
+                });
+            }
+            mark
+        } else {
+            self.behind.store(false, Ordering::SeqCst);
+            wall
+        }
+    }
+
+    fn high_water_mark(&self) -> Option<u64> {
+        Some(self.high_water.load(Ordering::SeqCst))
+    }
+
+    fn restore_high_water_mark(&self, millis: u64) {
+        self.high_water.fetch_max(millis, Ordering::SeqCst);
+        // Reading now reports the step if the wrapped clock is behind the restored mark.
+        self.now_unix_millis();
+    }
+}
+
+/// A clock that reads the wall clock once, as an anchor, and advances by monotonic `Instant`
+/// time from there: wall-clock steps while the process runs change nothing until
+/// [`resync`](Self::resync). Deadlines stay comparable across restarts, as each process anchors
+/// to the wall clock afresh.
+pub struct HybridClock {
+    wall: Arc<dyn Clock>,
+    anchor: Mutex<(u64, Instant)>,
+}
+
+impl HybridClock {
+    /// Anchor to the system clock.
+    pub fn new() -> Self {
+        Self::anchored_to(Arc::new(SystemClock))
+    }
+
+    /// Anchor to `wall` instead of the system clock (e.g. a [`ManualClock`] in tests).
+    pub fn anchored_to(wall: Arc<dyn Clock>) -> Self {
+        let anchor = Mutex::new((wall.now_unix_millis(), Instant::now()));
+        Self { wall, anchor }
+    }
+
+    /// Re-anchor to the wall clock's current time, e.g. once it is known to be synchronized
+    /// again. Never moves the clock backward.
+    pub fn resync(&self) {
+        let mut anchor = self.anchor.lock().unwrap_or_else(|e| e.into_inner());
+        let current = anchor
+            .0
+            .saturating_add(anchor.1.elapsed().as_millis() as u64);
+        *anchor = (self.wall.now_unix_millis().max(current), Instant::now());
+    }
+}
+
+impl Default for HybridClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for HybridClock {
+    fn now_unix_millis(&self) -> u64 {
+        let (wall, at) = *self.anchor.lock().unwrap_or_else(|e| e.into_inner());
+        wall.saturating_add(at.elapsed().as_millis() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_holds_through_backward_steps_and_reports_skew() {
+        let wall = ManualClock::new(1_000);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let guard = MonotonicGuardClock::new(Arc::new(wall.clone()))
+            .forward_tolerance(Duration::from_secs(10))
+            .on_skew(move |skew| log.lock().unwrap().push(skew));
+
+        wall.set(2_000);
+        assert_eq!(guard.now_unix_millis(), 2_000);
+        wall.set(500);
+        assert_eq!(guard.now_unix_millis(), 2_000);
+        assert_eq!(guard.now_unix_millis(), 2_000);
+        wall.set(2_500);
+        assert_eq!(guard.now_unix_millis(), 2_500);
+        wall.set(100_000);
+        assert_eq!(guard.now_unix_millis(), 100_000);
+        assert_eq!(guard.high_water_mark(), Some(100_000));
+
+        // A mark restored from an earlier process that ran ahead also holds.
+        guard.restore_high_water_mark(200_000);
+        assert_eq!(guard.now_unix_millis(), 200_000);
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(
+            seen,
+            vec![
+                ClockSkew::Backward { by_millis: 1_500 },
+                ClockSkew::Forward { by_millis: 97_500 },
+                ClockSkew::Backward { by_millis: 100_000 },
+            ]
+        );
+    }
+
+    #[test]
+    fn hybrid_clock_ignores_wall_steps_until_resync() {
+        let wall = ManualClock::new(1_000_000);
+        let clock = HybridClock::anchored_to(Arc::new(wall.clone()));
+        let within = |t: u64, from: u64| (from..from + 60_000).contains(&t);
+
+        wall.set(0);
+        assert!(within(clock.now_unix_millis(), 1_000_000));
+        wall.set(5_000_000);
+        assert!(within(clock.now_unix_millis(), 1_000_000));
+        clock.resync();
+        assert!(within(clock.now_unix_millis(), 5_000_000));
+        wall.set(0);
+        clock.resync();
+        assert!(within(clock.now_unix_millis(), 5_000_000));
+    }
+}
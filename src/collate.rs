@@ -0,0 +1,296 @@
+//! Collated string keys: case-insensitive and Unicode-normalized ordering.
+//!
+//! A plain `String` key sorts by its raw UTF-8 bytes, so `"Zebra" < "apple"` and `"Ann"` and
+//! `"ann"` are different keys. The wrappers here encode a *collation key* (the string folded to
+//! lowercase, or normalized) followed by the original string:
+//!
+//! ```text
+//! encode(CaseInsensitive(s)) = encode(lowercase(s)) ++ encode(s)
+//! ```
+//!
+//! so keys sort, and prefix scans match, by the collation key, while the original spelling is
+//! still stored and decoded. Two spellings of the same collation key are still distinct map keys
+//! (ordered by their original bytes); in a secondary index only the collation key is used (see
+//! [`OrderedKey::encode_collation_into`]), so a unique index over `CaseInsensitive` email
+//! addresses rejects `"Ann@x.io"` once `"ann@x.io"` is taken, and a lookup matches any case.
+
+use crate::error::{Error, Result};
+use crate::ordered::{
+    decode_bytes, encode_bytes, encode_bytes_no_terminator, OrderedKey, PrefixKey,
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// Lowercase `s` one character at a time. Unlike `str::to_lowercase` this ignores context (the
+/// Greek final sigma), so the folding of a prefix is a prefix of the folding of the whole.
+fn fold_case(s: &str) -> Cow<'_, str> {
+    if s.bytes().all(|b| b.is_ascii() && !b.is_ascii_uppercase()) {
+        return Cow::Borrowed(s);
+    }
+    Cow::Owned(s.chars().flat_map(char::to_lowercase).collect())
+}
+
+macro_rules! collated_key {
+    ($(#[$doc:meta])* $name:ident, $collate:path) => {
+        $(#[$doc])*
+        #[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+        #[serde(transparent)]
+        pub struct $name<S = String>(pub S);
+
+        impl<S: AsRef<str>> $name<S> {
+            /// The collation key the value sorts and matches by.
+            pub fn collation_key(&self) -> Cow<'_, str> {
+                $collate(self.0.as_ref())
+            }
+        }
+
+        impl<S: AsRef<str>> From<S> for $name<S> {
+            fn from(value: S) -> Self {
+                $name(value)
+            }
+        }
+
+        /// By collation key, then by the original string (the order of the encoding).
+        impl<S: AsRef<str> + Eq> Ord for $name<S> {
+            fn cmp(&self, other: &Self) -> Ordering {
+                self.collation_key()
+                    .cmp(&other.collation_key())
+                    .then_with(|| self.0.as_ref().cmp(other.0.as_ref()))
+            }
+        }
+
+        impl<S: AsRef<str> + Eq> PartialOrd for $name<S> {
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+
+        impl<S: AsRef<str> + From<String>> OrderedKey for $name<S> {
+            fn encode_into(&self, out: &mut Vec<u8>) {
+                self.encode_collation_into(out);
+                encode_bytes(self.0.as_ref().as_bytes(), out);
+            }
+            fn decode_from(input: &mut &[u8]) -> Result<Self> {
+                let collation = decode_bytes(input)?;
+                let original = String::decode_from(input)?;
+                if $collate(&original).as_bytes() != collation {
+                    return Err(Error::Deserialization(format!(
+                        "collation key does not match {:?} in ordered key",
+                        original
+                    )));
+                }
+                Ok($name(S::from(original)))
+            }
+            fn encode_collation_into(&self, out: &mut Vec<u8>) {
+                encode_bytes(self.collation_key().as_bytes(), out);
+            }
+        }
+
+        /// Prefix scans match on the collation key.
+        impl<S: AsRef<str> + From<String>> PrefixKey for $name<S> {
+            type Prefix = str;
+            fn encode_prefix(prefix: &str) -> Vec<u8> {
+                encode_bytes_no_terminator($collate(prefix).as_bytes())
+            }
+        }
+    };
+}
+
+collated_key!(
+    /// A string key that sorts, matches prefixes, and is unique in secondary indexes without
+    /// regard to case, while keeping its original spelling.
+    ///
+    /// Case is folded per character with Unicode's lowercase mapping. That is not full case
+    /// folding: `"ß"` and `"SS"` remain different.
+    CaseInsensitive,
+    fold_case
+);
+
+#[cfg(feature = "unicode")]
+mod normalized {
+    use super::*;
+    use unicode_normalization::{is_nfc_quick, is_nfkc_quick, IsNormalized, UnicodeNormalization};
+
+    fn nfc(s: &str) -> Cow<'_, str> {
+        match is_nfc_quick(s.chars()) {
+            IsNormalized::Yes => Cow::Borrowed(s),
+            _ => Cow::Owned(s.nfc().collect()),
+        }
+    }
+
+    fn nfkc(s: &str) -> Cow<'_, str> {
+        match is_nfkc_quick(s.chars()) {
+            IsNormalized::Yes => Cow::Borrowed(s),
+            _ => Cow::Owned(s.nfkc().collect()),
+        }
+    }
+
+    collated_key!(
+        /// A string key compared in Unicode normalization form C, so a precomposed `"é"` and `"e"`
+        /// followed by a combining acute accent are the same collation key.
+        ///
+        /// A prefix ending in a character that composes with what follows it (such as a bare
+        /// `"e"` before a combining accent) does not match the composed form.
+        Nfc,
+        nfc
+    );
+
+    collated_key!(
+        /// A string key compared in Unicode normalization form KC, which also folds compatibility
+        /// variants (`"ﬁ"` and `"fi"`, full-width and ASCII digits) to one collation key. The
+        /// prefix caveat of [`Nfc`] applies.
+        Nfkc,
+        nfkc
+    );
+}
+
+#[cfg(feature = "unicode")]
+pub use normalized::{Nfc, Nfkc};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ordered::encode_collation;
+    use crate::{IndexedRocksMap, KeyCodec, OrderedCodec, RocksMap, VerifyMode};
+    use proptest::prelude::*;
+    use tempfile::TempDir;
+
+    fn ci(s: &str) -> CaseInsensitive {
+        CaseInsensitive(s.to_string())
+    }
+
+    fn enc<K: OrderedKey>(key: &K) -> Vec<u8> {
+        <OrderedCodec<K> as KeyCodec<K>>::encode(key).unwrap()
+    }
+
+    #[test]
+    fn case_insensitive_order_and_roundtrip() {
+        let mut keys = vec![
+            ci("apple"),
+            ci("Zebra"),
+            ci("Apple"),
+            ci("banana"),
+            ci("ΣΟΦΟΣ"),
+        ];
+        keys.sort();
+        let sorted: Vec<&str> = keys.iter().map(|k| k.0.as_str()).collect();
+        assert_eq!(sorted, vec!["Apple", "apple", "banana", "Zebra", "ΣΟΦΟΣ"]);
+        for pair in keys.windows(2) {
+            assert!(enc(&pair[0]) < enc(&pair[1]));
+        }
+        for key in keys {
+            let decoded = <OrderedCodec<CaseInsensitive> as KeyCodec<_>>::decode(&enc(&key));
+            assert_eq!(decoded.unwrap(), key);
+        }
+
+        // A collation key that does not match the original is rejected.
+        let mut forged = Vec::new();
+        "x".to_string().encode_into(&mut forged);
+        "Y".to_string().encode_into(&mut forged);
+        assert!(<OrderedCodec<CaseInsensitive> as KeyCodec<_>>::decode(&forged).is_err());
+    }
+
+    #[test]
+    fn case_insensitive_prefix_scan() {
+        let dir = TempDir::new().unwrap();
+        let map = RocksMap::<CaseInsensitive, u32>::open(dir.path()).unwrap();
+        for (i, name) in ["Alice", "alan", "ALBERT", "bob"].iter().enumerate() {
+            map.put(ci(name), &(i as u32)).unwrap();
+        }
+        let names: Vec<String> = map
+            .scan_prefix("AL")
+            .unwrap()
+            .map(|r| r.unwrap().0 .0)
+            .collect();
+        assert_eq!(names, vec!["alan", "ALBERT", "Alice"]);
+        assert_eq!(
+            map.get(&ci("ALICE")).unwrap(),
+            None,
+            "map keys keep their spelling"
+        );
+    }
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Account {
+        email: String,
+    }
+
+    #[test]
+    fn unique_index_is_case_insensitive() {
+        let dir = TempDir::new().unwrap();
+        let mut builder = IndexedRocksMap::<u64, Account>::builder(dir.path());
+        let by_email = builder.unique_index("email", |a: &Account| {
+            Some(CaseInsensitive(a.email.clone()))
+        });
+        let map = builder.open().unwrap();
+        let account = |email: &str| Account {
+            email: email.to_string(),
+        };
+
+        map.put(1, &account("Ann@Example.com")).unwrap();
+        assert!(matches!(
+            map.put(2, &account("ann@example.com")),
+            Err(Error::UniqueViolation(_))
+        ));
+        assert_eq!(
+            map.find_keys_by(&by_email, &ci("ANN@EXAMPLE.COM")).unwrap(),
+            vec![1]
+        );
+        // Changing only the case of one's own address is not a conflict.
+        map.put(1, &account("ann@example.com")).unwrap();
+        assert!(map.verify(VerifyMode::Check).unwrap().is_clean());
+    }
+
+    proptest! {
+        #[test]
+        fn prop_sequences_of_collated_keys_index_by_collation(a: Vec<String>, b: Vec<String>) {
+            let folded = |words: &[String]| -> Vec<String> {
+                words.iter().map(|w| fold_case(w).into_owned()).collect()
+            };
+            let keys = |words: &[String]| -> Vec<CaseInsensitive> {
+                words.iter().map(|w| ci(w)).collect()
+            };
+            prop_assert_eq!(
+                folded(&a).cmp(&folded(&b)),
+                encode_collation(&keys(&a)).cmp(&encode_collation(&keys(&b)))
+            );
+            // Respelling every element keeps the index key.
+            let shouted: Vec<String> = a.iter().map(|w| w.to_ascii_uppercase()).collect();
+            prop_assert_eq!(encode_collation(&keys(&shouted)), encode_collation(&keys(&a)));
+            let boxed: Box<[CaseInsensitive]> = keys(&shouted).into_boxed_slice();
+            prop_assert_eq!(encode_collation(&boxed), encode_collation(&keys(&a)));
+        }
+    }
+
+    #[test]
+    fn arrays_of_collated_keys_index_by_collation() {
+        assert_eq!(
+            encode_collation(&[ci("Ann"), ci("BOB")]),
+            encode_collation(&[ci("ann"), ci("bob")])
+        );
+        assert_ne!(enc(&[ci("Ann"), ci("BOB")]), enc(&[ci("ann"), ci("bob")]));
+        assert_eq!(
+            encode_collation(&Some(vec![ci("Ann")])),
+            encode_collation(&Some(vec![ci("aNN")]))
+        );
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn normalized_keys_compare_equal_forms() {
+        let composed = Nfc("caf\u{e9}".to_string());
+        let decomposed = Nfc("cafe\u{301}".to_string());
+        assert_eq!(composed.collation_key(), decomposed.collation_key());
+        assert_eq!(
+            crate::ordered::encode_collation(&composed),
+            crate::ordered::encode_collation(&decomposed)
+        );
+        assert_ne!(enc(&composed), enc(&decomposed), "originals are kept");
+        let decoded = <OrderedCodec<Nfc> as KeyCodec<_>>::decode(&enc(&decomposed)).unwrap();
+        assert_eq!(decoded, decomposed);
+
+        assert_eq!(Nfkc("\u{fb01}le".to_string()).collation_key(), "file");
+        assert!(enc(&Nfkc("\u{ff11}0".to_string())) < enc(&Nfkc("2".to_string())));
+    }
+}
@@ -0,0 +1,372 @@
+//! Offline key-codec conversion for plain maps.
+//!
+//! The key codec a [`RocksMap`](crate::RocksMap) was created with is recorded in its metadata
+//! and pinned: reopening with another codec fails. To move a table from, say,
+//! [`BincodeCodec`](crate::BincodeCodec) keys to [`OrderedCodec`](crate::OrderedCodec) keys
+//! (to get `range` and the prefix scans), convert it while it is closed:
+//!
+//! ```no_run
+//! use rocksmap::{convert, BincodeCodec, OrderedCodec};
+//!
+//! // Into a new database...
+//! convert::rekey::<u64, String, BincodeCodec<u64>, OrderedCodec<u64>>("./old.db", "./new.db")?;
+//! // ...or in place.
+//! convert::rekey_in_place::<u64, String, BincodeCodec<u64>, OrderedCodec<u64>>("./old.db")?;
+//! # Ok::<(), rocksmap::Error>(())
+//! ```
+//!
+//! Every user column family is converted: each key is decoded with the old codec and
+//! re-encoded with the new one, values are copied as they are, and the new codec id is
+//! recorded. Quarantined rows (see [`verify`](crate::verify)) are not carried over by
+//! [`rekey`].
+//!
+//! [`rekey_in_place`] works one column family at a time through a scratch column family,
+//! recording its progress in the metadata. If it is interrupted, the map refuses to open (its
+//! keys are in a mix of codecs) until `rekey_in_place` is run again, which picks up where it
+//! stopped.
+
+use crate::codec::KeyCodec;
+use crate::error::{Error, Result};
+use crate::meta::{self, MapKind, RekeyMarker, RekeyPhase};
+use crate::schema::{self, TypeFingerprint};
+use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, IteratorMode, Options, WriteBatch, DB};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// Scratch column family an in-place conversion stages re-encoded rows in.
+const STAGING_CF: &str = "__rocksmap_rekey";
+
+/// Rows written per batch while copying.
+const BATCH_ROWS: usize = 1024;
+
+/// What a key conversion did.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RekeyReport {
+    /// The column families converted, in order.
+    pub column_families: Vec<String>,
+    /// Rows re-encoded.
+    pub rows: u64,
+    /// Whether an interrupted in-place conversion was resumed.
+    pub resumed: bool,
+}
+
+/// Check that `db` is a plain map of `K -> V` whose recorded key codec is one of `accepted`.
+fn check_source<K: DeserializeOwned, V: DeserializeOwned>(db: &DB, accepted: &[u8]) -> Result<()> {
+    match meta::read_kind(db)? {
+        Some(MapKind::Plain) => {}
+        Some(kind) => {
+            return Err(Error::FormatMismatch(format!(
+                "key codecs can only be converted in plain maps, not in a {kind} map"
+            )))
+        }
+        None => {
+            return Err(Error::FormatMismatch(
+                "metadata present but no schema record".to_string(),
+            ))
+        }
+    }
+    match meta::read_key_codec(db)? {
+        Some(id) if !accepted.contains(&id) => {
+            return Err(Error::FormatMismatch(format!(
+                "database uses key codec id {id}, expected {}",
+                accepted[0]
+            )))
+        }
+        _ => {}
+    }
+    schema::check_types::<K, V, _>(db)
+}
+
+fn user_cfs(db: &DB) -> Vec<String> {
+    let mut names: Vec<String> = meta::existing_cfs(&Options::default(), db.path())
+        .into_iter()
+        .filter(|name| !meta::is_internal_cf(name))
+        .collect();
+    names.sort();
+    names
+}
+
+fn cf<'a>(db: &'a DB, name: &str) -> Result<&'a ColumnFamily> {
+    db.cf_handle(name)
+        .ok_or_else(|| Error::ColumnFamilyNotFound(name.to_string()))
+}
+
+/// Copy every row of `from_cf` in `from` into `to_cf` in `to`, passing each key through
+/// `map_key`. Returns the number of rows copied.
+fn copy_rows(
+    from: &DB,
+    from_cf: &str,
+    to: &DB,
+    to_cf: &str,
+    map_key: impl Fn(&[u8]) -> Result<Vec<u8>>,
+) -> Result<u64> {
+    let source = cf(from, from_cf)?;
+    let target = cf(to, to_cf)?;
+    let mut batch = WriteBatch::default();
+    let mut rows = 0;
+    for item in from.iterator_cf(source, IteratorMode::Start) {
+        let (key, value) = item.map_err(Error::from)?;
+        batch.put_cf(target, map_key(&key)?, value);
+        rows += 1;
+        if batch.len() >= BATCH_ROWS {
+            to.write(std::mem::take(&mut batch)).map_err(Error::from)?;
+        }
+    }
+    to.write(batch).map_err(Error::from)?;
+    Ok(rows)
+}
+
+/// Delete every row of column family `name`.
+fn clear_rows(db: &DB, name: &str) -> Result<()> {
+    let handle = cf(db, name)?;
+    let mut batch = WriteBatch::default();
+    for item in db.iterator_cf(handle, IteratorMode::Start) {
+        let (key, _) = item.map_err(Error::from)?;
+        batch.delete_cf(handle, key);
+        if batch.len() >= BATCH_ROWS {
+            db.write(std::mem::take(&mut batch)).map_err(Error::from)?;
+        }
+    }
+    db.write(batch).map_err(Error::from)
+}
+
+fn reencode<K, FromKC: KeyCodec<K>, ToKC: KeyCodec<K>>(key: &[u8]) -> Result<Vec<u8>> {
+    ToKC::encode(&FromKC::decode(key)?)
+}
+
+/// Convert the (closed) plain map at `src`, created with key codec `FromKC`, into a new map at
+/// `dst` using `ToKC`. `dst` must not exist yet (or be an empty directory); `src` is only read.
+pub fn rekey<K, V, FromKC, ToKC>(
+    src: impl AsRef<Path>,
+    dst: impl AsRef<Path>,
+) -> Result<RekeyReport>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    FromKC: KeyCodec<K>,
+    ToKC: KeyCodec<K>,
+{
+    let dst = dst.as_ref();
+    let occupied = std::fs::read_dir(dst).map(|mut entries| entries.next().is_some());
+    if occupied.unwrap_or(false) {
+        return Err(Error::Other(format!(
+            "conversion target {} is not empty",
+            dst.display()
+        )));
+    }
+    let source = meta::open_managed(src.as_ref(), true)?;
+    check_source::<K, V>(&source, &[FromKC::ID])?;
+    let column_families = user_cfs(&source);
+
+    let mut opts = Options::default();
+    opts.create_if_missing(true);
+    opts.create_missing_column_families(true);
+    let descriptors: Vec<ColumnFamilyDescriptor> = column_families
+        .iter()
+        .map(String::as_str)
+        .chain([meta::META_CF])
+        .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+        .collect();
+    let target = DB::open_cf_descriptors(&opts, dst, descriptors).map_err(Error::from)?;
+    meta::verify_or_write_kind(&target, MapKind::Plain)?;
+    meta::write_key_codec(&target, ToKC::ID)?;
+    meta::write_types(&target, &TypeFingerprint::of::<K, V>())?;
+
+    let mut rows = 0;
+    for name in &column_families {
+        rows += copy_rows(&source, name, &target, name, reencode::<K, FromKC, ToKC>)?;
+    }
+    target.flush_wal(true).map_err(Error::from)?;
+    Ok(RekeyReport {
+        column_families,
+        rows,
+        resumed: false,
+    })
+}
+
+/// Stage the re-encoded rows of `name` in the scratch column family.
+fn stage<K, FromKC: KeyCodec<K>, ToKC: KeyCodec<K>>(db: &mut DB, name: &str) -> Result<u64> {
+    if db.cf_handle(STAGING_CF).is_some() {
+        db.drop_cf(STAGING_CF).map_err(Error::from)?;
+    }
+    db.create_cf(STAGING_CF, &Options::default())
+        .map_err(Error::from)?;
+    let marker = |phase| RekeyMarker {
+        to: ToKC::ID,
+        column_family: name.to_string(),
+        phase,
+    };
+    meta::set_rekeying(db, &marker(RekeyPhase::Stage))?;
+    let rows = copy_rows(db, name, db, STAGING_CF, reencode::<K, FromKC, ToKC>)?;
+    // The staged copy must be on disk before the original rows are deleted.
+    db.flush_wal(true).map_err(Error::from)?;
+    meta::set_rekeying(db, &marker(RekeyPhase::Swap))?;
+    Ok(rows)
+}
+
+/// Replace the rows of `name` with the staged copy, then drop the scratch column family.
+fn swap(db: &mut DB, name: &str, to: u8) -> Result<()> {
+    clear_rows(db, name)?;
+    copy_rows(db, STAGING_CF, db, name, |key| Ok(key.to_vec()))?;
+    db.flush_wal(true).map_err(Error::from)?;
+    meta::set_rekeying(
+        db,
+        &RekeyMarker {
+            to,
+            column_family: name.to_string(),
+            phase: RekeyPhase::Done,
+        },
+    )?;
+    db.drop_cf(STAGING_CF).map_err(Error::from)
+}
+
+/// Convert the (closed) plain map at `path` from key codec `FromKC` to `ToKC` in place, or
+/// finish a conversion to `ToKC` that was interrupted.
+pub fn rekey_in_place<K, V, FromKC, ToKC>(path: impl AsRef<Path>) -> Result<RekeyReport>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    FromKC: KeyCodec<K>,
+    ToKC: KeyCodec<K>,
+{
+    let mut db = meta::open_managed(path.as_ref(), false)?;
+    let pending = meta::get_rekeying(&db)?;
+    match &pending {
+        Some(marker) if marker.to != ToKC::ID => {
+            return Err(Error::FormatMismatch(format!(
+                "an interrupted conversion to key codec id {} is pending, not to {}",
+                marker.to,
+                ToKC::ID
+            )))
+        }
+        // The new codec id is recorded last, so an interrupted run may already have written it.
+        Some(_) => check_source::<K, V>(&db, &[FromKC::ID, ToKC::ID])?,
+        None => check_source::<K, V>(&db, &[FromKC::ID])?,
+    }
+
+    let column_families = user_cfs(&db);
+    let mut rows = 0;
+    for name in &column_families {
+        let phase = match &pending {
+            Some(marker) if *name < marker.column_family => continue,
+            Some(marker) if *name == marker.column_family => marker.phase,
+            _ => RekeyPhase::Stage,
+        };
+        match phase {
+            RekeyPhase::Done => continue,
+            RekeyPhase::Stage => rows += stage::<K, FromKC, ToKC>(&mut db, name)?,
+            RekeyPhase::Swap => {}
+        }
+        swap(&mut db, name, ToKC::ID)?;
+    }
+    meta::write_key_codec(&db, ToKC::ID)?;
+    meta::clear_rekeying(&db)?;
+    Ok(RekeyReport {
+        column_families,
+        rows,
+        resumed: pending.is_some(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BincodeCodec, OrderedCodec, RocksMap, TtlRocksMap};
+    use tempfile::TempDir;
+
+    type Unordered = RocksMap<u64, String, BincodeCodec<u64>>;
+    type Old = BincodeCodec<u64>;
+    type New = OrderedCodec<u64>;
+
+    fn seed(path: &Path) {
+        let mut map = Unordered::open_with_cfs(path, Options::default(), &["side"]).unwrap();
+        for n in [300u64, 2, 10_000, 45] {
+            map.put(n, &format!("v{n}")).unwrap();
+        }
+        map.column_family("side")
+            .unwrap()
+            .put(7, &"s".to_string())
+            .unwrap();
+    }
+
+    fn assert_converted(path: &Path) {
+        let mut map = RocksMap::<u64, String>::open(path).unwrap();
+        let keys: Vec<u64> = map.range(..).unwrap().map(|item| item.unwrap().0).collect();
+        assert_eq!(keys, vec![2, 45, 300, 10_000]);
+        assert_eq!(map.get(&300).unwrap(), Some("v300".to_string()));
+        assert_eq!(
+            map.column_family("side").unwrap().get(&7).unwrap(),
+            Some("s".to_string())
+        );
+        assert!(Unordered::open(path).is_err());
+    }
+
+    #[test]
+    fn rekey_copies_into_a_new_database() {
+        let dir = TempDir::new().unwrap();
+        let (src, dst) = (dir.path().join("src"), dir.path().join("dst"));
+        seed(&src);
+
+        let report = rekey::<u64, String, Old, New>(&src, &dst).unwrap();
+        assert_eq!(report.column_families, vec!["default", "side"]);
+        assert_eq!(report.rows, 5);
+        assert_converted(&dst);
+        // The source is untouched.
+        assert!(Unordered::open(&src).is_ok());
+        // The target must be fresh.
+        assert!(rekey::<u64, String, Old, New>(&src, &dst).is_err());
+    }
+
+    #[test]
+    fn rekey_in_place_converts_every_column_family() {
+        let dir = TempDir::new().unwrap();
+        seed(dir.path());
+
+        let report = rekey_in_place::<u64, String, Old, New>(dir.path()).unwrap();
+        assert_eq!(report.rows, 5);
+        assert!(!report.resumed);
+        assert_converted(dir.path());
+        assert!(
+            !meta::existing_cfs(&Options::default(), dir.path()).contains(&STAGING_CF.to_string())
+        );
+    }
+
+    #[test]
+    fn an_interrupted_conversion_blocks_opens_and_resumes() {
+        let dir = TempDir::new().unwrap();
+        seed(dir.path());
+        {
+            // Stage the default column family, then "crash" before swapping it in.
+            let mut db = meta::open_managed(dir.path(), false).unwrap();
+            stage::<u64, Old, New>(&mut db, "default").unwrap();
+        }
+        let err = Unordered::open(dir.path()).err().unwrap();
+        assert!(
+            matches!(err, Error::FormatMismatch(ref m) if m.contains("interrupted")),
+            "{err}"
+        );
+        assert!(rekey_in_place::<u64, String, New, Old>(dir.path()).is_err());
+
+        let report = rekey_in_place::<u64, String, Old, New>(dir.path()).unwrap();
+        assert!(report.resumed);
+        assert_converted(dir.path());
+    }
+
+    #[test]
+    fn only_plain_maps_with_the_expected_codec_convert() {
+        let dir = TempDir::new().unwrap();
+        seed(dir.path());
+        let err = rekey_in_place::<u64, String, New, Old>(dir.path())
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::FormatMismatch(_)), "{err}");
+        assert!(
+            rekey_in_place::<u64, u32, Old, New>(dir.path()).is_err(),
+            "value type is checked"
+        );
+
+        let ttl = dir.path().join("ttl");
+        drop(TtlRocksMap::<u64, String>::open(&ttl).unwrap());
+        assert!(rekey_in_place::<u64, String, Old, New>(&ttl).is_err());
+    }
+}
@@ -37,6 +37,15 @@ pub enum Error {
     #[error("Unique constraint violation: {0}")]
     UniqueViolation(String),
 
+    /// The write-ahead log no longer holds every change after the requested sequence number
+    /// (its files were purged), so a change stream cannot resume from it.
+    #[error("Changes after sequence {0} are no longer available in the write-ahead log")]
+    ChangesUnavailable(u64),
+
+    /// Replication between a primary and a follower failed (network, protocol, or resync).
+    #[error("Replication error: {0}")]
+    Replication(String),
+
     /// Other unexpected errors
     #[error("Unexpected error: {0}")]
     Other(String),
@@ -0,0 +1,284 @@
+//! On-disk format upgrades.
+//!
+//! Every database records the format version it was written with (see [`meta`](crate::meta)).
+//! When a release changes the on-disk layout it bumps that version and appends a step to
+//! [`STEPS`] that rewrites a database from version `N` to `N + 1`. Opening an older database
+//! fails with [`Error::FormatMismatch`] unless the caller opts in with
+//! [`OpenOptions::allow_format_upgrade`], in which case the pending steps run, in order, before
+//! the open completes.
+//!
+//! Each step runs under a marker in the metadata, like an index rebuild: a crash mid-step leaves
+//! the marker behind, the interrupted step is detected on the next open, and it runs again from
+//! the start once the caller allows upgrades. Steps must therefore be idempotent.
+//!
+//! [`plan_format_upgrade`] is the dry run: it reports what an upgrade would do without writing.
+//! [`upgrade_format`] upgrades a closed database by path, without knowing its types.
+
+use crate::error::{Error, Result};
+use crate::meta::{self, KvStore, FORMAT_VERSION};
+use std::path::Path;
+
+/// One upgrade of the on-disk format, from version `from` to `from + 1`.
+pub(crate) struct UpgradeStep {
+    pub(crate) from: u16,
+    pub(crate) description: &'static str,
+    pub(crate) apply: fn(&dyn KvStore) -> Result<()>,
+}
+
+/// Every upgrade step this build knows, in ascending `from` order. Format version 1 is the
+/// first released format, so there is nothing to upgrade yet.
+const STEPS: &[UpgradeStep] = &[];
+
+/// Options for opening a map, beyond the RocksDB tuning in [`rocks::Options`](crate::rocks).
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    format_upgrade: bool,
+    check_types_only: bool,
+}
+
+impl OpenOptions {
+    /// The default options: databases in an older on-disk format are rejected.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Upgrade a database written in an older on-disk format (or resume an interrupted upgrade)
+    /// as part of the open, instead of rejecting it.
+    pub fn allow_format_upgrade(mut self) -> Self {
+        self.format_upgrade = true;
+        self
+    }
+
+    /// Check the key/value types recorded in the database, but don't record the ones opened
+    /// with if none are: for generic tools that open someone else's database with stand-in
+    /// types, and must not pin it to them.
+    pub fn check_types_only(mut self) -> Self {
+        self.check_types_only = true;
+        self
+    }
+
+    pub(crate) fn records_types(&self) -> bool {
+        !self.check_types_only
+    }
+}
+
+/// What a format upgrade did, or (from [`plan_format_upgrade`]) would do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatUpgradeReport {
+    /// The format version found on disk.
+    pub from: u16,
+    /// The format version this build writes.
+    pub to: u16,
+    /// Descriptions of the steps from `from` to `to`, in order.
+    pub steps: Vec<String>,
+    /// Whether an interrupted upgrade was found (and, unless a dry run, resumed).
+    pub resumed: bool,
+    /// Whether the steps were run (`false` for a dry run).
+    pub applied: bool,
+}
+
+impl FormatUpgradeReport {
+    /// Whether the database is already in the current format, with no upgrade pending.
+    pub fn is_current(&self) -> bool {
+        self.steps.is_empty() && !self.resumed
+    }
+}
+
+/// Upgrade the format of a freshly opened database if it is out of date and `options` allow it.
+/// A database that is out of date but may not be upgraded is left for
+/// [`meta::verify_or_write_kind`] to reject; only an interrupted upgrade is reported here.
+pub(crate) fn prepare<S: KvStore>(store: &S, options: &OpenOptions) -> Result<()> {
+    let Some(version) = meta::read_format_version(store)? else {
+        return Ok(());
+    };
+    let interrupted = meta::get_format_upgrading(store)?;
+    if version >= FORMAT_VERSION && interrupted.is_none() {
+        return Ok(());
+    }
+    if options.format_upgrade {
+        return run(store, STEPS, FORMAT_VERSION).map(|_| ());
+    }
+    match interrupted {
+        Some(from) => Err(Error::FormatMismatch(format!(
+            "an upgrade from on-disk format version {from} was interrupted \
+             (open with `OpenOptions::allow_format_upgrade` to resume it)"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// The steps taking a database from `from` to `target`.
+fn pending(steps: &[UpgradeStep], from: u16, target: u16) -> Result<Vec<&UpgradeStep>> {
+    if from > target {
+        return Err(Error::FormatMismatch(format!(
+            "unsupported on-disk format version {from} (this build supports {target})"
+        )));
+    }
+    (from..target)
+        .map(|version| {
+            steps
+                .iter()
+                .find(|step| step.from == version)
+                .ok_or_else(|| {
+                    Error::FormatMismatch(format!(
+                        "no upgrade path from on-disk format version {version}"
+                    ))
+                })
+        })
+        .collect()
+}
+
+fn plan<S: KvStore>(store: &S, steps: &[UpgradeStep], target: u16) -> Result<FormatUpgradeReport> {
+    let from = meta::read_format_version(store)?.ok_or_else(|| {
+        Error::FormatMismatch("metadata present but no schema record".to_string())
+    })?;
+    Ok(FormatUpgradeReport {
+        from,
+        to: target,
+        steps: pending(steps, from, target)?
+            .iter()
+            .map(|step| format!("{} -> {}: {}", step.from, step.from + 1, step.description))
+            .collect(),
+        resumed: meta::get_format_upgrading(store)?.is_some(),
+        applied: false,
+    })
+}
+
+/// Run the steps from the recorded version up to `target`. An interrupted step has not yet
+/// bumped the version, so it is simply the first step run again.
+fn run<S: KvStore>(store: &S, steps: &[UpgradeStep], target: u16) -> Result<FormatUpgradeReport> {
+    let mut report = plan(store, steps, target)?;
+    let from = report.from;
+    for step in pending(steps, from, target)? {
+        meta::set_format_upgrading(store, step.from)?;
+        (step.apply)(store)?;
+        meta::write_format_version(store, step.from + 1)?;
+    }
+    meta::clear_format_upgrading(store)?;
+    report.applied = true;
+    Ok(report)
+}
+
+/// Report, without writing anything, which format upgrade steps the (closed) database at `path`
+/// needs to reach the format this build writes.
+pub fn plan_format_upgrade<P: AsRef<Path>>(path: P) -> Result<FormatUpgradeReport> {
+    let db = meta::open_managed(path.as_ref(), true)?;
+    plan(&db, STEPS, FORMAT_VERSION)
+}
+
+/// Upgrade the (closed) database at `path` to the format this build writes, resuming an
+/// interrupted upgrade if there is one.
+pub fn upgrade_format<P: AsRef<Path>>(path: P) -> Result<FormatUpgradeReport> {
+    let db = meta::open_managed(path.as_ref(), false)?;
+    run(&db, STEPS, FORMAT_VERSION)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RocksMap;
+    use rocksdb::DB;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tempfile::TempDir;
+
+    fn mark(store: &dyn KvStore, key: &[u8]) -> Result<()> {
+        let cf = store.cf(meta::META_CF).unwrap();
+        store.put_raw(cf, key, b"done")
+    }
+
+    fn marked(db: &DB, key: &[u8]) -> bool {
+        let cf = db.cf_handle(meta::META_CF).unwrap();
+        db.get_cf(cf, key).unwrap().is_some()
+    }
+
+    static FAIL_ONCE: AtomicBool = AtomicBool::new(true);
+
+    const TEST_STEPS: &[UpgradeStep] = &[
+        UpgradeStep {
+            from: 1,
+            description: "first",
+            apply: |store| mark(store, b"test/step1"),
+        },
+        UpgradeStep {
+            from: 2,
+            description: "second",
+            apply: |store| {
+                if FAIL_ONCE.swap(false, Ordering::SeqCst) {
+                    return Err(Error::Other("simulated crash".to_string()));
+                }
+                mark(store, b"test/step2")
+            },
+        },
+    ];
+
+    fn plain_db(dir: &TempDir) -> DB {
+        drop(RocksMap::<String, String>::open(dir.path()).unwrap());
+        meta::open_managed(dir.path(), false).unwrap()
+    }
+
+    #[test]
+    fn steps_run_in_order_and_resume_after_a_crash() {
+        let dir = TempDir::new().unwrap();
+        let db = plain_db(&dir);
+
+        let dry = plan(&db, TEST_STEPS, 3).unwrap();
+        assert_eq!(dry.steps, vec!["1 -> 2: first", "2 -> 3: second"]);
+        assert!(!dry.applied && !dry.resumed);
+        assert_eq!(meta::read_format_version(&db).unwrap(), Some(1));
+
+        // The second step "crashes": the first one is committed, the marker names the second.
+        assert!(run(&db, TEST_STEPS, 3).is_err());
+        assert_eq!(meta::read_format_version(&db).unwrap(), Some(2));
+        assert_eq!(meta::get_format_upgrading(&db).unwrap(), Some(2));
+        assert!(marked(&db, b"test/step1") && !marked(&db, b"test/step2"));
+
+        let report = run(&db, TEST_STEPS, 3).unwrap();
+        assert!(report.resumed && report.applied);
+        assert_eq!(report.steps, vec!["2 -> 3: second"]);
+        assert_eq!(meta::read_format_version(&db).unwrap(), Some(3));
+        assert_eq!(meta::get_format_upgrading(&db).unwrap(), None);
+        assert!(marked(&db, b"test/step2"));
+    }
+
+    #[test]
+    fn missing_steps_and_newer_formats_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        let db = plain_db(&dir);
+        assert!(matches!(
+            plan(&db, &TEST_STEPS[1..], 3),
+            Err(Error::FormatMismatch(m)) if m.contains("no upgrade path from on-disk format version 1")
+        ));
+        assert!(plan(&db, TEST_STEPS, 0).is_err());
+    }
+
+    #[test]
+    fn opening_an_old_or_interrupted_database_requires_opting_in() {
+        let dir = TempDir::new().unwrap();
+        {
+            let db = plain_db(&dir);
+            meta::set_format_upgrading(&db, FORMAT_VERSION).unwrap();
+        }
+        let err = RocksMap::<String, String>::open(dir.path()).err().unwrap();
+        assert!(
+            matches!(err, Error::FormatMismatch(ref m) if m.contains("interrupted")),
+            "{err}"
+        );
+        assert!(!plan_format_upgrade(dir.path()).unwrap().is_current());
+
+        let options = OpenOptions::new().allow_format_upgrade();
+        drop(RocksMap::<String, String>::open_with(dir.path(), &options).unwrap());
+        assert!(plan_format_upgrade(dir.path()).unwrap().is_current());
+
+        {
+            let db = meta::open_managed(dir.path(), false).unwrap();
+            meta::write_format_version(&db, 0).unwrap();
+        }
+        let err = RocksMap::<String, String>::open(dir.path()).err().unwrap();
+        assert!(
+            matches!(err, Error::FormatMismatch(ref m) if m.contains("allow_format_upgrade")),
+            "{err}"
+        );
+        // This build has no step from version 0.
+        assert!(upgrade_format(dir.path()).is_err());
+    }
+}
@@ -5,16 +5,22 @@
 //! concurrent writer can never leave an index diverged from the data. Indexes are declared up
 //! front via the builder, which returns a typed [`Index`] handle used for lookups.
 //!
-//! Index entry layout:
-//! - non-unique: composite key `encode((secondary_key, primary_key)) -> []`; a lookup is a
-//!   tuple prefix scan.
-//! - unique: `encode(secondary_key) -> encode(primary_key)`; a lookup is a point read, and a
+//! Index entry layout, where `collate(sk)` is the secondary key's
+//! [collation encoding](crate::OrderedKey::encode_collation_into) (its ordered encoding, except
+//! for collated keys such as [`CaseInsensitive`](crate::CaseInsensitive)):
+//! - non-unique: composite key `collate(secondary_key) ++ encode(primary_key)`, whose value is
+//!   the length of `collate(secondary_key)` (`u32` big-endian) so the entry can be split without
+//!   knowing the secondary key's type; a lookup is a prefix scan.
+//! - unique: `collate(secondary_key) -> encode(primary_key)`; a lookup is a point read, and a
 //!   second primary key for the same secondary key is rejected.
 
 use crate::codec::{BincodeCodec, KeyCodec, ValueCodec};
 use crate::error::{Error, Result};
+use crate::format::{self, OpenOptions};
 use crate::meta::{self, MapKind};
-use crate::ordered::{OrderedCodec, OrderedKey};
+use crate::ordered::{encode_collation, OrderedCodec, OrderedKey};
+use crate::schema;
+use crate::verify::{ExpectedEntry, RowLayout, Verifier, VerifyMode, VerifyReport};
 use rocksdb::{
     ColumnFamily, ColumnFamilyDescriptor, IteratorMode, Options, ReadOptions, TransactionDB,
     TransactionDBOptions,
@@ -39,6 +45,18 @@ fn byte_successor(prefix: &[u8]) -> Option<Vec<u8>> {
     None
 }
 
+/// The value of a non-unique index entry: the length of its secondary key.
+pub(crate) fn entry_value(sk: &[u8]) -> [u8; 4] {
+    (sk.len() as u32).to_be_bytes()
+}
+
+/// The primary-key part of a non-unique index entry, split off at the secondary-key length its
+/// value records, or `None` if the value is not a valid length.
+pub(crate) fn entry_primary_key<'a>(entry: &'a [u8], value: &[u8]) -> Option<&'a [u8]> {
+    let len = u32::from_be_bytes(value.try_into().ok()?) as usize;
+    entry.get(len..)
+}
+
 /// Encodes a value's secondary key to ordered bytes, or `None` to skip indexing that value.
 type Extractor<V> = Box<dyn Fn(&V) -> Result<Option<Vec<u8>>> + Send + Sync>;
 
@@ -103,7 +121,7 @@ where
     {
         let extractor: Extractor<V> = Box::new(move |v| match extract(v) {
             None => Ok(None),
-            Some(sk) => Ok(Some(<OrderedCodec<SK> as KeyCodec<SK>>::encode(&sk)?)),
+            Some(sk) => Ok(Some(encode_collation(&sk))),
         });
         self.indexes.push(IndexDef {
             name: name.to_string(),
@@ -119,7 +137,13 @@ where
 
     /// Open the database, creating column families and verifying the metadata.
     pub fn open(self) -> Result<IndexedRocksMap<K, V>> {
-        IndexedRocksMap::open_internal(self.path, self.indexes)
+        IndexedRocksMap::open_internal(self.path, self.indexes, &OpenOptions::default())
+    }
+
+    /// Like [`open`](Self::open), with rocksmap-level [`OpenOptions`] (e.g. to allow a format
+    /// upgrade).
+    pub fn open_with(self, open: &OpenOptions) -> Result<IndexedRocksMap<K, V>> {
+        IndexedRocksMap::open_internal(self.path, self.indexes, open)
     }
 }
 
@@ -148,7 +172,7 @@ where
         }
     }
 
-    fn open_internal(path: PathBuf, indexes: Vec<IndexDef<V>>) -> Result<Self> {
+    fn open_internal(path: PathBuf, indexes: Vec<IndexDef<V>>, open: &OpenOptions) -> Result<Self> {
         if !path.exists() {
             std::fs::create_dir_all(&path).map_err(|_| Error::InvalidPath(path.clone()))?;
         }
@@ -169,7 +193,9 @@ where
         let db = TransactionDB::open_cf_descriptors(&db_opts, &txn_db_opts, &path, descriptors)
             .map_err(Error::from)?;
 
+        format::prepare(&db, open)?;
         meta::verify_or_write_kind(&db, MapKind::Indexed)?;
+        schema::verify_or_write_types::<K, V, _>(&db, open)?;
         let mut sorted_names: Vec<String> = indexes.iter().map(|i| i.name.clone()).collect();
         sorted_names.sort();
         meta::verify_or_write_indexes(&db, &sorted_names)?;
@@ -180,9 +206,38 @@ where
             _marker: PhantomData,
         };
         map.resume_pending_rebuild()?;
+        map.record_unique_indexes()?;
         Ok(map)
     }
 
+    /// Record which indexes are unique, first rebuilding the non-unique ones if their entries
+    /// predate the record and so lack their secondary-key length.
+    fn record_unique_indexes(&self) -> Result<()> {
+        let mut unique: Vec<String> = self
+            .indexes
+            .iter()
+            .filter(|i| i.unique)
+            .map(|i| i.name.clone())
+            .collect();
+        unique.sort();
+        match meta::read_unique_indexes(&self.db)? {
+            Some(recorded) if recorded == unique => return Ok(()),
+            Some(_) => {}
+            None => {
+                let legacy: Vec<String> = self
+                    .indexes
+                    .iter()
+                    .filter(|i| !i.unique)
+                    .map(|i| i.name.clone())
+                    .collect();
+                for name in legacy {
+                    self.rebuild_named(&name)?;
+                }
+            }
+        }
+        meta::write_unique_indexes(&self.db, &unique)
+    }
+
     fn cf(&self, name: &str) -> Result<&ColumnFamily> {
         self.db
             .cf_handle(name)
@@ -288,9 +343,10 @@ where
                     }
                     txn.put_cf(idx_cf, &nsk, &key_bytes).map_err(Error::from)?;
                 } else {
+                    let value = entry_value(&nsk);
                     let mut entry = nsk;
                     entry.extend_from_slice(&key_bytes);
-                    txn.put_cf(idx_cf, &entry, b"").map_err(Error::from)?;
+                    txn.put_cf(idx_cf, &entry, value).map_err(Error::from)?;
                 }
             }
         }
@@ -340,7 +396,7 @@ where
     ) -> Result<Vec<K>> {
         let def = self.index_def(&index.name)?;
         let idx_cf = self.cf(&def.cf_name)?;
-        let sk_bytes = <OrderedCodec<SK> as KeyCodec<SK>>::encode(secondary_key)?;
+        let sk_bytes = encode_collation(secondary_key);
 
         if def.unique {
             return match self.db.get_cf(idx_cf, &sk_bytes).map_err(Error::from)? {
@@ -429,9 +485,10 @@ where
                         .put_cf(idx_cf, &sk, &key_bytes)
                         .map_err(Error::from)?;
                 } else {
+                    let value = entry_value(&sk);
                     let mut entry = sk;
                     entry.extend_from_slice(&key_bytes);
-                    self.db.put_cf(idx_cf, &entry, b"").map_err(Error::from)?;
+                    self.db.put_cf(idx_cf, &entry, value).map_err(Error::from)?;
                 }
             }
         }
@@ -450,6 +507,44 @@ where
         Ok(())
     }
 
+    /// Check that every row decodes and that each index agrees with the data both ways: every
+    /// entry points at a row that produces it, every row has its entries, and no two rows share
+    /// a unique secondary key. [`VerifyMode::Quarantine`] moves undecodable rows and dangling
+    /// entries aside; missing entries are only reported ([`rebuild_all`](Self::rebuild_all)
+    /// restores them). Holds the expected entries of every index in memory while checking.
+    pub fn verify(&self, mode: VerifyMode) -> Result<VerifyReport> {
+        let mut verifier = Verifier::new(&self.db, mode, MapKind::Indexed);
+        let mut expected: Vec<Vec<ExpectedEntry>> =
+            self.indexes.iter().map(|_| Vec::new()).collect();
+        verifier.check_rows::<K, V, OrderedCodec<K>>(DATA_CF, RowLayout::Bare, |pk, value| {
+            for (idx, entries) in self.indexes.iter().zip(expected.iter_mut()) {
+                if let Some(sk) = (idx.extract)(&value)? {
+                    entries.push(if idx.unique {
+                        ExpectedEntry {
+                            entry: sk,
+                            value: pk.to_vec(),
+                            pk: pk.to_vec(),
+                        }
+                    } else {
+                        let value = entry_value(&sk).to_vec();
+                        let mut entry = sk;
+                        entry.extend_from_slice(pk);
+                        ExpectedEntry {
+                            entry,
+                            value,
+                            pk: pk.to_vec(),
+                        }
+                    });
+                }
+            }
+            Ok(())
+        })?;
+        for (idx, entries) in self.indexes.iter().zip(expected) {
+            verifier.check_index_entries(&idx.name, idx.unique, entries)?;
+        }
+        Ok(verifier.finish())
+    }
+
     /// Access the underlying transactional RocksDB handle.
     pub fn db(&self) -> &TransactionDB {
         &self.db
@@ -595,6 +690,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn entries_without_a_secondary_key_length_are_rebuilt_at_open() {
+        let dir = TempDir::new().unwrap();
+        let open = || {
+            let mut builder = IndexedRocksMap::<u64, User>::builder(dir.path());
+            let by_org = builder.index("by_org", |u: &User| Some(u.org.clone()));
+            builder.unique_index("by_email", |u: &User| Some(u.email.clone()));
+            (builder.open().unwrap(), by_org)
+        };
+        let (map, _) = open();
+        map.put(1, &user(1, "a@x.com", "x")).unwrap();
+        map.put(2, &user(2, "b@x.com", "x")).unwrap();
+
+        // Rewrite the entries the way they were stored before the length was recorded.
+        let idx_cf = map.cf("__idx_by_org").unwrap();
+        let entries: Vec<Box<[u8]>> = map
+            .db()
+            .iterator_cf(idx_cf, IteratorMode::Start)
+            .map(|r| r.unwrap().0)
+            .collect();
+        for entry in &entries {
+            map.db().put_cf(idx_cf, entry, b"").unwrap();
+        }
+        let meta_cf = map.cf(meta::META_CF).unwrap();
+        map.db().delete_cf(meta_cf, b"unique_indexes").unwrap();
+        drop(map);
+
+        let report =
+            crate::verify::verify::<u64, User, OrderedCodec<u64>, _>(dir.path(), VerifyMode::Check)
+                .unwrap();
+        assert_eq!(report.problems.len(), 2, "{report:?}"); // one per index
+
+        let (map, by_org) = open();
+        let idx_cf = map.cf("__idx_by_org").unwrap();
+        for item in map.db().iterator_cf(idx_cf, IteratorMode::Start) {
+            let (entry, value) = item.unwrap();
+            assert_eq!(entry_primary_key(&entry, &value).map(<[u8]>::len), Some(8));
+        }
+        assert_eq!(
+            meta::read_unique_indexes(map.db()).unwrap(),
+            Some(vec!["by_email".to_string()])
+        );
+        assert_eq!(
+            map.find_keys_by(&by_org, &"x".to_string()).unwrap(),
+            vec![1, 2]
+        );
+        assert!(map.verify(VerifyMode::Check).unwrap().is_clean());
+    }
+
     #[test]
     fn rebuild_repopulates_index() {
         let dir = TempDir::new().unwrap();
@@ -1,23 +1,35 @@
 //! Read-only introspection of a rocksmap database's metadata.
 //!
-//! Lets tooling (e.g. `rocksmap-cli`) discover what a database *is* — plain, TTL, or indexed, and
-//! which key codec / indexes it uses — without knowing its typed generics, and without mutating it.
+//! Lets tooling (e.g. `rocksmap-cli`) discover what a database *is* — plain, TTL, indexed, or versioned, and
+//! which key codec / indexes / key and value types it uses — without knowing its typed generics, and without mutating it.
 
 use crate::error::{Error, Result};
 use crate::meta::{self, MapKind};
+use crate::schema::TypeFingerprint;
 use rocksdb::{ColumnFamilyDescriptor, Options, DB};
 use std::path::Path;
 
 /// A read-only summary of a rocksmap database.
 #[derive(Debug, Clone)]
 pub struct DbInfo {
-    /// Plain, TTL, or indexed.
+    /// Plain, TTL, indexed, or versioned.
     pub kind: MapKind,
     /// Recorded key-codec id (`1` = ordered, `2` = bincode), if written.
     pub key_codec_id: Option<u8>,
     /// Declared secondary-index names (empty unless `kind` is indexed).
     pub indexes: Vec<String>,
-    /// All column families present on disk, including internal ones (`__rocksmap_meta`, `__idx_*`).
+    /// The key/value types the database was created with, if recorded.
+    pub types: Option<TypeFingerprint>,
+    /// The current value schema version (only recorded for versioned maps).
+    pub value_version: Option<u16>,
+    /// Whether a TTL map keeps the expiry index (`__ttl_expiry`, keyed by the big-endian
+    /// deadline followed by the encoded key). TTL databases gain it when next opened for writing.
+    pub ttl_expiry_index: bool,
+    /// The TTL tables of a TTL map besides `default`: column families whose values are TTL
+    /// envelopes, each with its own expiry index (`__ttl_expiry_<table>`).
+    pub ttl_tables: Vec<String>,
+    /// All column families present on disk, including internal ones (`__rocksmap_meta`,
+    /// `__idx_*`, `__ttl_expiry*`).
     pub column_families: Vec<String>,
 }
 
@@ -39,7 +51,8 @@ pub fn inspect<P: AsRef<Path>>(path: P) -> Result<DbInfo> {
 
     if db.cf_handle(meta::META_CF).is_none() {
         return Err(Error::FormatMismatch(
-            "not a rocksmap-managed database (no metadata column family)".to_string(),
+            "not a rocksmap-managed database (no metadata column family; see `rocksmap::adopt`)"
+                .to_string(),
         ));
     }
 
@@ -48,11 +61,19 @@ pub fn inspect<P: AsRef<Path>>(path: P) -> Result<DbInfo> {
     })?;
     let key_codec_id = meta::read_key_codec(&db)?;
     let indexes = meta::read_indexes(&db)?;
+    let types = meta::read_types(&db)?;
+    let value_version = meta::read_value_version(&db)?;
+    let ttl_expiry_index = meta::is_ttl_expiry_indexed(&db)?;
+    let ttl_tables = meta::read_ttl_tables(&db)?;
 
     Ok(DbInfo {
         kind,
         key_codec_id,
         indexes,
+        types,
+        value_version,
+        ttl_expiry_index,
+        ttl_tables,
         column_families,
     })
 }
@@ -13,36 +13,83 @@
 //! the default, matching RocksDB and every embedded-store peer. For power-loss durability, call
 //! [`RocksMap::sync_wal`] (or [`TtlRocksMap::sync_wal`]) at a checkpoint; it costs one fsync.
 //!
-//! **Atomicity holds regardless of durability mode:** a [`RocksMapBatch`] and every
-//! [`IndexedRocksMap`] operation is all-or-nothing — a partial batch, or a data row without its
-//! index entries, never becomes visible, even across a crash.
+//! **Atomicity holds regardless of durability mode:** a [`RocksMapBatch`] or
+//! [`TtlRocksMapBatch`] and every [`IndexedRocksMap`] operation is all-or-nothing — a partial
+//! batch, or a data row without its index entries, never becomes visible, even across a crash.
 
 #![forbid(unsafe_code)]
 #![deny(missing_docs)]
 
+mod adopt;
+pub mod backup;
 mod batch;
+mod cache;
+pub mod cdc;
 mod clock;
 mod codec;
+mod collate;
+pub mod convert;
 mod error;
+mod format;
 mod index;
 mod inspect;
 mod meta;
 mod ordered;
+mod reclaim;
+mod replication;
 mod rocks_map;
+mod schema;
+mod sweeper;
 mod ttl;
+mod verify;
+mod versioned;
+mod wal;
 
-pub use crate::batch::RocksMapBatch;
-pub use crate::clock::{Clock, ManualClock, SystemClock};
+pub use crate::adopt::{adopt, AdoptReport, AdoptScan};
+pub use crate::batch::{RocksMapBatch, TtlRocksMapBatch};
+pub use crate::cache::{CacheOptions, CacheStats, EvictionPolicy};
+pub use crate::cdc::{Change, ChangeCheckpoint, ChangeOp, ChangeStream};
+pub use crate::clock::{
+    Clock, ClockSkew, HybridClock, ManualClock, MonotonicGuardClock, SystemClock,
+};
 pub use crate::codec::{BincodeCodec, KeyCodec, ValueCodec};
+pub use crate::collate::CaseInsensitive;
+#[cfg(feature = "unicode")]
+pub use crate::collate::{Nfc, Nfkc};
 pub use crate::error::{Error, Result};
+pub use crate::format::{plan_format_upgrade, upgrade_format, FormatUpgradeReport, OpenOptions};
 pub use crate::index::{Index, IndexedRocksMap, IndexedRocksMapBuilder};
 pub use crate::inspect::{inspect, DbInfo};
 pub use crate::meta::MapKind;
 pub use crate::ordered::{
-    OrderedCodec, OrderedF32, OrderedF64, OrderedKey, OrderedKeyCodec, PrefixKey,
+    Desc, OrderedCodec, OrderedF32, OrderedF64, OrderedKey, OrderedKeyCodec, PrefixKey, VarI64,
+    VarU64,
 };
+pub use crate::reclaim::TtlStats;
+pub use crate::replication::{ReplicaFollower, ReplicationServer, ReplicationSource, SyncReport};
 pub use crate::rocks_map::{RocksMap, RocksMapIterator};
-pub use crate::ttl::{strip_ttl_envelope, TtlIterator, TtlRocksMap};
+pub use crate::schema::{accept_type_change, TypeFingerprint};
+pub use crate::sweeper::{ExpirySweeper, SweepOptions};
+pub use crate::ttl::{
+    strip_ttl_envelope, ExpiringIterator, TtlIterator, TtlRocksMap, TtlRocksMapBuilder,
+};
+pub use crate::verify::{
+    quarantined, verify, ProblemKind, QuarantinedRow, VerifyMode, VerifyProblem, VerifyReport,
+};
+pub use crate::versioned::{
+    strip_version_envelope, Versioned, VersionedIterator, VersionedRocksMap,
+    VersionedRocksMapBuilder,
+};
+
+/// Derive [`OrderedKey`] for structs and enums (requires the `derive` feature).
+#[cfg(feature = "derive")]
+pub use rocksmap_derive::OrderedKey;
+
+/// Support code for `#[derive(OrderedKey)]`; not a public API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::ordered::{decode_desc, encode_collation_desc, encode_desc};
+}
 
 /// Re-export important RocksDB types and options for configuration
 pub mod rocks {
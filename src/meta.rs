@@ -1,24 +1,53 @@
 //! Database metadata stored in a dedicated `__rocksmap_meta` column family.
 //!
-//! Records the format version, the map "kind" (plain / TTL / indexed), and (for indexed maps)
-//! the declared index names and any in-progress rebuild. Reopening a database the wrong way
+//! Records the format version, the map "kind" (plain / TTL / indexed / versioned), (for indexed
+//! maps) the declared index names and any in-progress rebuild, and (for versioned maps) the
+//! current value schema version. Reopening a database the wrong way
 //! (e.g. a TTL store as a plain map, or with a different index set) fails loudly via
 //! [`Error::FormatMismatch`] instead of mis-decoding values. Kept in its own column family so
 //! it never appears in user iteration.
 
 use crate::error::{Error, Result};
-use rocksdb::{ColumnFamily, Options, TransactionDB, DB};
+use crate::schema::TypeFingerprint;
+use rocksdb::{
+    ColumnFamily, ColumnFamilyDescriptor, Direction, IteratorMode, Options, TransactionDB,
+    WriteBatch, WriteBatchWithTransaction, DB,
+};
 use std::collections::BTreeSet;
 use std::path::Path;
 
 /// Name of the reserved metadata column family.
 pub const META_CF: &str = "__rocksmap_meta";
+/// Expiry index of a TTL map: `deadline (u64 BE) ++ key -> ()`. A TTL table other than the
+/// default column family has its own, named by [`ttl_expiry_cf`].
+pub const TTL_EXPIRY_CF: &str = "__ttl_expiry";
+/// Access metadata of a TTL map in cache mode: each entry's eviction rank and size, and the
+/// entries in eviction order.
+pub const CACHE_CF: &str = "__rocksmap_cache";
 
 const SCHEMA_KEY: &[u8] = b"schema";
 const INDEXES_KEY: &[u8] = b"indexes";
 const REBUILD_KEY: &[u8] = b"rebuilding";
 const KEY_CODEC_KEY: &[u8] = b"key_codec";
-const FORMAT_VERSION: u16 = 1;
+const APPLIED_SEQUENCE_KEY: &[u8] = b"replica_applied_seq";
+const QUARANTINE_PREFIX: &[u8] = b"quarantine/";
+const TYPES_KEY: &[u8] = b"type_shapes";
+/// Where type fingerprints were kept when they were `std::any::type_name`s, which are not stable
+/// across compilers; such a record is ignored and replaced.
+const LEGACY_TYPES_KEY: &[u8] = b"types";
+const VALUE_VERSION_KEY: &[u8] = b"value_version";
+const STORED_VERSIONS_KEY: &[u8] = b"stored_versions";
+const FORMAT_UPGRADE_KEY: &[u8] = b"format_upgrading";
+const REKEY_KEY: &[u8] = b"rekeying";
+const TTL_EXPIRY_INDEXED_KEY: &[u8] = b"ttl_expiry_indexed";
+const TTL_TABLE_PREFIX: &[u8] = b"ttl_table/";
+const CLOCK_HIGH_WATER_KEY: &[u8] = b"clock_high_water";
+const CACHE_POLICY_KEY: &[u8] = b"cache_policy";
+const UNIQUE_INDEXES_KEY: &[u8] = b"unique_indexes";
+
+/// The on-disk format version this build writes. Older databases are brought up to it by the
+/// steps in [`format`](crate::format).
+pub const FORMAT_VERSION: u16 = 1;
 
 /// How a database's values are laid out on disk.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -29,6 +58,8 @@ pub enum MapKind {
     Ttl,
     /// Indexed dataset (data CF + index CFs, transactional).
     Indexed,
+    /// Schema-versioned values (version header + payload).
+    Versioned,
 }
 
 impl MapKind {
@@ -37,6 +68,7 @@ impl MapKind {
             MapKind::Plain => 0,
             MapKind::Ttl => 1,
             MapKind::Indexed => 2,
+            MapKind::Versioned => 3,
         }
     }
 
@@ -44,16 +76,29 @@ impl MapKind {
         label_of(self.tag())
     }
 
-    /// Human-readable name of this kind (`"plain"` / `"ttl"` / `"indexed"`).
+    /// Human-readable name of this kind (`"plain"` / `"ttl"` / `"indexed"` / `"versioned"`).
     pub fn as_str(self) -> &'static str {
         self.label()
     }
 
+    /// The kind named by [`as_str`](Self::as_str), if `name` is one.
+    pub(crate) fn parse(name: &str) -> Option<Self> {
+        [
+            MapKind::Plain,
+            MapKind::Ttl,
+            MapKind::Indexed,
+            MapKind::Versioned,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str() == name)
+    }
+
     fn from_tag(tag: u8) -> Result<Self> {
         match tag {
             0 => Ok(MapKind::Plain),
             1 => Ok(MapKind::Ttl),
             2 => Ok(MapKind::Indexed),
+            3 => Ok(MapKind::Versioned),
             other => Err(Error::FormatMismatch(format!(
                 "unknown map kind tag {other}"
             ))),
@@ -72,6 +117,7 @@ fn label_of(tag: u8) -> &'static str {
         0 => "plain",
         1 => "ttl",
         2 => "indexed",
+        3 => "versioned",
         _ => "unknown",
     }
 }
@@ -79,13 +125,42 @@ fn label_of(tag: u8) -> &'static str {
 /// Minimal key-value access over the metadata column family, implemented for both the plain
 /// [`DB`] and the transactional [`TransactionDB`].
 pub trait KvStore {
+    fn path(&self) -> &Path;
     fn cf(&self, name: &str) -> Option<&ColumnFamily>;
     fn get_raw(&self, cf: &ColumnFamily, key: &[u8]) -> Result<Option<Vec<u8>>>;
     fn put_raw(&self, cf: &ColumnFamily, key: &[u8], value: &[u8]) -> Result<()>;
     fn delete_raw(&self, cf: &ColumnFamily, key: &[u8]) -> Result<()>;
+    fn scan_raw<'a>(&'a self, cf: &'a ColumnFamily, from: &[u8]) -> RawScan<'a>;
+    /// Apply `writes` atomically.
+    fn write_raw(&self, writes: &[RawWrite<'_>]) -> Result<()>;
 }
 
+/// One write of a [`KvStore::write_raw`] batch.
+pub enum RawWrite<'a> {
+    /// Set `key` to `value` in the column family.
+    Put(&'a ColumnFamily, &'a [u8], &'a [u8]),
+    /// Delete `key` from the column family.
+    Delete(&'a ColumnFamily, &'a [u8]),
+}
+
+fn batch_of<const T: bool>(writes: &[RawWrite<'_>]) -> WriteBatchWithTransaction<T> {
+    let mut batch = WriteBatchWithTransaction::<T>::default();
+    for write in writes {
+        match write {
+            RawWrite::Put(cf, key, value) => batch.put_cf(*cf, key, value),
+            RawWrite::Delete(cf, key) => batch.delete_cf(*cf, key),
+        }
+    }
+    batch
+}
+
+/// Raw `(key, value)` pairs of a column family, as returned by [`KvStore::scan_raw`].
+pub type RawScan<'a> = Box<dyn Iterator<Item = Result<(Box<[u8]>, Box<[u8]>)>> + 'a>;
+
 impl KvStore for DB {
+    fn path(&self) -> &Path {
+        DB::path(self)
+    }
     fn cf(&self, name: &str) -> Option<&ColumnFamily> {
         self.cf_handle(name)
     }
@@ -98,9 +173,21 @@ impl KvStore for DB {
     fn delete_raw(&self, cf: &ColumnFamily, key: &[u8]) -> Result<()> {
         self.delete_cf(cf, key).map_err(Error::from)
     }
+    fn scan_raw<'a>(&'a self, cf: &'a ColumnFamily, from: &[u8]) -> RawScan<'a> {
+        Box::new(
+            self.iterator_cf(cf, IteratorMode::From(from, Direction::Forward))
+                .map(|item| item.map_err(Error::from)),
+        )
+    }
+    fn write_raw(&self, writes: &[RawWrite<'_>]) -> Result<()> {
+        self.write(batch_of(writes)).map_err(Error::from)
+    }
 }
 
 impl KvStore for TransactionDB {
+    fn path(&self) -> &Path {
+        TransactionDB::path(self)
+    }
     fn cf(&self, name: &str) -> Option<&ColumnFamily> {
         self.cf_handle(name)
     }
@@ -113,6 +200,15 @@ impl KvStore for TransactionDB {
     fn delete_raw(&self, cf: &ColumnFamily, key: &[u8]) -> Result<()> {
         self.delete_cf(cf, key).map_err(Error::from)
     }
+    fn scan_raw<'a>(&'a self, cf: &'a ColumnFamily, from: &[u8]) -> RawScan<'a> {
+        Box::new(
+            self.iterator_cf(cf, IteratorMode::From(from, Direction::Forward))
+                .map(|item| item.map_err(Error::from)),
+        )
+    }
+    fn write_raw(&self, writes: &[RawWrite<'_>]) -> Result<()> {
+        self.write(batch_of(writes)).map_err(Error::from)
+    }
 }
 
 fn meta_cf<S: KvStore>(store: &S) -> Result<&ColumnFamily> {
@@ -121,6 +217,43 @@ fn meta_cf<S: KvStore>(store: &S) -> Result<&ColumnFamily> {
         .ok_or_else(|| Error::Other(format!("missing `{META_CF}` column family")))
 }
 
+/// Whether `name` is a column family rocksmap maintains internally (metadata, index entries,
+/// the TTL expiry indexes, or scratch space for a conversion) rather than one holding user data.
+pub fn is_internal_cf(name: &str) -> bool {
+    name.starts_with("__rocksmap_") || name.starts_with("__idx_") || name.starts_with(TTL_EXPIRY_CF)
+}
+
+/// The expiry index column family of the TTL table stored in column family `table`.
+pub fn ttl_expiry_cf(table: &str) -> String {
+    if table == "default" {
+        TTL_EXPIRY_CF.to_string()
+    } else {
+        format!("{TTL_EXPIRY_CF}_{table}")
+    }
+}
+
+/// Open the existing rocksmap-managed database at `path` with every column family, without
+/// knowing its types. Errors if it has no metadata column family.
+pub fn open_managed(path: &Path, read_only: bool) -> Result<DB> {
+    let opts = Options::default();
+    let descriptors: Vec<ColumnFamilyDescriptor> = existing_cfs(&opts, path)
+        .iter()
+        .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+        .collect();
+    let db = if read_only {
+        DB::open_cf_descriptors_read_only(&opts, path, descriptors, false)
+    } else {
+        DB::open_cf_descriptors(&opts, path, descriptors)
+    }
+    .map_err(Error::from)?;
+    if db.cf_handle(META_CF).is_none() {
+        return Err(Error::FormatMismatch(
+            "not a rocksmap-managed database (no metadata column family)".to_string(),
+        ));
+    }
+    Ok(db)
+}
+
 /// Existing column families for the database at `path`, or `["default"]` if it does not exist
 /// yet (a fresh database).
 pub fn existing_cfs(opts: &Options, path: &Path) -> Vec<String> {
@@ -139,7 +272,49 @@ pub fn all_cf_names(opts: &Options, path: &Path, extra: &[&str]) -> Vec<String>
     names.into_iter().collect()
 }
 
-/// Verify the stored kind matches `kind`, writing it if the database is fresh.
+/// Whether any user column family holds a row.
+fn holds_user_data<S: KvStore>(store: &S) -> Result<bool> {
+    for name in existing_cfs(&Options::default(), store.path()) {
+        if is_internal_cf(&name) {
+            continue;
+        }
+        if let Some(cf) = store.cf(&name) {
+            if let Some(item) = store.scan_raw(cf, &[]).next() {
+                item?;
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Record `kind` (and the current format version) for a database that has no metadata yet.
+pub fn write_kind<S: KvStore>(store: &S, kind: MapKind) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.put_raw(cf, SCHEMA_KEY, &kind_record(kind))
+}
+
+fn kind_record(kind: MapKind) -> Vec<u8> {
+    let mut record = Vec::with_capacity(3);
+    record.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+    record.push(kind.tag());
+    record
+}
+
+/// Record `kind`, key codec `key_codec` and `types` for a database that has no metadata yet, in
+/// one write: an interrupted adoption leaves the database either untagged or fully tagged.
+pub fn write_adopted(db: &DB, kind: MapKind, key_codec: u8, types: &TypeFingerprint) -> Result<()> {
+    let cf = meta_cf(db)?;
+    let mut batch = WriteBatch::default();
+    batch.put_cf(cf, SCHEMA_KEY, kind_record(kind));
+    batch.put_cf(cf, KEY_CODEC_KEY, [key_codec]);
+    batch.put_cf(cf, TYPES_KEY, types_record(types)?);
+    db.write(batch).map_err(Error::from)
+}
+
+/// Verify the stored kind matches `kind`, writing it if the database is fresh. A database with
+/// data but no metadata is not fresh: it must be checked and tagged by
+/// [`adopt`](crate::adopt) first.
 pub fn verify_or_write_kind<S: KvStore>(store: &S, kind: MapKind) -> Result<()> {
     let cf = meta_cf(store)?;
     match store.get_raw(cf, SCHEMA_KEY)? {
@@ -148,6 +323,12 @@ pub fn verify_or_write_kind<S: KvStore>(store: &S, kind: MapKind) -> Result<()>
                 return Err(Error::FormatMismatch("corrupt metadata record".to_string()));
             }
             let version = u16::from_be_bytes([bytes[0], bytes[1]]);
+            if version < FORMAT_VERSION {
+                return Err(Error::FormatMismatch(format!(
+                    "database uses on-disk format version {version}, this build writes \
+                     {FORMAT_VERSION} (open with `OpenOptions::allow_format_upgrade` to upgrade it)"
+                )));
+            }
             if version != FORMAT_VERSION {
                 return Err(Error::FormatMismatch(format!(
                     "unsupported on-disk format version {version} (this build supports {FORMAT_VERSION})"
@@ -163,12 +344,11 @@ pub fn verify_or_write_kind<S: KvStore>(store: &S, kind: MapKind) -> Result<()>
             }
             Ok(())
         }
-        None => {
-            let mut record = Vec::with_capacity(3);
-            record.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
-            record.push(kind.tag());
-            store.put_raw(cf, SCHEMA_KEY, &record)
-        }
+        None if holds_user_data(store)? => Err(Error::FormatMismatch(format!(
+            "{} holds data but no rocksmap metadata (check and tag it with `rocksmap::adopt` first)",
+            store.path().display()
+        ))),
+        None => write_kind(store, kind),
     }
 }
 
@@ -198,6 +378,28 @@ pub fn read_kind<S: KvStore>(store: &S) -> Result<Option<MapKind>> {
     }
 }
 
+/// Read the recorded on-disk format version (read-only; `None` if the metadata has never been
+/// written).
+pub fn read_format_version<S: KvStore>(store: &S) -> Result<Option<u16>> {
+    let cf = meta_cf(store)?;
+    match store.get_raw(cf, SCHEMA_KEY)? {
+        Some(bytes) if bytes.len() >= 3 => Ok(Some(u16::from_be_bytes([bytes[0], bytes[1]]))),
+        Some(_) => Err(Error::FormatMismatch("corrupt metadata record".to_string())),
+        None => Ok(None),
+    }
+}
+
+/// Record `version` as the on-disk format version, keeping the recorded kind.
+pub fn write_format_version<S: KvStore>(store: &S, version: u16) -> Result<()> {
+    let cf = meta_cf(store)?;
+    let mut record = store
+        .get_raw(cf, SCHEMA_KEY)?
+        .filter(|bytes| bytes.len() >= 3)
+        .ok_or_else(|| Error::FormatMismatch("corrupt metadata record".to_string()))?;
+    record[..2].copy_from_slice(&version.to_be_bytes());
+    store.put_raw(cf, SCHEMA_KEY, &record)
+}
+
 /// Read the recorded key-codec id (read-only; `None` if never written).
 pub fn read_key_codec<S: KvStore>(store: &S) -> Result<Option<u8>> {
     let cf = meta_cf(store)?;
@@ -206,6 +408,27 @@ pub fn read_key_codec<S: KvStore>(store: &S) -> Result<Option<u8>> {
         .and_then(|b| b.first().copied()))
 }
 
+/// Record which of the declared indexes are unique. Written once every non-unique entry stores
+/// the length of its secondary key (entries written before that stored nothing).
+pub fn write_unique_indexes<S: KvStore>(store: &S, sorted_names: &[String]) -> Result<()> {
+    let cf = meta_cf(store)?;
+    let bytes =
+        bincode::serialize(sorted_names).map_err(|e| Error::Serialization(e.to_string()))?;
+    store.put_raw(cf, UNIQUE_INDEXES_KEY, &bytes)
+}
+
+/// Read the names of the unique indexes (read-only; `None` if the index entries predate the
+/// record, see [`write_unique_indexes`]).
+pub fn read_unique_indexes<S: KvStore>(store: &S) -> Result<Option<Vec<String>>> {
+    let cf = meta_cf(store)?;
+    match store.get_raw(cf, UNIQUE_INDEXES_KEY)? {
+        Some(bytes) => bincode::deserialize(&bytes)
+            .map(Some)
+            .map_err(|_| Error::FormatMismatch("corrupt unique-index record".to_string())),
+        None => Ok(None),
+    }
+}
+
 /// Read the declared index names (read-only; empty if none).
 pub fn read_indexes<S: KvStore>(store: &S) -> Result<Vec<String>> {
     let cf = meta_cf(store)?;
@@ -215,9 +438,88 @@ pub fn read_indexes<S: KvStore>(store: &S) -> Result<Vec<String>> {
     }
 }
 
-/// Verify the stored key-codec id matches `id`, writing it if the database is fresh.
+/// Read the recorded key/value type fingerprint (read-only; `None` if never written).
+pub fn read_types<S: KvStore>(store: &S) -> Result<Option<TypeFingerprint>> {
+    let cf = meta_cf(store)?;
+    match store.get_raw(cf, TYPES_KEY)? {
+        Some(bytes) => {
+            let (key, value): (String, String) = bincode::deserialize(&bytes).map_err(|_| {
+                Error::FormatMismatch("corrupt type fingerprint record".to_string())
+            })?;
+            Ok(Some(TypeFingerprint { key, value }))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Record the key/value type fingerprint, replacing any previous one.
+pub fn write_types<S: KvStore>(store: &S, types: &TypeFingerprint) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.put_raw(cf, TYPES_KEY, &types_record(types)?)?;
+    store.delete_raw(cf, LEGACY_TYPES_KEY)
+}
+
+fn types_record(types: &TypeFingerprint) -> Result<Vec<u8>> {
+    bincode::serialize(&(&types.key, &types.value)).map_err(|e| Error::Serialization(e.to_string()))
+}
+
+/// Read the recorded value schema version of a versioned map (`None` if never written).
+pub fn read_value_version<S: KvStore>(store: &S) -> Result<Option<u16>> {
+    let cf = meta_cf(store)?;
+    match store.get_raw(cf, VALUE_VERSION_KEY)? {
+        Some(bytes) => {
+            let bytes: [u8; 2] = bytes
+                .as_slice()
+                .try_into()
+                .map_err(|_| Error::FormatMismatch("corrupt value version record".to_string()))?;
+            Ok(Some(u16::from_be_bytes(bytes)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Record the value schema version of a versioned map.
+pub fn write_value_version<S: KvStore>(store: &S, version: u16) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.put_raw(cf, VALUE_VERSION_KEY, &version.to_be_bytes())
+}
+
+/// Read the value versions the rows of a versioned map may be stored at (read-only; `None` if
+/// never written).
+pub fn read_stored_versions<S: KvStore>(store: &S) -> Result<Option<BTreeSet<u16>>> {
+    let cf = meta_cf(store)?;
+    match store.get_raw(cf, STORED_VERSIONS_KEY)? {
+        Some(bytes) if bytes.len() % 2 == 0 => Ok(Some(
+            bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect(),
+        )),
+        Some(_) => Err(Error::FormatMismatch(
+            "corrupt stored versions record".to_string(),
+        )),
+        None => Ok(None),
+    }
+}
+
+/// Record the value versions the rows of a versioned map may be stored at.
+pub fn write_stored_versions<S: KvStore>(store: &S, versions: &BTreeSet<u16>) -> Result<()> {
+    let cf = meta_cf(store)?;
+    let bytes: Vec<u8> = versions.iter().flat_map(|v| v.to_be_bytes()).collect();
+    store.put_raw(cf, STORED_VERSIONS_KEY, &bytes)
+}
+
+/// Verify the stored key-codec id matches `id`, writing it if the database is fresh. Fails while
+/// a key conversion is unfinished, as the keys are then in a mix of codecs.
 pub fn verify_or_write_key_codec<S: KvStore>(store: &S, id: u8) -> Result<()> {
     let cf = meta_cf(store)?;
+    if get_rekeying(store)?.is_some() {
+        return Err(Error::FormatMismatch(
+            "a key codec conversion was interrupted \
+             (run `rocksmap::convert::rekey_in_place` again to finish it)"
+                .to_string(),
+        ));
+    }
     match store.get_raw(cf, KEY_CODEC_KEY)? {
         Some(have) if have.first() == Some(&id) => Ok(()),
         Some(have) => Err(Error::FormatMismatch(format!(
@@ -228,6 +530,153 @@ pub fn verify_or_write_key_codec<S: KvStore>(store: &S, id: u8) -> Result<()> {
     }
 }
 
+/// Whether a TTL map's expiry index has been built for the rows already stored (it is then kept
+/// up to date by every write).
+pub fn is_ttl_expiry_indexed<S: KvStore>(store: &S) -> Result<bool> {
+    let cf = meta_cf(store)?;
+    Ok(store.get_raw(cf, TTL_EXPIRY_INDEXED_KEY)?.is_some())
+}
+
+/// Record that a TTL map's expiry index covers every stored row.
+pub fn mark_ttl_expiry_indexed<S: KvStore>(store: &S) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.put_raw(cf, TTL_EXPIRY_INDEXED_KEY, &[])
+}
+
+/// Record that column family `table` is a TTL table: its values are TTL envelopes and its
+/// expiry index covers every row.
+pub fn mark_ttl_table<S: KvStore>(store: &S, table: &str) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.put_raw(cf, &ttl_table_key(table), &[])
+}
+
+/// The column families recorded as TTL tables besides `default`, in name order.
+pub fn read_ttl_tables<S: KvStore>(store: &S) -> Result<Vec<String>> {
+    let cf = meta_cf(store)?;
+    let mut tables = Vec::new();
+    for item in store.scan_raw(cf, TTL_TABLE_PREFIX) {
+        let (key, _) = item?;
+        let Some(name) = key.strip_prefix(TTL_TABLE_PREFIX) else {
+            break;
+        };
+        tables.push(String::from_utf8_lossy(name).into_owned());
+    }
+    Ok(tables)
+}
+
+fn ttl_table_key(table: &str) -> Vec<u8> {
+    let mut key = TTL_TABLE_PREFIX.to_vec();
+    key.extend_from_slice(table.as_bytes());
+    key
+}
+
+/// The `(key, value)` metadata record holding the high-water mark of a TTL map's clock, for
+/// writing atomically alongside the map's own writes.
+pub fn clock_high_water_record(millis: u64) -> (&'static [u8], [u8; 8]) {
+    (CLOCK_HIGH_WATER_KEY, millis.to_be_bytes())
+}
+
+/// The persisted high-water mark of a TTL map's clock (`None` if never written).
+pub fn read_clock_high_water<S: KvStore>(store: &S) -> Result<Option<u64>> {
+    let cf = meta_cf(store)?;
+    match store.get_raw(cf, CLOCK_HIGH_WATER_KEY)? {
+        Some(bytes) => {
+            let bytes: [u8; 8] = bytes.as_slice().try_into().map_err(|_| {
+                Error::FormatMismatch("corrupt clock high-water record".to_string())
+            })?;
+            Ok(Some(u64::from_be_bytes(bytes)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// The eviction policy the cache metadata of a TTL map was kept for, if it is up to date
+/// (`None` if the map is not in cache mode, or was last opened without it).
+pub fn read_cache_policy<S: KvStore>(store: &S) -> Result<Option<u8>> {
+    let cf = meta_cf(store)?;
+    Ok(store
+        .get_raw(cf, CACHE_POLICY_KEY)?
+        .and_then(|b| b.first().copied()))
+}
+
+/// Record that the cache metadata of a TTL map covers every row, ranked by eviction policy
+/// `policy`.
+pub fn write_cache_policy<S: KvStore>(store: &S, policy: u8) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.put_raw(cf, CACHE_POLICY_KEY, &[policy])
+}
+
+/// Record that the cache metadata of a TTL map is no longer kept up to date.
+pub fn clear_cache_policy<S: KvStore>(store: &S) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.delete_raw(cf, CACHE_POLICY_KEY)
+}
+
+/// Record `id` as the key-codec id, replacing the previous one.
+pub fn write_key_codec<S: KvStore>(store: &S, id: u8) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.put_raw(cf, KEY_CODEC_KEY, &[id])
+}
+
+/// Progress of an in-place key codec conversion through one column family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RekeyPhase {
+    /// Re-encoded rows are being copied into the scratch column family.
+    Stage = 0,
+    /// The column family is being replaced by the scratch copy.
+    Swap = 1,
+    /// The column family is converted.
+    Done = 2,
+}
+
+/// An in-place key codec conversion in progress: converting to codec `to`, currently at `phase`
+/// of column family `column_family` (column families are converted in name order).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RekeyMarker {
+    /// The key-codec id being converted to.
+    pub to: u8,
+    /// The column family being converted.
+    pub column_family: String,
+    /// How far its conversion got.
+    pub phase: RekeyPhase,
+}
+
+/// Record the progress of an in-place key codec conversion.
+pub fn set_rekeying<S: KvStore>(store: &S, marker: &RekeyMarker) -> Result<()> {
+    let cf = meta_cf(store)?;
+    let bytes = bincode::serialize(&(marker.to, &marker.column_family, marker.phase as u8))
+        .map_err(|e| Error::Serialization(e.to_string()))?;
+    store.put_raw(cf, REKEY_KEY, &bytes)
+}
+
+/// The in-place key codec conversion in progress, if any.
+pub fn get_rekeying<S: KvStore>(store: &S) -> Result<Option<RekeyMarker>> {
+    let cf = meta_cf(store)?;
+    let Some(bytes) = store.get_raw(cf, REKEY_KEY)? else {
+        return Ok(None);
+    };
+    let corrupt = || Error::FormatMismatch("corrupt key conversion marker".to_string());
+    let (to, column_family, phase): (u8, String, u8) =
+        bincode::deserialize(&bytes).map_err(|_| corrupt())?;
+    let phase = match phase {
+        0 => RekeyPhase::Stage,
+        1 => RekeyPhase::Swap,
+        2 => RekeyPhase::Done,
+        _ => return Err(corrupt()),
+    };
+    Ok(Some(RekeyMarker {
+        to,
+        column_family,
+        phase,
+    }))
+}
+
+/// Clear the key codec conversion marker.
+pub fn clear_rekeying<S: KvStore>(store: &S) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.delete_raw(cf, REKEY_KEY)
+}
+
 /// Mark that `index_name` is being rebuilt (so a crash mid-rebuild is detectable on reopen).
 pub fn set_rebuilding<S: KvStore>(store: &S, index_name: &str) -> Result<()> {
     let cf = meta_cf(store)?;
@@ -247,3 +696,120 @@ pub fn clear_rebuilding<S: KvStore>(store: &S) -> Result<()> {
     let cf = meta_cf(store)?;
     store.delete_raw(cf, REBUILD_KEY)
 }
+
+/// Mark that the format upgrade step from version `from` is running (so a crash mid-upgrade is
+/// detectable on reopen).
+pub fn set_format_upgrading<S: KvStore>(store: &S, from: u16) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.put_raw(cf, FORMAT_UPGRADE_KEY, &from.to_be_bytes())
+}
+
+/// The version an interrupted format upgrade step started from, if any.
+pub fn get_format_upgrading<S: KvStore>(store: &S) -> Result<Option<u16>> {
+    let cf = meta_cf(store)?;
+    match store.get_raw(cf, FORMAT_UPGRADE_KEY)? {
+        Some(bytes) => {
+            let bytes: [u8; 2] = bytes
+                .as_slice()
+                .try_into()
+                .map_err(|_| Error::FormatMismatch("corrupt format upgrade marker".to_string()))?;
+            Ok(Some(u16::from_be_bytes(bytes)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Clear the format upgrade marker.
+pub fn clear_format_upgrading<S: KvStore>(store: &S) -> Result<()> {
+    let cf = meta_cf(store)?;
+    store.delete_raw(cf, FORMAT_UPGRADE_KEY)
+}
+
+/// The `(key, value)` metadata record holding a replica's applied primary sequence number, for
+/// writing atomically alongside the replicated batch itself.
+pub fn applied_sequence_record(sequence: u64) -> (&'static [u8], [u8; 8]) {
+    (APPLIED_SEQUENCE_KEY, sequence.to_be_bytes())
+}
+
+/// Record the primary sequence number a replica has applied up to.
+pub fn write_applied_sequence<S: KvStore>(store: &S, sequence: u64) -> Result<()> {
+    let cf = meta_cf(store)?;
+    let (key, value) = applied_sequence_record(sequence);
+    store.put_raw(cf, key, &value)
+}
+
+/// The primary sequence number a replica has applied up to (`None` if it never synced).
+pub fn read_applied_sequence<S: KvStore>(store: &S) -> Result<Option<u64>> {
+    let cf = meta_cf(store)?;
+    match store.get_raw(cf, APPLIED_SEQUENCE_KEY)? {
+        Some(bytes) => {
+            let bytes: [u8; 8] = bytes.as_slice().try_into().map_err(|_| {
+                Error::FormatMismatch("corrupt replica sequence record".to_string())
+            })?;
+            Ok(Some(u64::from_be_bytes(bytes)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Move the row `key -> value` of column family `cf_name` into the metadata column family, out
+/// of reach of normal reads. Idempotent: quarantining the same row again overwrites the copy.
+pub fn quarantine_row<S: KvStore>(
+    store: &S,
+    cf_name: &str,
+    key: &[u8],
+    value: &[u8],
+    also_delete: &[(&str, &[u8])],
+) -> Result<()> {
+    let meta = meta_cf(store)?;
+    let cf_of = |name: &str| {
+        store
+            .cf(name)
+            .ok_or_else(|| Error::ColumnFamilyNotFound(name.to_string()))
+    };
+    let mut record_key = QUARANTINE_PREFIX.to_vec();
+    record_key.extend_from_slice(cf_name.as_bytes());
+    record_key.push(0);
+    record_key.extend_from_slice(key);
+    let mut writes = vec![
+        RawWrite::Put(meta, &record_key, value),
+        RawWrite::Delete(cf_of(cf_name)?, key),
+    ];
+    for (name, key) in also_delete {
+        writes.push(RawWrite::Delete(cf_of(name)?, key));
+    }
+    store.write_raw(&writes)
+}
+
+/// A row moved aside by [`VerifyMode::Quarantine`](crate::VerifyMode::Quarantine).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuarantinedRow {
+    /// The column family the row was taken from.
+    pub column_family: String,
+    /// Its raw key bytes.
+    pub key: Vec<u8>,
+    /// Its raw value bytes.
+    pub value: Vec<u8>,
+}
+
+/// Every quarantined row.
+pub fn quarantined_rows<S: KvStore>(store: &S) -> Result<Vec<QuarantinedRow>> {
+    let meta = meta_cf(store)?;
+    let mut rows = Vec::new();
+    for item in store.scan_raw(meta, QUARANTINE_PREFIX) {
+        let (record_key, value) = item?;
+        let Some(rest) = record_key.strip_prefix(QUARANTINE_PREFIX) else {
+            break;
+        };
+        let split = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| Error::FormatMismatch("corrupt quarantine record".to_string()))?;
+        rows.push(QuarantinedRow {
+            column_family: String::from_utf8_lossy(&rest[..split]).into_owned(),
+            key: rest[split + 1..].to_vec(),
+            value: value.into_vec(),
+        });
+    }
+    Ok(rows)
+}
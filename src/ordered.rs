@@ -7,21 +7,43 @@
 //! ```
 //!
 //! This lets rocksmap keep RocksDB's default bytewise comparator while iteration, ranges,
-//! and prefix scans follow the logical order of the key type.
+//! and prefix scans follow the logical order of the key type. Encodings are also
+//! **prefix-free**: no key's encoding is a proper prefix of another's. That is what lets fields
+//! be concatenated into composite keys, and what makes a descending field (every byte of its
+//! encoding inverted) sort in exactly the reverse order.
 //!
 //! Encoding summary:
 //! - unsigned integers: big-endian, fixed width;
 //! - signed integers: big-endian with the sign bit flipped (negatives sort below positives);
+//! - [`VarU64`]/[`VarI64`]: a length byte then only the significant big-endian bytes;
 //! - `bool`: `0x00`/`0x01`; `char`: big-endian `u32` scalar value;
-//! - `String`/`Vec<u8>`: raw bytes, `0x00` escaped as `0x00 0xFF`, terminated by `0x00 0x00`;
+//! - `String`/`Vec<u8>` (and `Box<str>`, `Cow<str>`, `Box<[u8]>`): raw bytes, `0x00` escaped as
+//!   `0x00 0xFF`, terminated by `0x00 0x00`;
+//! - other sequences (`Vec<T>`, `Box<[T]>`): `0x01` ++ `encode(item)` per element, then `0x00`;
+//! - arrays `[T; N]`: the elements concatenated (a fixed count needs no terminator); `()`: nothing;
+//! - `Duration`: seconds (`u64`) then nanoseconds (`u32`); `SystemTime`: signed `i128`
+//!   nanoseconds from the Unix epoch;
+//! - `Ipv4Addr`/`Ipv6Addr`: octets; `IpAddr`: `0x00` (v4) or `0x01` (v6), then the octets;
+//! - `uuid::Uuid` (`uuid` feature): its 16 bytes; `chrono` dates and times (`chrono` feature):
+//!   days from the common era, seconds from midnight, nanoseconds;
 //! - `Option<T>`: `0x00` for `None`, `0x01` ++ `encode(T)` for `Some` (so `None < Some(_)`);
-//! - tuples: each field in declaration order (fixed-width fields carry no terminator, so a
-//!   leading field is a true byte prefix of the whole key — load-bearing for prefix scans);
-//! - floats: not on bare `f32`/`f64`; use the explicit [`OrderedF32`]/[`OrderedF64`] wrappers.
+//! - tuples (up to 12 fields): each field in declaration order (fixed-width fields carry no
+//!   terminator, so a leading field is a true byte prefix of the whole key — load-bearing for
+//!   prefix scans);
+//! - floats: not on bare `f32`/`f64`; use the explicit [`OrderedF32`]/[`OrderedF64`] wrappers;
+//! - collated strings ([`CaseInsensitive`](crate::CaseInsensitive), and with the `unicode`
+//!   feature `Nfc`/`Nfkc`): the collation key then the original string, each as a `String`;
+//! - descending fields ([`Desc<T>`], or `#[ordered_key(desc)]` with the `derive` feature): the
+//!   field's encoding with every byte inverted.
 
 use crate::codec::KeyCodec;
 use crate::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::marker::PhantomData;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// A key type that can be encoded to bytes such that byte order matches its `Ord` order.
 ///
@@ -33,6 +55,54 @@ pub trait OrderedKey: Sized {
 
     /// Decode one value from the front of `input`, advancing `input` past the bytes consumed.
     fn decode_from(input: &mut &[u8]) -> Result<Self>;
+
+    /// Append the bytes that decide whether two keys are the *same* secondary-index key. The
+    /// default is the full encoding; collated keys such as [`CaseInsensitive`](crate::CaseInsensitive)
+    /// write only their collation key, so values that differ only in case share an index entry
+    /// (and collide in a unique index). Like the encoding, it must be prefix-free and ordered.
+    fn encode_collation_into(&self, out: &mut Vec<u8>) {
+        self.encode_into(out);
+    }
+
+    /// Encode a sequence of `Self` (the body of `Vec<Self>` / `Box<[Self]>`). The default writes
+    /// `0x01` before each element and `0x00` after the last, so a sequence sorts before any
+    /// longer one it is a prefix of. `u8` overrides it with the escaped byte-string encoding.
+    #[doc(hidden)]
+    fn encode_seq_into(items: &[Self], out: &mut Vec<u8>) {
+        for item in items {
+            out.push(0x01);
+            item.encode_into(out);
+        }
+        out.push(0x00);
+    }
+
+    /// The collation counterpart of [`OrderedKey::encode_seq_into`]: the same framing around
+    /// each element's [`encode_collation_into`](OrderedKey::encode_collation_into).
+    #[doc(hidden)]
+    fn encode_collation_seq_into(items: &[Self], out: &mut Vec<u8>) {
+        for item in items {
+            out.push(0x01);
+            item.encode_collation_into(out);
+        }
+        out.push(0x00);
+    }
+
+    /// Decode a sequence written by [`OrderedKey::encode_seq_into`].
+    #[doc(hidden)]
+    fn decode_seq_from(input: &mut &[u8]) -> Result<Vec<Self>> {
+        let mut items = Vec::new();
+        loop {
+            match read_byte(input)? {
+                0x00 => return Ok(items),
+                0x01 => items.push(Self::decode_from(input)?),
+                other => {
+                    return Err(Error::Deserialization(format!(
+                        "invalid sequence marker 0x{other:02X} in ordered key"
+                    )))
+                }
+            }
+        }
+    }
 }
 
 fn unexpected_end() -> Error {
@@ -75,13 +145,13 @@ pub(crate) fn encode_bytes_no_terminator(bytes: &[u8]) -> Vec<u8> {
 /// Encode raw bytes with `0x00` escaping and a `0x00 0x00` terminator. The terminator
 /// (`0x00 0x00`) sorts below an escaped zero (`0x00 0xFF`) and below any non-zero byte, so a
 /// shorter string sorts before a longer one sharing its prefix.
-fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+pub(crate) fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
     out.extend_from_slice(&encode_bytes_no_terminator(bytes));
     out.push(0x00);
     out.push(0x00);
 }
 
-fn decode_bytes(input: &mut &[u8]) -> Result<Vec<u8>> {
+pub(crate) fn decode_bytes(input: &mut &[u8]) -> Result<Vec<u8>> {
     let mut out = Vec::new();
     loop {
         let b = read_byte(input)?;
@@ -101,6 +171,52 @@ fn decode_bytes(input: &mut &[u8]) -> Result<Vec<u8>> {
     }
 }
 
+/// Append the encoding of `value` with every byte inverted, so that it sorts in descending
+/// order. Correct because encodings are prefix-free: two encodings differ at some byte, and
+/// inverting that byte flips their comparison.
+pub fn encode_desc<T: OrderedKey>(value: &T, out: &mut Vec<u8>) {
+    let start = out.len();
+    value.encode_into(out);
+    for byte in &mut out[start..] {
+        *byte = !*byte;
+    }
+}
+
+/// Append the collation encoding of `value` (see [`OrderedKey::encode_collation_into`]) with
+/// every byte inverted, for a descending field.
+pub fn encode_collation_desc<T: OrderedKey>(value: &T, out: &mut Vec<u8>) {
+    let start = out.len();
+    value.encode_collation_into(out);
+    for byte in &mut out[start..] {
+        *byte = !*byte;
+    }
+}
+
+/// Bytes first un-inverted by [`decode_desc`].
+const DESC_WINDOW: usize = 16;
+
+/// Decode a value written by [`encode_desc`] from the front of `input`, advancing it.
+pub fn decode_desc<T: OrderedKey>(input: &mut &[u8]) -> Result<T> {
+    // The length of an encoding is only known once it is decoded, so un-invert a window of the
+    // input, doubling it until the value decodes within it. Encodings are prefix-free and
+    // decoded front to back, so a value decoded within the window is the one the whole input
+    // holds; the work stays proportional to the field rather than to the rest of the key.
+    let mut window = DESC_WINDOW.min(input.len());
+    let mut restored = Vec::with_capacity(window);
+    loop {
+        restored.extend(input[restored.len()..window].iter().map(|byte| !byte));
+        let mut rest = restored.as_slice();
+        match T::decode_from(&mut rest) {
+            Ok(value) => {
+                *input = &input[restored.len() - rest.len()..];
+                return Ok(value);
+            }
+            Err(e) if window == input.len() => return Err(e),
+            Err(_) => window = window.saturating_mul(2).min(input.len()),
+        }
+    }
+}
+
 macro_rules! impl_unsigned {
     ($($t:ty),+) => {$(
         impl OrderedKey for $t {
@@ -113,7 +229,25 @@ macro_rules! impl_unsigned {
         }
     )+};
 }
-impl_unsigned!(u8, u16, u32, u64, u128);
+impl_unsigned!(u16, u32, u64, u128);
+
+impl OrderedKey for u8 {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        read_byte(input)
+    }
+    fn encode_seq_into(items: &[Self], out: &mut Vec<u8>) {
+        encode_bytes(items, out);
+    }
+    fn encode_collation_seq_into(items: &[Self], out: &mut Vec<u8>) {
+        encode_bytes(items, out);
+    }
+    fn decode_seq_from(input: &mut &[u8]) -> Result<Vec<Self>> {
+        decode_bytes(input)
+    }
+}
 
 macro_rules! impl_signed {
     ($($t:ty => $u:ty),+) => {$(
@@ -194,12 +328,163 @@ impl OrderedKey for String {
     }
 }
 
-impl OrderedKey for Vec<u8> {
+impl OrderedKey for Box<str> {
     fn encode_into(&self, out: &mut Vec<u8>) {
-        encode_bytes(self, out);
+        encode_bytes(self.as_bytes(), out);
     }
     fn decode_from(input: &mut &[u8]) -> Result<Self> {
-        decode_bytes(input)
+        String::decode_from(input).map(String::into_boxed_str)
+    }
+}
+
+impl OrderedKey for Cow<'_, str> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        encode_bytes(self.as_bytes(), out);
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        String::decode_from(input).map(Cow::Owned)
+    }
+}
+
+impl<T: OrderedKey> OrderedKey for Vec<T> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        T::encode_seq_into(self, out);
+    }
+    fn encode_collation_into(&self, out: &mut Vec<u8>) {
+        T::encode_collation_seq_into(self, out);
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        T::decode_seq_from(input)
+    }
+}
+
+impl<T: OrderedKey> OrderedKey for Box<[T]> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        T::encode_seq_into(self, out);
+    }
+    fn encode_collation_into(&self, out: &mut Vec<u8>) {
+        T::encode_collation_seq_into(self, out);
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        T::decode_seq_from(input).map(Vec::into_boxed_slice)
+    }
+}
+
+// Arrays have a fixed length, so their elements are simply concatenated (`[u8; N]` is its raw
+// bytes), like the fields of a tuple.
+impl<T: OrderedKey, const N: usize> OrderedKey for [T; N] {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        for item in self {
+            item.encode_into(out);
+        }
+    }
+    fn encode_collation_into(&self, out: &mut Vec<u8>) {
+        for item in self {
+            item.encode_collation_into(out);
+        }
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        let items = (0..N)
+            .map(|_| T::decode_from(input))
+            .collect::<Result<Vec<T>>>()?;
+        items
+            .try_into()
+            .map_err(|_| Error::Deserialization("array length mismatch in ordered key".to_string()))
+    }
+}
+
+impl OrderedKey for () {
+    fn encode_into(&self, _out: &mut Vec<u8>) {}
+    fn decode_from(_input: &mut &[u8]) -> Result<Self> {
+        Ok(())
+    }
+}
+
+impl OrderedKey for Duration {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.as_secs().encode_into(out);
+        self.subsec_nanos().encode_into(out);
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        let secs = u64::decode_from(input)?;
+        let nanos = u32::decode_from(input)?;
+        if nanos >= 1_000_000_000 {
+            return Err(Error::Deserialization(format!(
+                "invalid Duration nanoseconds {nanos} in ordered key"
+            )));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+/// Signed nanoseconds relative to the Unix epoch, as an `i128` (which holds any `Duration` in
+/// nanoseconds, so every representable `SystemTime` fits).
+impl OrderedKey for SystemTime {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        let nanos = match self.duration_since(UNIX_EPOCH) {
+            Ok(after) => after.as_nanos() as i128,
+            Err(before) => -(before.duration().as_nanos() as i128),
+        };
+        nanos.encode_into(out);
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        let nanos = i128::decode_from(input)?;
+        let offset = Duration::new(
+            (nanos.unsigned_abs() / 1_000_000_000) as u64,
+            (nanos.unsigned_abs() % 1_000_000_000) as u32,
+        );
+        let time = if nanos >= 0 {
+            UNIX_EPOCH.checked_add(offset)
+        } else {
+            UNIX_EPOCH.checked_sub(offset)
+        };
+        time.ok_or_else(|| {
+            Error::Deserialization("SystemTime out of range on this platform".to_string())
+        })
+    }
+}
+
+impl OrderedKey for Ipv4Addr {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.octets());
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        Ok(Ipv4Addr::from(read_array::<4>(input)?))
+    }
+}
+
+impl OrderedKey for Ipv6Addr {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.octets());
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        Ok(Ipv6Addr::from(read_array::<16>(input)?))
+    }
+}
+
+/// `0x00` ++ octets for IPv4, `0x01` ++ octets for IPv6, so every IPv4 address sorts first (as
+/// in `IpAddr`'s `Ord`).
+impl OrderedKey for IpAddr {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        match self {
+            IpAddr::V4(addr) => {
+                out.push(0x00);
+                addr.encode_into(out);
+            }
+            IpAddr::V6(addr) => {
+                out.push(0x01);
+                addr.encode_into(out);
+            }
+        }
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        match read_byte(input)? {
+            0x00 => Ok(IpAddr::V4(Ipv4Addr::decode_from(input)?)),
+            0x01 => Ok(IpAddr::V6(Ipv6Addr::decode_from(input)?)),
+            other => Err(Error::Deserialization(format!(
+                "invalid IpAddr tag 0x{other:02X} in ordered key"
+            ))),
+        }
     }
 }
 
@@ -213,6 +498,15 @@ impl<T: OrderedKey> OrderedKey for Option<T> {
             }
         }
     }
+    fn encode_collation_into(&self, out: &mut Vec<u8>) {
+        match self {
+            None => out.push(0x00),
+            Some(v) => {
+                out.push(0x01);
+                v.encode_collation_into(out);
+            }
+        }
+    }
     fn decode_from(input: &mut &[u8]) -> Result<Self> {
         match read_byte(input)? {
             0x00 => Ok(None),
@@ -236,6 +530,11 @@ macro_rules! impl_tuple {
             fn decode_from(input: &mut &[u8]) -> Result<Self> {
                 Ok(($($name::decode_from(input)?,)+))
             }
+            #[allow(non_snake_case)]
+            fn encode_collation_into(&self, out: &mut Vec<u8>) {
+                let ($($name,)+) = self;
+                $($name.encode_collation_into(out);)+
+            }
         }
     };
 }
@@ -245,6 +544,77 @@ impl_tuple!(A, B, C);
 impl_tuple!(A, B, C, D);
 impl_tuple!(A, B, C, D, E);
 impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+impl_tuple!(A, B, C, D, E, F, G, H, I);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+#[cfg(feature = "uuid")]
+impl OrderedKey for uuid::Uuid {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        Ok(uuid::Uuid::from_bytes(read_array::<16>(input)?))
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_keys {
+    use super::{OrderedKey, Result};
+    use crate::error::Error;
+    use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+
+    fn invalid(what: &str) -> Error {
+        Error::Deserialization(format!("invalid {what} in ordered key"))
+    }
+
+    /// Days from the proleptic Gregorian 0001-01-01 (day 1).
+    impl OrderedKey for NaiveDate {
+        fn encode_into(&self, out: &mut Vec<u8>) {
+            self.num_days_from_ce().encode_into(out);
+        }
+        fn decode_from(input: &mut &[u8]) -> Result<Self> {
+            NaiveDate::from_num_days_from_ce_opt(i32::decode_from(input)?)
+                .ok_or_else(|| invalid("NaiveDate"))
+        }
+    }
+
+    /// Seconds from midnight, then nanoseconds (which exceed 10^9 during a leap second).
+    impl OrderedKey for NaiveTime {
+        fn encode_into(&self, out: &mut Vec<u8>) {
+            self.num_seconds_from_midnight().encode_into(out);
+            self.nanosecond().encode_into(out);
+        }
+        fn decode_from(input: &mut &[u8]) -> Result<Self> {
+            let secs = u32::decode_from(input)?;
+            let nanos = u32::decode_from(input)?;
+            NaiveTime::from_num_seconds_from_midnight_opt(secs, nanos)
+                .ok_or_else(|| invalid("NaiveTime"))
+        }
+    }
+
+    impl OrderedKey for NaiveDateTime {
+        fn encode_into(&self, out: &mut Vec<u8>) {
+            self.date().encode_into(out);
+            self.time().encode_into(out);
+        }
+        fn decode_from(input: &mut &[u8]) -> Result<Self> {
+            Ok(NaiveDate::decode_from(input)?.and_time(NaiveTime::decode_from(input)?))
+        }
+    }
+
+    impl OrderedKey for DateTime<Utc> {
+        fn encode_into(&self, out: &mut Vec<u8>) {
+            self.naive_utc().encode_into(out);
+        }
+        fn decode_from(input: &mut &[u8]) -> Result<Self> {
+            Ok(NaiveDateTime::decode_from(input)?.and_utc())
+        }
+    }
+}
 
 /// Order-preserving wrapper for `f64` keys.
 ///
@@ -300,6 +670,173 @@ impl OrderedKey for OrderedF32 {
     }
 }
 
+/// Descending-order wrapper: `Desc(a) < Desc(b)` exactly when `a > b`, in both `Ord` and the
+/// encoded bytes.
+///
+/// Use it as a field of a composite key to mix directions, e.g. `(user_id, Desc(timestamp))` so
+/// that a forward prefix scan over one user visits the newest entries first. The encoding is
+/// that of `T` with every byte inverted ([`encode_desc`]); because `T`'s encoding is prefix-free
+/// (strings and byte vectors included, thanks to their escaped terminator), the inverted one is
+/// too, and fields after a `Desc` still compare correctly.
+///
+/// Prefix scans match on the *inverted* bytes, so a `Desc<String>` field does not support
+/// [`scan_prefix`](crate::RocksMap::scan_prefix) by string prefix; whole leading fields
+/// (`scan_prefix_fields`) work as usual.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Desc<T>(pub T);
+
+impl<T: Ord> Ord for Desc<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.cmp(&self.0)
+    }
+}
+
+impl<T: Ord> PartialOrd for Desc<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: OrderedKey> OrderedKey for Desc<T> {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        encode_desc(&self.0, out);
+    }
+    fn encode_collation_into(&self, out: &mut Vec<u8>) {
+        encode_collation_desc(&self.0, out);
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        decode_desc(input).map(Desc)
+    }
+}
+
+/// Number of big-endian bytes needed to hold `value` (0 for 0).
+fn significant_bytes(value: u64) -> usize {
+    8 - value.leading_zeros() as usize / 8
+}
+
+/// Read `len` big-endian bytes (each inverted first if `inverted`) as a `u64`, rejecting a
+/// leading zero byte: a varint has one canonical encoding, or equal values would compare unequal.
+fn read_var_payload(input: &mut &[u8], len: usize, inverted: bool) -> Result<u64> {
+    if input.len() < len {
+        return Err(unexpected_end());
+    }
+    let (payload, rest) = input.split_at(len);
+    let byte = |b: &u8| if inverted { !*b } else { *b };
+    if payload.first().map(byte) == Some(0) {
+        return Err(Error::Deserialization(
+            "non-canonical varint in ordered key".to_string(),
+        ));
+    }
+    *input = rest;
+    Ok(payload
+        .iter()
+        .fold(0, |acc, b| (acc << 8) | u64::from(byte(b))))
+}
+
+/// Variable-length, order-preserving `u64`: a length byte (0..=8) followed by that many
+/// big-endian bytes without leading zeros. A value below 2^8 costs 2 bytes, below 2^16 costs 3,
+/// and so on, up to 9 for the largest values. Longer encodings hold larger values, so the
+/// length byte orders values of different sizes and the payload orders values of the same size.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct VarU64(pub u64);
+
+impl OrderedKey for VarU64 {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        let len = significant_bytes(self.0);
+        out.push(len as u8);
+        out.extend_from_slice(&self.0.to_be_bytes()[8 - len..]);
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        let len = read_byte(input)? as usize;
+        if len > 8 {
+            return Err(Error::Deserialization(format!(
+                "invalid varint length {len} in ordered key"
+            )));
+        }
+        read_var_payload(input, len, false).map(VarU64)
+    }
+}
+
+impl From<u64> for VarU64 {
+    fn from(value: u64) -> Self {
+        VarU64(value)
+    }
+}
+
+impl From<VarU64> for u64 {
+    fn from(value: VarU64) -> Self {
+        value.0
+    }
+}
+
+/// Variable-length, order-preserving `i64`, the signed counterpart of [`VarU64`]. A
+/// non-negative value is `0x80 + len` then its bytes; a negative value `v` is `0x7F - len` then
+/// the inverted bytes of `!v` (its distance below `-1`), so larger magnitudes take more bytes
+/// and sort lower. Values in `-256..256` cost 2 bytes (`-1` and `0` just 1).
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct VarI64(pub i64);
+
+impl OrderedKey for VarI64 {
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        if self.0 >= 0 {
+            let magnitude = self.0 as u64;
+            let len = significant_bytes(magnitude);
+            out.push(0x80 + len as u8);
+            out.extend_from_slice(&magnitude.to_be_bytes()[8 - len..]);
+        } else {
+            let magnitude = !self.0 as u64;
+            let len = significant_bytes(magnitude);
+            out.push(0x7F - len as u8);
+            out.extend_from_slice(&(!magnitude).to_be_bytes()[8 - len..]);
+        }
+    }
+    fn decode_from(input: &mut &[u8]) -> Result<Self> {
+        let header = read_byte(input)?;
+        let invalid = || {
+            Error::Deserialization(format!(
+                "invalid varint header 0x{header:02X} in ordered key"
+            ))
+        };
+        let (len, negative) = if header >= 0x80 {
+            ((header - 0x80) as usize, false)
+        } else {
+            ((0x7F - header) as usize, true)
+        };
+        if len > 8 {
+            return Err(invalid());
+        }
+        let magnitude = read_var_payload(input, len, negative)?;
+        let magnitude = i64::try_from(magnitude).map_err(|_| invalid())?;
+        Ok(VarI64(if negative { !magnitude } else { magnitude }))
+    }
+}
+
+impl From<i64> for VarI64 {
+    fn from(value: i64) -> Self {
+        VarI64(value)
+    }
+}
+
+impl From<VarI64> for i64 {
+    fn from(value: VarI64) -> Self {
+        value.0
+    }
+}
+
+/// The secondary-index form of `key` (see [`OrderedKey::encode_collation_into`]).
+pub(crate) fn encode_collation<K: OrderedKey>(key: &K) -> Vec<u8> {
+    let mut out = Vec::new();
+    key.encode_collation_into(&mut out);
+    out
+}
+
 /// Order-preserving codec, the default key codec for [`crate::RocksMap`].
 ///
 /// Implements [`KeyCodec`] for any [`OrderedKey`], and is marked [`OrderedKeyCodec`] so that
@@ -465,6 +1002,172 @@ mod tests {
         roundtrip((1u64, "x".to_string(), -3i32));
     }
 
+    #[test]
+    fn desc_reverses_a_field_and_keeps_later_fields_ordered() {
+        assert!(enc(&Desc(5u64)) < enc(&Desc(3u64)));
+        assert!(enc(&(1u32, Desc(5u64))) < enc(&(1u32, Desc(3u64))));
+        assert!(enc(&(1u32, Desc(3u64))) < enc(&(2u32, Desc(9u64))));
+        // A longer string sorts first, and the terminator still separates the next field.
+        let s = |v: &str| Desc(v.to_string());
+        assert!(enc(&s("aa")) < enc(&s("a")));
+        assert!(enc(&(s("a\u{0}"), 0u8)) < enc(&(s("a"), 255u8)));
+        assert!(enc(&(s("a"), 0u8)) < enc(&(s("a"), 1u8)));
+        roundtrip((s("x\u{0}y"), Desc(-4i32), Desc(Some(vec![0u8, 255]))));
+        assert!(Desc(1) > Desc(2));
+
+        // Fields longer than the first window `decode_desc` un-inverts, one after another.
+        let long = (
+            s(&"ab\u{0}".repeat(30)),
+            Desc(vec![7u8; 40]),
+            s(""),
+            Desc(9u64),
+            3u8,
+        );
+        roundtrip(long.clone());
+        let bytes = enc(&long);
+        let decode = |bytes: &[u8]| {
+            <OrderedCodec<(Desc<String>, Desc<Vec<u8>>, Desc<String>, Desc<u64>, u8)> as KeyCodec<
+                _,
+            >>::decode(bytes)
+        };
+        assert!(decode(&bytes[..bytes.len() - 2]).is_err());
+        assert!(decode(&bytes[..40]).is_err());
+    }
+
+    #[test]
+    fn std_types_roundtrip() {
+        roundtrip([7u8, 0, 255]);
+        roundtrip([(1u16, "a".to_string()), (0, String::new())]);
+        roundtrip(vec![3u32, 0, u32::MAX]);
+        roundtrip(vec!["a".to_string(), String::new()]);
+        roundtrip(Vec::<i64>::new());
+        roundtrip(vec![0u8, 1].into_boxed_slice());
+        roundtrip(vec![Some(1u8), None].into_boxed_slice());
+        roundtrip(Box::<str>::from("box\u{0}"));
+        roundtrip(Cow::Borrowed("cow"));
+        roundtrip(());
+        roundtrip(Duration::new(5, 999_999_999));
+        roundtrip(UNIX_EPOCH - Duration::new(3, 1));
+        roundtrip(UNIX_EPOCH + Duration::new(1_700_000_000, 5));
+        roundtrip(Ipv4Addr::new(10, 0, 0, 1));
+        roundtrip(IpAddr::V6(Ipv6Addr::LOCALHOST));
+        roundtrip((
+            1u8, 2u8, 3u8, 4u8, 5u8, 6u8, 7u8, 8u8, 9u8, 10u8, 11u8, 12u8,
+        ));
+    }
+
+    #[test]
+    fn byte_sequences_keep_their_escaped_encoding() {
+        // `Vec<u8>` goes through the generic `Vec<T>` impl but must stay byte-compatible with
+        // keys written before it existed.
+        assert_eq!(enc(&vec![b'a', 0]), enc(&"a\u{0}".to_string()));
+        assert_eq!(enc(&vec![b'a', 0]), vec![b'a', 0x00, 0xFF, 0x00, 0x00]);
+        assert_eq!(enc(&vec![1u8].into_boxed_slice()), enc(&vec![1u8]));
+        assert_eq!(enc(&vec![1u16, 2]), vec![0x01, 0, 1, 0x01, 0, 2, 0x00]);
+    }
+
+    #[test]
+    fn time_and_address_order() {
+        assert!(enc(&(UNIX_EPOCH - Duration::from_secs(1))) < enc(&UNIX_EPOCH));
+        assert!(enc(&UNIX_EPOCH) < enc(&(UNIX_EPOCH + Duration::from_nanos(1))));
+        assert!(enc(&Duration::new(1, 999_999_999)) < enc(&Duration::new(2, 0)));
+        assert!(enc(&Ipv4Addr::new(9, 255, 255, 255)) < enc(&Ipv4Addr::new(10, 0, 0, 0)));
+        assert!(
+            enc(&IpAddr::from([255, 255, 255, 255])) < enc(&IpAddr::from(Ipv6Addr::UNSPECIFIED))
+        );
+    }
+
+    #[test]
+    fn varints_are_compact_and_canonical() {
+        assert_eq!(enc(&VarU64(0)), vec![0x00]);
+        assert_eq!(enc(&VarU64(255)), vec![0x01, 0xFF]);
+        assert_eq!(enc(&VarU64(256)), vec![0x02, 0x01, 0x00]);
+        assert_eq!(enc(&VarU64(u64::MAX)).len(), 9);
+        assert_eq!(enc(&VarI64(0)), vec![0x80]);
+        assert_eq!(enc(&VarI64(-1)), vec![0x7F]);
+        assert_eq!(enc(&VarI64(-2)), vec![0x7E, 0xFE]);
+        roundtrip(VarI64(i64::MIN));
+        roundtrip(VarI64(i64::MAX));
+        roundtrip(VarU64(u64::MAX));
+        let decode = |bytes: &[u8]| <OrderedCodec<VarU64> as KeyCodec<VarU64>>::decode(bytes);
+        assert!(decode(&[0x02, 0x00, 0x05]).is_err(), "leading zero byte");
+        assert!(
+            decode(&[0x09, 1, 1, 1, 1, 1, 1, 1, 1, 1]).is_err(),
+            "too long"
+        );
+        assert!(decode(&[0x02, 0x01]).is_err(), "truncated");
+        let decode = |bytes: &[u8]| <OrderedCodec<VarI64> as KeyCodec<VarI64>>::decode(bytes);
+        assert!(
+            decode(&[0x88, 0x80, 0, 0, 0, 0, 0, 0, 0]).is_err(),
+            "above i64::MAX"
+        );
+        assert!(decode(&[0x7E, 0xFF]).is_err(), "non-canonical negative");
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_keys() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let map = crate::RocksMap::<uuid::Uuid, u32>::open(dir.path()).unwrap();
+        let low = uuid::Uuid::from_u128(1);
+        let high = uuid::Uuid::from_u128(1 << 100);
+        map.put(high, &2).unwrap();
+        map.put(low, &1).unwrap();
+        assert_eq!(map.get(&high).unwrap(), Some(2));
+        let keys: Vec<uuid::Uuid> = map.iter().unwrap().map(|item| item.unwrap().0).collect();
+        assert_eq!(keys, [low, high]);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_keys() {
+        use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let dates = crate::RocksMap::<NaiveDate, u32>::open(dir.path().join("dates")).unwrap();
+        for (i, day) in [
+            date(2024, 3, 1),
+            date(-1, 12, 31),
+            date(2024, 2, 29),
+            date(1, 1, 1),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            dates.put(day, &(i as u32)).unwrap();
+        }
+        let keys: Vec<NaiveDate> = dates.iter().unwrap().map(|item| item.unwrap().0).collect();
+        assert_eq!(
+            keys,
+            [
+                date(-1, 12, 31),
+                date(1, 1, 1),
+                date(2024, 2, 29),
+                date(2024, 3, 1)
+            ]
+        );
+
+        let earlier = date(2024, 1, 1)
+            .and_hms_nano_opt(23, 59, 59, 999_999_999)
+            .unwrap();
+        let later = date(2024, 1, 2).and_hms_opt(0, 0, 0).unwrap();
+        let stamps =
+            crate::RocksMap::<NaiveDateTime, u32>::open(dir.path().join("stamps")).unwrap();
+        stamps.put(later, &2).unwrap();
+        stamps.put(earlier, &1).unwrap();
+        let keys: Vec<NaiveDateTime> = stamps.iter().unwrap().map(|item| item.unwrap().0).collect();
+        assert_eq!(keys, [earlier, later]);
+
+        let instants =
+            crate::RocksMap::<DateTime<Utc>, u32>::open(dir.path().join("instants")).unwrap();
+        instants.put(later.and_utc(), &2).unwrap();
+        instants.put(earlier.and_utc(), &1).unwrap();
+        assert_eq!(instants.get(&later.and_utc()).unwrap(), Some(2));
+        let first = instants.iter().unwrap().next().unwrap().unwrap();
+        assert_eq!(first, (earlier.and_utc(), 1));
+    }
+
     // --- Property tests: round-trip and order preservation ---
 
     proptest! {
@@ -503,6 +1206,92 @@ mod tests {
             prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
         }
 
+        #[test]
+        fn prop_desc_u64_order(a: u64, b: u64) {
+            prop_assert_eq!(Desc(a).cmp(&Desc(b)), enc(&Desc(a)).cmp(&enc(&Desc(b))));
+        }
+
+        #[test]
+        fn prop_desc_string_order(a: String, b: String) {
+            let (a, b) = (Desc(a), Desc(b));
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_desc_bytes_order(a: Vec<u8>, b: Vec<u8>) {
+            let (a, b) = (Desc(a), Desc(b));
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_mixed_direction_tuple_order(a: (u8, String, i16), b: (u8, String, i16)) {
+            let a = (a.0, Desc(a.1), a.2);
+            let b = (b.0, Desc(b.1), b.2);
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_encodings_are_prefix_free(a: Vec<u8>, b: Vec<u8>) {
+            prop_assume!(a != b);
+            prop_assert!(!enc(&a).starts_with(&enc(&b)));
+            prop_assert!(!enc(&Desc(a.clone())).starts_with(&enc(&Desc(b.clone()))));
+            let (a, b) = (Some(a), Some(b));
+            prop_assert!(!enc(&a).starts_with(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_desc_roundtrip(a: (String, Option<i64>), b: u32) {
+            let key = (Desc(a), b);
+            prop_assert_eq!(
+                <OrderedCodec<(Desc<(String, Option<i64>)>, u32)> as KeyCodec<_>>::decode(&enc(&key)).unwrap(),
+                key
+            );
+        }
+
+        #[test]
+        fn prop_sequence_order(a: Vec<i16>, b: Vec<i16>) {
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_nested_sequence_order(a: Vec<Vec<u8>>, b: Vec<Vec<u8>>) {
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_array_order(a: [u16; 3], b: [u16; 3]) {
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_time_order(a: (u64, u32), b: (u64, u32), before: bool) {
+            let a = Duration::new(a.0 >> 2, a.1 % 1_000_000_000);
+            let b = Duration::new(b.0 >> 2, b.1 % 1_000_000_000);
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+            let (a, b) = if before {
+                (UNIX_EPOCH - a, UNIX_EPOCH - b)
+            } else {
+                (UNIX_EPOCH + a, UNIX_EPOCH + b)
+            };
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_ip_order(a: IpAddr, b: IpAddr) {
+            prop_assert_eq!(a.cmp(&b), enc(&a).cmp(&enc(&b)));
+        }
+
+        #[test]
+        fn prop_varint_order(a: u64, b: u64, c: i64, d: i64, shift: u32) {
+            // Shift so that every encoded length is exercised, not just full-width values.
+            let (a, b) = (a >> (shift % 64), b >> (shift / 2 % 64));
+            prop_assert_eq!(a.cmp(&b), enc(&VarU64(a)).cmp(&enc(&VarU64(b))));
+            let (c, d) = (c >> (shift % 64), d >> (shift / 2 % 64));
+            prop_assert_eq!(c.cmp(&d), enc(&VarI64(c)).cmp(&enc(&VarI64(d))));
+            prop_assert_eq!(<OrderedCodec<VarI64> as KeyCodec<VarI64>>::decode(&enc(&VarI64(c))).unwrap(), VarI64(c));
+            prop_assert_eq!(<OrderedCodec<VarU64> as KeyCodec<VarU64>>::decode(&enc(&VarU64(a))).unwrap(), VarU64(a));
+        }
+
         #[test]
         fn prop_u64_roundtrip(a: u64) {
             prop_assert_eq!(<OrderedCodec<u64> as KeyCodec<u64>>::decode(&enc(&a)).unwrap(), a);
@@ -0,0 +1,326 @@
+//! Reclaiming the disk space held by expired [`TtlRocksMap`] entries.
+//!
+//! Expired rows stay on disk until a compaction passes the TTL compaction filter over them, and
+//! RocksDB only compacts a key range once enough is written to it, so a cold range can hold
+//! expired data indefinitely. Two remedies, usable together:
+//!
+//! - [`periodic_compaction`](crate::TtlRocksMapBuilder::periodic_compaction) has RocksDB
+//!   recompact every file at least once per interval;
+//! - [`TtlRocksMap::compact_expired`] compacts just the key ranges of the SST files holding the
+//!   most expired rows (found through the expiry index) and tombstones, and can be run on a
+//!   schedule by the sweeper ([`SweepOptions::reclaim_every`](crate::SweepOptions::reclaim_every)).
+//!
+//! [`TtlRocksMap::ttl_stats`] reports how much expired data is waiting to be reclaimed.
+
+use crate::cache::CacheDelta;
+use crate::error::{Error, Result};
+use crate::ordered::OrderedKey;
+use crate::ttl::{decode_envelope, is_expired, split_expiry_index_key, TtlRocksMap};
+use rocksdb::{IteratorMode, ReadOptions, WriteBatch};
+use serde::{de::DeserializeOwned, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+/// Expired index entries examined per [`TtlRocksMap::compact_expired`] call.
+const EXPIRED_SAMPLE: usize = 100_000;
+
+/// What a [`TtlRocksMap`] holds on disk, by expiry state, from [`TtlRocksMap::ttl_stats`]. Sizes
+/// are the logical bytes of the stored keys and values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TtlStats {
+    /// Entries with a deadline still in the future.
+    pub live_entries: u64,
+    /// Total size of the live entries.
+    pub live_bytes: u64,
+    /// Entries past their deadline that no compaction or sweep has removed yet.
+    pub expired_entries: u64,
+    /// Total size of the expired entries: roughly what a compaction would reclaim.
+    pub expired_bytes: u64,
+    /// Entries that never expire.
+    pub no_expiry_entries: u64,
+    /// Total size of the entries that never expire.
+    pub no_expiry_bytes: u64,
+    /// Size of the table's SST files on disk, as reported by RocksDB.
+    pub sst_bytes: u64,
+}
+
+impl<K, V> TtlRocksMap<K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Serialize + DeserializeOwned + Clone,
+{
+    /// Count the entries of this table by expiry state. **O(n)** — scans every stored row,
+    /// without decoding keys or values. Rows that are not valid envelopes are not counted.
+    pub fn ttl_stats(&self) -> Result<TtlStats> {
+        let now = self.now();
+        let mut stats = TtlStats::default();
+        for item in self.db().iterator_cf(self.data_cf(), IteratorMode::Start) {
+            let (key, value) = item.map_err(Error::from)?;
+            let Ok((expire_at, _)) = decode_envelope(&value) else {
+                continue;
+            };
+            let size = (key.len() + value.len()) as u64;
+            let (entries, bytes) = match expire_at {
+                None => (&mut stats.no_expiry_entries, &mut stats.no_expiry_bytes),
+                Some(_) if is_expired(expire_at, now) => {
+                    (&mut stats.expired_entries, &mut stats.expired_bytes)
+                }
+                Some(_) => (&mut stats.live_entries, &mut stats.live_bytes),
+            };
+            *entries += 1;
+            *bytes += size;
+        }
+        stats.sst_bytes = self
+            .db()
+            .property_int_value_cf(self.data_cf(), "rocksdb.total-sst-files-size")
+            .map_err(Error::from)?
+            .unwrap_or(0);
+        Ok(stats)
+    }
+
+    /// Compact the key ranges of up to `max_ranges` SST files of this table, those covering the
+    /// most expired rows plus tombstones first, so the compaction filter drops the expired rows
+    /// and the deletes are reclaimed. The expired rows are taken from the expiry index (up to
+    /// 100 000 per call); the index entries of those the compaction dropped are removed, the
+    /// others are left for the sweep. Returns the number of ranges compacted; files holding
+    /// nothing to reclaim are left alone.
+    ///
+    /// Rows dropped here are not handed to the expiry listeners; a sweeper scheduling it with
+    /// [`SweepOptions::reclaim_every`](crate::SweepOptions::reclaim_every) sweeps every due
+    /// entry first, so they are.
+    pub fn compact_expired(&self, max_ranges: usize) -> Result<usize> {
+        self.reclaim_expired(max_ranges, false)
+    }
+
+    /// [`compact_expired`](Self::compact_expired), but with every entry due removed and handed
+    /// to the expiry listeners once the ranges are chosen and before they are compacted.
+    pub(crate) fn sweep_and_compact_expired(&self, max_ranges: usize) -> Result<usize> {
+        self.reclaim_expired(max_ranges, true)
+    }
+
+    fn reclaim_expired(&self, max_ranges: usize, sweep_first: bool) -> Result<usize> {
+        let db = self.db();
+        let now = self.now();
+        let mut readopts = ReadOptions::default();
+        let upper = now.saturating_add(1).to_be_bytes();
+        readopts.set_iterate_upper_bound(upper);
+        let mut expired = Vec::new();
+        for item in db
+            .iterator_cf_opt(self.expiry_index(), readopts, IteratorMode::Start)
+            .take(EXPIRED_SAMPLE)
+        {
+            let (index_key, _) = item.map_err(Error::from)?;
+            expired.push((split_expiry_index_key(&index_key)?.1.to_vec(), index_key));
+        }
+        expired.sort();
+
+        let mut files: Vec<_> = db
+            .live_files()
+            .map_err(Error::from)?
+            .into_iter()
+            .filter(|file| file.column_family_name == self.table_name())
+            .map(|file| {
+                let from = match &file.start_key {
+                    Some(start) => expired.partition_point(|(key, _)| key < start),
+                    None => 0,
+                };
+                let to = match &file.end_key {
+                    Some(end) => expired.partition_point(|(key, _)| key <= end),
+                    None => expired.len(),
+                };
+                let to = to.max(from);
+                let score = (to - from) as u64 + file.num_deletions;
+                (score, from..to, file.start_key, file.end_key)
+            })
+            .filter(|(score, ..)| *score > 0)
+            .collect();
+        files.sort_by_key(|(score, ..)| Reverse(*score));
+        files.truncate(max_ranges);
+        if files.is_empty() {
+            return Ok(0);
+        }
+
+        // The ranges are chosen while the expired rows are still there to find them; the deletes
+        // of the sweep are compacted away with them.
+        if sweep_first {
+            self.sweep_expired()?;
+        }
+        for (_, _, start, end) in &files {
+            db.compact_range_cf(self.data_cf(), start.as_deref(), end.as_deref());
+        }
+        // Only the sampled rows in the compacted ranges can have been dropped; a row rewritten
+        // since has a different index entry, so a row still missing under the lock is gone.
+        let _guard = self.lock();
+        let mut batch = WriteBatch::default();
+        let mut delta = CacheDelta::default();
+        let mut dropped = HashSet::new();
+        for (_, sampled, _, _) in &files {
+            for (key, index_key) in &expired[sampled.clone()] {
+                if db
+                    .get_pinned_cf(self.data_cf(), key)
+                    .map_err(Error::from)?
+                    .is_none()
+                {
+                    batch.delete_cf(self.expiry_index(), index_key);
+                    if dropped.insert(key) {
+                        delta.add(self.stage_cache(&mut batch, key, None)?);
+                    }
+                }
+            }
+        }
+        db.write(batch).map_err(Error::from)?;
+        self.commit_cache_delta(delta);
+        Ok(files.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn ttl_map(dir: &TempDir, clock: &ManualClock) -> TtlRocksMap<String, String> {
+        TtlRocksMap::open_with_clock(dir.path(), Arc::new(clock.clone())).unwrap()
+    }
+
+    #[test]
+    fn ttl_stats_counts_entries_by_expiry_state() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = ttl_map(&dir, &clock);
+        let value = "v".to_string();
+        for i in 0..4 {
+            map.put_with_ttl(format!("short{i}"), &value, Duration::from_millis(10))
+                .unwrap();
+        }
+        for i in 0..3 {
+            map.put_with_ttl(format!("long{i}"), &value, Duration::from_secs(60))
+                .unwrap();
+        }
+        for i in 0..2 {
+            map.put(format!("keep{i}"), &value).unwrap();
+        }
+        clock.set(10);
+
+        let stats = map.ttl_stats().unwrap();
+        assert_eq!(
+            (
+                stats.live_entries,
+                stats.expired_entries,
+                stats.no_expiry_entries
+            ),
+            (3, 4, 2)
+        );
+        assert!(stats.expired_bytes > 0 && stats.live_bytes > 0 && stats.no_expiry_bytes > 0);
+        assert_eq!(stats.sst_bytes, 0); // nothing flushed yet
+
+        map.db().flush().unwrap();
+        map.compact();
+        let stats = map.ttl_stats().unwrap();
+        assert_eq!((stats.expired_entries, stats.expired_bytes), (0, 0));
+        assert_eq!(stats.live_entries, 3);
+        assert!(stats.sst_bytes > 0);
+    }
+
+    #[test]
+    fn compact_expired_targets_the_files_holding_expired_rows() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = ttl_map(&dir, &clock);
+        let value = "v".to_string();
+        for i in 0..50 {
+            map.put_with_ttl(format!("a{i:02}"), &value, Duration::from_millis(10))
+                .unwrap();
+        }
+        map.db().flush().unwrap();
+        for i in 0..50 {
+            map.put(format!("m{i:02}"), &value).unwrap();
+        }
+        map.db().flush().unwrap();
+        let live_file = |map: &TtlRocksMap<String, String>| {
+            map.db()
+                .live_files()
+                .unwrap()
+                .into_iter()
+                .filter(|file| file.column_family_name == "default")
+                .find(|file| file.start_key.as_deref().unwrap_or_default() > b"m".as_slice())
+                .map(|file| file.name)
+        };
+        let untouched = live_file(&map).unwrap();
+
+        assert_eq!(map.compact_expired(4).unwrap(), 0); // nothing has expired yet
+        clock.set(10);
+        assert_eq!(map.compact_expired(4).unwrap(), 1);
+        let stats = map.ttl_stats().unwrap();
+        assert_eq!((stats.expired_entries, stats.no_expiry_entries), (0, 50));
+        assert_eq!(live_file(&map), Some(untouched));
+        assert_eq!(map.compact_expired(4).unwrap(), 0);
+    }
+
+    #[test]
+    fn rows_outside_the_compacted_ranges_are_still_swept() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let (sender, events) = std::sync::mpsc::channel();
+        let map = TtlRocksMap::<String, String>::builder(dir.path())
+            .clock(Arc::new(clock.clone()))
+            .notify_expired(sender)
+            .open()
+            .unwrap();
+        let value = "v".to_string();
+        for prefix in ["a", "b", "c"] {
+            for i in 0..20 {
+                map.put_with_ttl(format!("{prefix}{i:02}"), &value, Duration::from_millis(10))
+                    .unwrap();
+            }
+            map.db().flush().unwrap();
+        }
+        clock.set(10);
+
+        assert_eq!(map.compact_expired(1).unwrap(), 1);
+        assert_eq!(map.ttl_stats().unwrap().expired_entries, 40);
+        let index = map.expiry_index();
+        assert_eq!(map.db().iterator_cf(index, IteratorMode::Start).count(), 40);
+        assert_eq!(
+            map.compact_expired(1).unwrap(),
+            1,
+            "the next file still scores"
+        );
+
+        assert_eq!(map.sweep_expired().unwrap(), 20);
+        assert_eq!(events.try_iter().count(), 20);
+        assert_eq!(map.db().iterator(IteratorMode::Start).count(), 0);
+        assert_eq!(map.db().iterator_cf(index, IteratorMode::Start).count(), 0);
+    }
+
+    #[test]
+    fn periodic_compaction_is_set_on_every_ttl_table() {
+        let dir = TempDir::new().unwrap();
+        let map = TtlRocksMap::<String, String>::builder(dir.path())
+            .table("sessions", None)
+            .periodic_compaction(Duration::from_secs(3600))
+            .open()
+            .unwrap();
+        drop(map);
+
+        let mut options_files: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with("OPTIONS-")
+            })
+            .collect();
+        options_files.sort();
+        let latest = std::fs::read_to_string(options_files.last().unwrap()).unwrap();
+        assert_eq!(
+            latest.matches("periodic_compaction_seconds=3600").count(),
+            2
+        );
+    }
+}
@@ -0,0 +1,795 @@
+//! Log-shipping replication from a primary database to read-only followers.
+//!
+//! A [`ReplicationServer`] runs next to the primary and serves the write-ahead log over TCP: a
+//! follower asks for every write batch after the last sequence number it applied. A
+//! [`ReplicaFollower`] applies each shipped batch atomically, together with the new applied
+//! sequence (recorded in `__rocksmap_meta`), so after a crash or restart it resumes exactly
+//! where it stopped.
+//!
+//! If the primary no longer retains the WAL a follower needs (it was flushed and purged), the
+//! follower falls back to a **full resync**: the primary takes a RocksDB checkpoint and streams
+//! its files, and the follower swaps them in place of its own directory. The swap is marked on
+//! disk, so a crash mid-resync is completed or rolled back on the next open.
+//!
+//! Replication is raw and kind-agnostic: every column family is shipped byte-for-byte,
+//! including the primary's metadata, so plain and TTL databases replicate the same way (TTL
+//! envelopes arrive intact; the follower's reads apply expiry). Indexed maps are not supported,
+//! as their `TransactionDB` does not expose the WAL. To keep followers on the incremental path,
+//! open the primary with WAL retention (`Options::set_wal_ttl_seconds` or
+//! `set_wal_size_limit_mb`).
+//!
+//! Protocol: length-prefixed (`u32` big-endian) bincode frames. The follower sends `Hello`,
+//! then any number of `Pull { since }` / `Snapshot` requests on one connection.
+
+use crate::cdc::cf_names_by_id;
+use crate::codec::KeyCodec;
+use crate::error::{Error, Result};
+use crate::meta::{self, MapKind};
+use crate::rocks_map::{RocksMap, RocksMapRef};
+use crate::schema;
+use crate::ttl::TtlRocksMap;
+use crate::wal::{decode_batch, WalOp, WalTail};
+use rocksdb::{checkpoint::Checkpoint, ColumnFamilyDescriptor, Options, WriteBatch, DB};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const PROTOCOL_VERSION: u32 = 1;
+/// Largest frame either side accepts (guards against a garbage length prefix).
+const MAX_FRAME_LEN: usize = 1 << 30;
+/// Batches shipped per `Pull` round trip.
+const PULL_MAX_BATCHES: u32 = 1024;
+/// Marker written into a fully downloaded resync snapshot, before it is swapped in.
+const RESYNC_COMPLETE: &str = "ROCKSMAP_RESYNC_COMPLETE";
+
+#[derive(Serialize, Deserialize)]
+enum Request {
+    Hello { version: u32 },
+    Pull { since: u64, max_batches: u32 },
+    Snapshot,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Response {
+    Hello { version: u32 },
+    Batches(Vec<ShippedBatch>),
+    ResyncRequired,
+    SnapshotBegin { sequence: u64, files: u32 },
+    SnapshotFile { name: String, data: Vec<u8> },
+    Error(String),
+}
+
+/// One WAL batch as shipped to a follower: applied as a unit, after which the follower has
+/// everything up to `last_sequence`.
+#[derive(Serialize, Deserialize)]
+struct ShippedBatch {
+    last_sequence: u64,
+    ops: Vec<ShippedOp>,
+}
+
+/// A put (`value: Some`) or delete (`value: None`) on a named column family.
+#[derive(Serialize, Deserialize)]
+struct ShippedOp {
+    cf: String,
+    key: Vec<u8>,
+    value: Option<Vec<u8>>,
+}
+
+fn net_err(e: std::io::Error) -> Error {
+    Error::Replication(e.to_string())
+}
+
+fn write_frame<T: Serialize>(stream: &mut TcpStream, msg: &T) -> Result<()> {
+    let body = bincode::serialize(msg).map_err(|e| Error::Serialization(e.to_string()))?;
+    let len = u32::try_from(body.len())
+        .ok()
+        .filter(|&n| n as usize <= MAX_FRAME_LEN)
+        .ok_or_else(|| Error::Replication(format!("frame of {} bytes is too large", body.len())))?;
+    stream.write_all(&len.to_be_bytes()).map_err(net_err)?;
+    stream.write_all(&body).map_err(net_err)?;
+    stream.flush().map_err(net_err)
+}
+
+fn read_frame<T: DeserializeOwned>(stream: &mut TcpStream) -> Result<T> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).map_err(net_err)?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(Error::Replication(format!(
+            "frame of {len} bytes is too large"
+        )));
+    }
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).map_err(net_err)?;
+    bincode::deserialize(&body).map_err(|e| Error::Deserialization(e.to_string()))
+}
+
+// --- Primary side ---
+
+/// A database that can act as a replication primary.
+///
+/// Implemented for [`RocksMap`] and [`TtlRocksMap`]; replication ships the raw WAL, so the
+/// typed parameters do not matter to it.
+pub trait ReplicationSource: Send + Sync + 'static {
+    /// The database whose write-ahead log is served.
+    fn source_db(&self) -> &DB;
+}
+
+impl<K, V, KC> ReplicationSource for RocksMap<K, V, KC>
+where
+    K: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    V: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+    KC: KeyCodec<K> + Send + Sync + 'static,
+{
+    fn source_db(&self) -> &DB {
+        self.db()
+    }
+}
+
+impl<K, V> ReplicationSource for TtlRocksMap<K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + crate::OrderedKey + Send + Sync + 'static,
+    V: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    fn source_db(&self) -> &DB {
+        self.db()
+    }
+}
+
+/// A follower connection (a handle to shut its socket down) and the thread serving it.
+type Connection = (TcpStream, JoinHandle<()>);
+
+/// Serves a primary's write-ahead log to followers over TCP.
+///
+/// Each connection is handled on its own thread. Dropping the server stops accepting, closes
+/// every follower connection, and joins the threads.
+pub struct ReplicationServer {
+    local_addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    connections: Arc<Mutex<Vec<Connection>>>,
+    acceptor: Option<JoinHandle<()>>,
+}
+
+impl ReplicationServer {
+    /// Bind `addr` (e.g. `"127.0.0.1:0"` for an ephemeral port) and start serving `source`.
+    pub fn start<S, A>(source: Arc<S>, addr: A) -> Result<Self>
+    where
+        S: ReplicationSource,
+        A: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(addr).map_err(net_err)?;
+        let local_addr = listener.local_addr().map_err(net_err)?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let connections: Arc<Mutex<Vec<Connection>>> = Arc::default();
+
+        let acceptor = {
+            let shutdown = shutdown.clone();
+            let connections = connections.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    let Ok(stream) = stream else { continue };
+                    let Ok(handle_stream) = stream.try_clone() else {
+                        continue;
+                    };
+                    let source = source.clone();
+                    let worker =
+                        std::thread::spawn(move || serve_connection(source.source_db(), stream));
+                    let mut connections = connections.lock().expect("connection list poisoned");
+                    connections.retain(|(_, worker)| !worker.is_finished());
+                    connections.push((handle_stream, worker));
+                }
+            })
+        };
+
+        Ok(ReplicationServer {
+            local_addr,
+            shutdown,
+            connections,
+            acceptor: Some(acceptor),
+        })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+}
+
+impl Drop for ReplicationServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake the blocking `accept` so the acceptor sees the flag.
+        let _ = TcpStream::connect(self.local_addr);
+        if let Some(acceptor) = self.acceptor.take() {
+            let _ = acceptor.join();
+        }
+        let connections =
+            std::mem::take(&mut *self.connections.lock().expect("connection list poisoned"));
+        for (stream, worker) in connections {
+            let _ = stream.shutdown(Shutdown::Both);
+            let _ = worker.join();
+        }
+    }
+}
+
+fn serve_connection(db: &DB, mut stream: TcpStream) {
+    // Any I/O error (including the follower disconnecting) ends the connection.
+    while let Ok(request) = read_frame::<Request>(&mut stream) {
+        let sent = match request {
+            Request::Hello { .. } => write_frame(
+                &mut stream,
+                &Response::Hello {
+                    version: PROTOCOL_VERSION,
+                },
+            ),
+            Request::Pull { since, max_batches } => {
+                let response = match collect_batches(db, since, max_batches as usize) {
+                    Ok(batches) => Response::Batches(batches),
+                    Err(Error::ChangesUnavailable(_)) => Response::ResyncRequired,
+                    Err(e) => Response::Error(e.to_string()),
+                };
+                write_frame(&mut stream, &response)
+            }
+            Request::Snapshot => match send_snapshot(db, &mut stream) {
+                Ok(()) => Ok(()),
+                Err(e) => write_frame(&mut stream, &Response::Error(e.to_string())),
+            },
+        };
+        if sent.is_err() {
+            return;
+        }
+    }
+}
+
+/// Up to `max` WAL batches containing the writes after `since`, across every column family.
+fn collect_batches(db: &DB, since: u64, max: usize) -> Result<Vec<ShippedBatch>> {
+    let mut cf_names = cf_names_by_id(db)?;
+    let mut out = Vec::new();
+    for item in WalTail::open(db, since)? {
+        let (batch_seq, batch) = item?;
+        let records = decode_batch(batch.data())?;
+        let Some(last_sequence) = (batch_seq + records.len() as u64).checked_sub(1) else {
+            continue;
+        };
+        if records.is_empty() || last_sequence <= since {
+            continue;
+        }
+
+        let mut ops = Vec::with_capacity(records.len());
+        for (i, record) in records.into_iter().enumerate() {
+            if batch_seq + i as u64 <= since {
+                continue; // already applied by the follower
+            }
+            if !cf_names.contains_key(&record.cf_id) {
+                cf_names = cf_names_by_id(db)?;
+            }
+            let cf = cf_names.get(&record.cf_id).cloned().ok_or_else(|| {
+                Error::ColumnFamilyNotFound(format!("column family id {}", record.cf_id))
+            })?;
+            let (key, value) = match record.op {
+                WalOp::Put { key, value } => (key.to_vec(), Some(value.to_vec())),
+                WalOp::Delete { key } => (key.to_vec(), None),
+                WalOp::Unsupported { tag } => {
+                    return Err(Error::Replication(format!(
+                        "cannot replicate write-batch record 0x{tag:02X}"
+                    )))
+                }
+            };
+            ops.push(ShippedOp { cf, key, value });
+        }
+        out.push(ShippedBatch { last_sequence, ops });
+        if out.len() >= max {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+/// A sibling of `path` named `<path>.<suffix>`.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+fn open_all_cfs(path: &Path, create: bool) -> Result<DB> {
+    let mut opts = Options::default();
+    opts.create_if_missing(create);
+    opts.create_missing_column_families(true);
+    let descriptors: Vec<ColumnFamilyDescriptor> = meta::all_cf_names(&opts, path, &[])
+        .iter()
+        .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+        .collect();
+    DB::open_cf_descriptors(&opts, path, descriptors).map_err(Error::from)
+}
+
+/// Take a checkpoint of `db` and stream its files, announcing the sequence number it holds.
+fn send_snapshot(db: &DB, stream: &mut TcpStream) -> Result<()> {
+    static SNAPSHOT_COUNTER: AtomicU64 = AtomicU64::new(0);
+    let dir = sibling(
+        db.path(),
+        &format!(
+            "snapshot-{}-{}",
+            std::process::id(),
+            SNAPSHOT_COUNTER.fetch_add(1, Ordering::SeqCst)
+        ),
+    );
+    Checkpoint::new(db)
+        .and_then(|c| c.create_checkpoint(&dir))
+        .map_err(Error::from)?;
+
+    let result = (|| {
+        // The checkpoint's own latest sequence is exactly what it contains.
+        let sequence = {
+            let opts = Options::default();
+            let descriptors: Vec<ColumnFamilyDescriptor> = meta::existing_cfs(&opts, &dir)
+                .iter()
+                .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+                .collect();
+            DB::open_cf_descriptors_read_only(&opts, &dir, descriptors, false)
+                .map_err(Error::from)?
+                .latest_sequence_number()
+        };
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&dir).map_err(net_err)? {
+            let entry = entry.map_err(net_err)?;
+            if entry.file_type().map_err(net_err)?.is_file() {
+                files.push(entry.path());
+            }
+        }
+        write_frame(
+            stream,
+            &Response::SnapshotBegin {
+                sequence,
+                files: files.len() as u32,
+            },
+        )?;
+        for file in files {
+            let name = file
+                .file_name()
+                .and_then(|n| n.to_str())
+                .ok_or_else(|| Error::Replication("non UTF-8 snapshot file name".to_string()))?
+                .to_string();
+            let data = std::fs::read(&file).map_err(net_err)?;
+            write_frame(stream, &Response::SnapshotFile { name, data })?;
+        }
+        Ok(())
+    })();
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+// --- Follower side ---
+
+/// What one [`ReplicaFollower::sync_once`] call did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyncReport {
+    /// WAL batches applied.
+    pub batches_applied: usize,
+    /// Whether the follower had to fall back to a full resync from a checkpoint.
+    pub resynced: bool,
+    /// The primary sequence number the follower has now applied up to.
+    pub applied_sequence: u64,
+}
+
+/// A local replica kept in sync with a [`ReplicationServer`].
+///
+/// The follower owns its database; read it through [`view`](Self::view) (plain maps) or
+/// [`db`](Self::db). Writing to a follower directly diverges it from the primary.
+pub struct ReplicaFollower {
+    path: PathBuf,
+    primary: String,
+    db: Option<DB>,
+    conn: Option<TcpStream>,
+}
+
+impl ReplicaFollower {
+    /// Open (or create) the replica at `path`, following the primary at `primary`
+    /// (`host:port`). Completes or rolls back a resync interrupted by a crash. No network
+    /// traffic happens until the first [`sync_once`](Self::sync_once).
+    pub fn open<P: AsRef<Path>>(path: P, primary: &str) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        recover_interrupted_resync(&path)?;
+        if !path.exists() {
+            std::fs::create_dir_all(&path).map_err(|_| Error::InvalidPath(path.clone()))?;
+        }
+        let db = open_all_cfs(&path, true)?;
+        Ok(ReplicaFollower {
+            path,
+            primary: primary.to_string(),
+            db: Some(db),
+            conn: None,
+        })
+    }
+
+    /// The replica's database handle.
+    pub fn db(&self) -> &DB {
+        self.db.as_ref().expect("replica database is open")
+    }
+
+    /// A typed read view of the replicated data, for a plain [`RocksMap`] primary. Fails with
+    /// [`Error::FormatMismatch`] if the replicated metadata records another kind, key codec, or
+    /// key/value types.
+    pub fn view<K, V, KC>(&self) -> Result<RocksMapRef<'_, K, V, KC>>
+    where
+        K: Serialize + DeserializeOwned + Clone,
+        V: Serialize + DeserializeOwned + Clone,
+        KC: KeyCodec<K>,
+    {
+        let db = self.db();
+        match meta::read_kind(db)? {
+            Some(MapKind::Plain) => {}
+            Some(other) => {
+                return Err(Error::FormatMismatch(format!(
+                    "replica holds a {other} map, not a plain map"
+                )))
+            }
+            None => {
+                return Err(Error::FormatMismatch(
+                    "replica has not received the primary's metadata yet".to_string(),
+                ))
+            }
+        }
+        if let Some(id) = meta::read_key_codec(db)? {
+            if id != KC::ID {
+                return Err(Error::FormatMismatch(format!(
+                    "replica uses key codec id {id} but was viewed with id {}",
+                    KC::ID
+                )));
+            }
+        }
+        schema::check_types::<K, V, _>(db)?;
+        Ok(RocksMapRef::new(db, None))
+    }
+
+    /// The last primary sequence number applied to this replica (`0` if none).
+    pub fn applied_sequence(&self) -> Result<u64> {
+        Ok(meta::read_applied_sequence(self.db())?.unwrap_or(0))
+    }
+
+    /// Pull and apply everything the primary has written since the last applied sequence,
+    /// resyncing from a checkpoint if the primary no longer has the WAL for it.
+    pub fn sync_once(&mut self) -> Result<SyncReport> {
+        let result = self.sync_inner();
+        if result.is_err() {
+            self.conn = None; // reconnect on the next call
+        }
+        result
+    }
+
+    /// Call [`sync_once`](Self::sync_once) every `poll_interval` until `stop` is set.
+    pub fn run(&mut self, poll_interval: Duration, stop: &AtomicBool) -> Result<()> {
+        while !stop.load(Ordering::SeqCst) {
+            self.sync_once()?;
+            std::thread::sleep(poll_interval);
+        }
+        Ok(())
+    }
+
+    fn sync_inner(&mut self) -> Result<SyncReport> {
+        let mut report = SyncReport {
+            batches_applied: 0,
+            resynced: false,
+            applied_sequence: self.applied_sequence()?,
+        };
+        loop {
+            let request = Request::Pull {
+                since: report.applied_sequence,
+                max_batches: PULL_MAX_BATCHES,
+            };
+            match self.request(&request)? {
+                Response::Batches(batches) if batches.is_empty() => return Ok(report),
+                Response::Batches(batches) => {
+                    for batch in batches {
+                        report.applied_sequence = self.apply(batch)?;
+                        report.batches_applied += 1;
+                    }
+                }
+                Response::ResyncRequired if report.resynced => {
+                    return Err(Error::Replication(
+                        "primary still cannot serve the WAL after a full resync".to_string(),
+                    ))
+                }
+                Response::ResyncRequired => {
+                    report.applied_sequence = self.resync()?;
+                    report.resynced = true;
+                }
+                Response::Error(msg) => return Err(Error::Replication(msg)),
+                _ => return Err(unexpected_response()),
+            }
+        }
+    }
+
+    fn connection(&mut self) -> Result<&mut TcpStream> {
+        if self.conn.is_none() {
+            let mut stream = TcpStream::connect(&self.primary).map_err(net_err)?;
+            write_frame(
+                &mut stream,
+                &Request::Hello {
+                    version: PROTOCOL_VERSION,
+                },
+            )?;
+            match read_frame(&mut stream)? {
+                Response::Hello { version } if version == PROTOCOL_VERSION => {}
+                Response::Hello { version } => {
+                    return Err(Error::Replication(format!(
+                        "primary speaks protocol version {version}, expected {PROTOCOL_VERSION}"
+                    )))
+                }
+                _ => return Err(unexpected_response()),
+            }
+            self.conn = Some(stream);
+        }
+        Ok(self.conn.as_mut().expect("connection just established"))
+    }
+
+    fn request(&mut self, request: &Request) -> Result<Response> {
+        let stream = self.connection()?;
+        write_frame(stream, request)?;
+        read_frame(stream)
+    }
+
+    /// Apply one shipped batch and its applied-sequence record in a single atomic write.
+    fn apply(&mut self, batch: ShippedBatch) -> Result<u64> {
+        let db = self.db.as_mut().expect("replica database is open");
+        for op in &batch.ops {
+            if db.cf_handle(&op.cf).is_none() {
+                db.create_cf(&op.cf, &Options::default())
+                    .map_err(Error::from)?;
+            }
+        }
+
+        let mut write = WriteBatch::default();
+        for op in &batch.ops {
+            let cf = db
+                .cf_handle(&op.cf)
+                .ok_or_else(|| Error::ColumnFamilyNotFound(op.cf.clone()))?;
+            match &op.value {
+                Some(value) => write.put_cf(cf, &op.key, value),
+                None => write.delete_cf(cf, &op.key),
+            }
+        }
+        let meta_cf = db
+            .cf_handle(meta::META_CF)
+            .ok_or_else(|| Error::ColumnFamilyNotFound(meta::META_CF.to_string()))?;
+        let (key, value) = meta::applied_sequence_record(batch.last_sequence);
+        write.put_cf(meta_cf, key, value);
+        db.write(write).map_err(Error::from)?;
+        Ok(batch.last_sequence)
+    }
+
+    /// Replace the replica with a fresh checkpoint of the primary. Returns its sequence number.
+    fn resync(&mut self) -> Result<u64> {
+        let staging = sibling(&self.path, "resync");
+        if staging.exists() {
+            std::fs::remove_dir_all(&staging).map_err(net_err)?;
+        }
+        std::fs::create_dir_all(&staging).map_err(net_err)?;
+
+        let stream = self.connection()?;
+        write_frame(stream, &Request::Snapshot)?;
+        let (sequence, files) = match read_frame(stream)? {
+            Response::SnapshotBegin { sequence, files } => (sequence, files),
+            Response::Error(msg) => return Err(Error::Replication(msg)),
+            _ => return Err(unexpected_response()),
+        };
+        for _ in 0..files {
+            match read_frame(stream)? {
+                Response::SnapshotFile { name, data } => {
+                    if Path::new(&name).file_name() != Some(name.as_ref()) {
+                        return Err(Error::Replication(format!(
+                            "invalid snapshot file name `{name}`"
+                        )));
+                    }
+                    let mut file = std::fs::File::create(staging.join(&name)).map_err(net_err)?;
+                    file.write_all(&data).map_err(net_err)?;
+                    file.sync_all().map_err(net_err)?;
+                }
+                Response::Error(msg) => return Err(Error::Replication(msg)),
+                _ => return Err(unexpected_response()),
+            }
+        }
+
+        // Record the applied sequence inside the snapshot before it goes live, then mark it
+        // complete: from here on a crash finishes the swap instead of discarding the download.
+        {
+            let snapshot = open_all_cfs(&staging, false)?;
+            meta::write_applied_sequence(&snapshot, sequence)?;
+            snapshot.flush_wal(true).map_err(Error::from)?;
+        }
+        std::fs::File::create(staging.join(RESYNC_COMPLETE))
+            .and_then(|f| f.sync_all())
+            .map_err(net_err)?;
+
+        self.db = None; // close before swapping directories
+        recover_interrupted_resync(&self.path)?;
+        self.db = Some(open_all_cfs(&self.path, true)?);
+        Ok(sequence)
+    }
+}
+
+fn unexpected_response() -> Error {
+    Error::Replication("unexpected response from primary".to_string())
+}
+
+/// Finish or roll back a resync swap. A staging directory carrying the completion marker
+/// replaces the replica; an unmarked one is an incomplete download and is discarded.
+fn recover_interrupted_resync(path: &Path) -> Result<()> {
+    let staging = sibling(path, "resync");
+    let old = sibling(path, "resync-old");
+
+    if staging.join(RESYNC_COMPLETE).exists() {
+        if path.exists() {
+            if old.exists() {
+                std::fs::remove_dir_all(&old).map_err(net_err)?;
+            }
+            std::fs::rename(path, &old).map_err(net_err)?;
+        }
+        std::fs::rename(&staging, path).map_err(net_err)?;
+    } else if staging.exists() {
+        std::fs::remove_dir_all(&staging).map_err(net_err)?;
+    }
+
+    if !path.exists() && old.exists() {
+        std::fs::rename(&old, path).map_err(net_err)?;
+    }
+    let marker = path.join(RESYNC_COMPLETE);
+    if marker.exists() {
+        std::fs::remove_file(marker).map_err(net_err)?;
+    }
+    if old.exists() {
+        std::fs::remove_dir_all(&old).map_err(net_err)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocksdb::IteratorMode;
+    use tempfile::TempDir;
+
+    fn primary(dir: &Path) -> (Arc<RocksMap<u64, String>>, ReplicationServer) {
+        let mut opts = Options::default();
+        opts.set_wal_ttl_seconds(3600);
+        let map = Arc::new(RocksMap::<u64, String>::open_with_cfs(dir, opts, &["side"]).unwrap());
+        let server = ReplicationServer::start(map.clone(), "127.0.0.1:0").unwrap();
+        (map, server)
+    }
+
+    fn values(follower: &ReplicaFollower) -> Vec<(u64, String)> {
+        follower
+            .view::<u64, String, crate::OrderedCodec<u64>>()
+            .unwrap()
+            .iter()
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn follower_applies_writes_and_column_families() {
+        let dir = TempDir::new().unwrap();
+        let (map, server) = primary(&dir.path().join("primary"));
+        map.put(1, &"a".to_string()).unwrap();
+        map.put(2, &"b".to_string()).unwrap();
+        map.delete(&1).unwrap();
+        let mut batch = map.batch();
+        batch.put(&3, &"c".to_string()).unwrap();
+        batch.put(&4, &"d".to_string()).unwrap();
+        batch.commit().unwrap();
+        map.with_cf("side").put(9, &"side".to_string()).unwrap();
+
+        let mut follower =
+            ReplicaFollower::open(dir.path().join("replica"), &server.local_addr().to_string())
+                .unwrap();
+        let report = follower.sync_once().unwrap();
+        assert!(!report.resynced);
+        assert_eq!(report.applied_sequence, map.latest_sequence_number());
+        assert_eq!(
+            values(&follower),
+            vec![
+                (2, "b".to_string()),
+                (3, "c".to_string()),
+                (4, "d".to_string())
+            ]
+        );
+
+        let side = follower
+            .db()
+            .cf_handle("side")
+            .expect("column family replicated");
+        assert_eq!(
+            follower.db().iterator_cf(side, IteratorMode::Start).count(),
+            1
+        );
+
+        // Caught up: nothing more to apply.
+        assert_eq!(follower.sync_once().unwrap().batches_applied, 0);
+    }
+
+    #[test]
+    fn follower_resumes_after_restart() {
+        let dir = TempDir::new().unwrap();
+        let (map, server) = primary(&dir.path().join("primary"));
+        let addr = server.local_addr().to_string();
+        let replica = dir.path().join("replica");
+
+        map.put(1, &"a".to_string()).unwrap();
+        {
+            let mut follower = ReplicaFollower::open(&replica, &addr).unwrap();
+            follower.sync_once().unwrap();
+        }
+
+        map.put(2, &"b".to_string()).unwrap();
+        let mut follower = ReplicaFollower::open(&replica, &addr).unwrap();
+        let before = follower.applied_sequence().unwrap();
+        let report = follower.sync_once().unwrap();
+        assert_eq!(report.batches_applied, 1, "resumed from {before}");
+        assert_eq!(
+            values(&follower),
+            vec![(1, "a".to_string()), (2, "b".to_string())]
+        );
+    }
+
+    #[test]
+    fn purged_wal_falls_back_to_full_resync() {
+        let dir = TempDir::new().unwrap();
+        // No WAL retention: flushing lets RocksDB delete the WAL the follower would need.
+        let map = Arc::new(RocksMap::<u64, String>::open(dir.path().join("primary")).unwrap());
+        let server = ReplicationServer::start(map.clone(), "127.0.0.1:0").unwrap();
+        for k in 0..10u64 {
+            map.put(k, &format!("v{k}")).unwrap();
+        }
+        // Every column family must be flushed before the WAL they share can be purged.
+        for cf in ["default", meta::META_CF] {
+            map.db().flush_cf(map.db().cf_handle(cf).unwrap()).unwrap();
+        }
+        map.put(10, &"v10".to_string()).unwrap();
+
+        let mut follower =
+            ReplicaFollower::open(dir.path().join("replica"), &server.local_addr().to_string())
+                .unwrap();
+        let report = follower.sync_once().unwrap();
+        assert!(report.resynced);
+        assert_eq!(values(&follower).len(), 11);
+        assert_eq!(
+            follower.applied_sequence().unwrap(),
+            map.latest_sequence_number()
+        );
+
+        // Back on the incremental path afterwards.
+        map.put(11, &"v11".to_string()).unwrap();
+        let report = follower.sync_once().unwrap();
+        assert!(!report.resynced);
+        assert_eq!(values(&follower).len(), 12);
+    }
+
+    #[test]
+    fn interrupted_swap_is_completed_on_open() {
+        let dir = TempDir::new().unwrap();
+        let replica = dir.path().join("replica");
+        let staging = sibling(&replica, "resync");
+        let old = sibling(&replica, "resync-old");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join("data"), b"new").unwrap();
+        std::fs::write(staging.join(RESYNC_COMPLETE), b"").unwrap();
+        // Crash after the live directory was moved aside, before the staging one moved in.
+        std::fs::create_dir_all(&old).unwrap();
+        std::fs::write(old.join("data"), b"old").unwrap();
+
+        recover_interrupted_resync(&replica).unwrap();
+        assert_eq!(std::fs::read(replica.join("data")).unwrap(), b"new");
+        assert!(!staging.exists() && !old.exists());
+        assert!(!replica.join(RESYNC_COMPLETE).exists());
+    }
+}
@@ -1,8 +1,12 @@
 use crate::{
+    cdc::ChangeStream,
     codec::{BincodeCodec, KeyCodec, ValueCodec},
     error::{Error, Result},
+    format::{self, OpenOptions},
     meta,
     ordered::{OrderedCodec, OrderedKey, PrefixKey},
+    schema,
+    verify::{self, VerifyMode, VerifyReport},
 };
 use rocksdb::{ColumnFamily, ColumnFamilyDescriptor, IteratorMode, Options, ReadOptions, DB};
 use serde::{de::DeserializeOwned, Serialize};
@@ -52,7 +56,12 @@ where
 
     /// Opens a RocksMap with custom options
     pub fn open_with_options<P: AsRef<Path>>(path: P, options: Options) -> Result<Self> {
-        Self::open_internal(path, options, &[])
+        Self::open_internal(path, options, &[], &OpenOptions::default())
+    }
+
+    /// Opens a RocksMap with rocksmap-level [`OpenOptions`] (e.g. to allow a format upgrade).
+    pub fn open_with<P: AsRef<Path>>(path: P, open: &OpenOptions) -> Result<Self> {
+        Self::open_internal(path, Options::default(), &[], open)
     }
 
     /// Opens a RocksMap with the specified column families
@@ -61,13 +70,14 @@ where
         options: Options,
         column_families: &[&str],
     ) -> Result<Self> {
-        Self::open_internal(path, options, column_families)
+        Self::open_internal(path, options, column_families, &OpenOptions::default())
     }
 
     fn open_internal<P: AsRef<Path>>(
         path: P,
         mut options: Options,
         extra_cfs: &[&str],
+        open: &OpenOptions,
     ) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
 
@@ -94,8 +104,10 @@ where
             .collect();
 
         let db = DB::open_cf_descriptors(&options, &path, descriptors).map_err(Error::from)?;
+        format::prepare(&db, open)?;
         meta::verify_or_write_kind(&db, meta::MapKind::Plain)?;
         meta::verify_or_write_key_codec(&db, <KC as KeyCodec<K>>::ID)?;
+        schema::verify_or_write_types::<K, V, _>(&db, open)?;
 
         Ok(Self {
             db,
@@ -186,6 +198,25 @@ where
     pub fn iter(&self) -> Result<RocksMapIterator<'_, K, V, KC>> {
         make_iter::<K, V, KC>(&self.db, self.cf_name.as_deref(), None, None, false)
     }
+
+    /// Sequence number of the most recent write to the database (any column family). Pass it
+    /// to [`changes_since`](Self::changes_since) to follow the writes made after this point.
+    pub fn latest_sequence_number(&self) -> u64 {
+        self.db.latest_sequence_number()
+    }
+
+    /// Typed changes written after sequence number `since`, across every user column family,
+    /// read from the write-ahead log. See [`ChangeStream`] for checkpointing and the WAL
+    /// retention this requires.
+    pub fn changes_since(&self, since: u64) -> Result<ChangeStream<'_, K, V, KC>> {
+        ChangeStream::new(&self.db, since, false)
+    }
+
+    /// Check that every row of every column family decodes as `K -> V`, optionally
+    /// quarantining those that do not. See [`verify`](crate::verify) for what is checked.
+    pub fn verify(&self, mode: VerifyMode) -> Result<VerifyReport> {
+        verify::verify_plain::<K, V, KC>(&self.db, mode)
+    }
 }
 
 /// Ordered queries — only available when keys use the default order-preserving [`OrderedCodec`].
@@ -246,6 +277,39 @@ where
             false,
         )
     }
+
+    /// Iterate the pairs whose composite key begins with the leading fields `prefix` and whose
+    /// next field falls in `range`, in ascending key order: e.g. `range_within_prefix(&(user,),
+    /// 100..200)` for one user's timestamps `100..200` out of `(user, timestamp, ..)` keys.
+    pub fn range_within_prefix<P, N, R>(
+        &self,
+        prefix: &P,
+        range: R,
+    ) -> Result<RocksMapIterator<'_, K, V, OrderedCodec<K>>>
+    where
+        P: OrderedKey,
+        N: OrderedKey,
+        R: RangeBounds<N>,
+    {
+        let (lower, upper) = prefix_range_to_bounds(&encode_ordered(prefix)?, &range)?;
+        make_iter::<K, V, OrderedCodec<K>>(&self.db, self.cf_name.as_deref(), lower, upper, false)
+    }
+
+    /// Like [`range_within_prefix`](Self::range_within_prefix) but yields pairs in descending
+    /// key order.
+    pub fn range_within_prefix_rev<P, N, R>(
+        &self,
+        prefix: &P,
+        range: R,
+    ) -> Result<RocksMapIterator<'_, K, V, OrderedCodec<K>>>
+    where
+        P: OrderedKey,
+        N: OrderedKey,
+        R: RangeBounds<N>,
+    {
+        let (lower, upper) = prefix_range_to_bounds(&encode_ordered(prefix)?, &range)?;
+        make_iter::<K, V, OrderedCodec<K>>(&self.db, self.cf_name.as_deref(), lower, upper, true)
+    }
 }
 
 /// A reference to a RocksMap that holds a reference to the database rather than owning it.
@@ -267,6 +331,15 @@ where
     V: Serialize + DeserializeOwned + Clone,
     KC: KeyCodec<K>,
 {
+    /// A view of `cf_name` (the default column family if `None`) in `db`.
+    pub(crate) fn new(db: &'a DB, cf_name: Option<String>) -> Self {
+        RocksMapRef {
+            db,
+            cf_name,
+            marker: PhantomData,
+        }
+    }
+
     /// Returns a reference to the underlying database
     pub fn db(&self) -> &DB {
         self.db
@@ -374,12 +447,45 @@ where
             false,
         )
     }
+
+    /// Iterate the pairs whose composite key begins with the leading fields `prefix` and whose
+    /// next field falls in `range`, in ascending key order: e.g. `range_within_prefix(&(user,),
+    /// 100..200)` for one user's timestamps `100..200` out of `(user, timestamp, ..)` keys.
+    pub fn range_within_prefix<P, N, R>(
+        &self,
+        prefix: &P,
+        range: R,
+    ) -> Result<RocksMapIterator<'_, K, V, OrderedCodec<K>>>
+    where
+        P: OrderedKey,
+        N: OrderedKey,
+        R: RangeBounds<N>,
+    {
+        let (lower, upper) = prefix_range_to_bounds(&encode_ordered(prefix)?, &range)?;
+        make_iter::<K, V, OrderedCodec<K>>(self.db, self.cf_name.as_deref(), lower, upper, false)
+    }
+
+    /// Like [`range_within_prefix`](Self::range_within_prefix) but yields pairs in descending
+    /// key order.
+    pub fn range_within_prefix_rev<P, N, R>(
+        &self,
+        prefix: &P,
+        range: R,
+    ) -> Result<RocksMapIterator<'_, K, V, OrderedCodec<K>>>
+    where
+        P: OrderedKey,
+        N: OrderedKey,
+        R: RangeBounds<N>,
+    {
+        let (lower, upper) = prefix_range_to_bounds(&encode_ordered(prefix)?, &range)?;
+        make_iter::<K, V, OrderedCodec<K>>(self.db, self.cf_name.as_deref(), lower, upper, true)
+    }
 }
 
 // --- Shared implementation helpers ---
 
 /// Inclusive lower bound and exclusive upper bound byte strings for a key range.
-type ByteBounds = (Option<Vec<u8>>, Option<Vec<u8>>);
+pub(crate) type ByteBounds = (Option<Vec<u8>>, Option<Vec<u8>>);
 
 fn cf_handle<'a>(db: &'a DB, cf_name: Option<&str>) -> Result<Option<&'a ColumnFamily>> {
     match cf_name {
@@ -391,7 +497,7 @@ fn cf_handle<'a>(db: &'a DB, cf_name: Option<&str>) -> Result<Option<&'a ColumnF
     }
 }
 
-fn encode_ordered<K: OrderedKey>(key: &K) -> Result<Vec<u8>> {
+pub(crate) fn encode_ordered<K: OrderedKey>(key: &K) -> Result<Vec<u8>> {
     <OrderedCodec<K> as KeyCodec<K>>::encode(key)
 }
 
@@ -492,7 +598,7 @@ fn len_estimate_impl(db: &DB, cf_name: Option<&str>) -> usize {
 /// RocksDB `ReadOptions`. Relies on the ordered key encoding being prefix-free: appending `0x00`
 /// to a key's encoding yields a byte string strictly between it and the next possible key, so an
 /// exclusive lower / inclusive upper both map to native bounds without a stop predicate.
-fn range_to_bounds<K, R>(range: &R) -> Result<ByteBounds>
+pub(crate) fn range_to_bounds<K, R>(range: &R) -> Result<ByteBounds>
 where
     K: OrderedKey,
     R: RangeBounds<K>,
@@ -510,8 +616,42 @@ where
     Ok((lower, upper))
 }
 
+/// Byte bounds for the keys that begin with the encoded leading fields `prefix` followed by a
+/// field in `range`. Unlike a whole key, that field is usually followed by more fields, so an
+/// excluded start or included end must cover every key *extending* the bound's encoding: the
+/// bound becomes its [`byte_successor`] rather than its [`successor`].
+pub(crate) fn prefix_range_to_bounds<N, R>(prefix: &[u8], range: &R) -> Result<ByteBounds>
+where
+    N: OrderedKey,
+    R: RangeBounds<N>,
+{
+    let with_prefix = |field: &N| {
+        let mut bytes = prefix.to_vec();
+        field.encode_into(&mut bytes);
+        bytes
+    };
+    let lower = match range.start_bound() {
+        Bound::Unbounded => prefix.to_vec(),
+        Bound::Included(field) => with_prefix(field),
+        Bound::Excluded(field) => {
+            let bound = with_prefix(field);
+            match byte_successor(&bound) {
+                Some(lower) => lower,
+                // Every byte is 0xFF: no key sorts after the extensions of `bound`.
+                None => return Ok((Some(bound.clone()), Some(bound))),
+            }
+        }
+    };
+    let upper = match range.end_bound() {
+        Bound::Unbounded => byte_successor(prefix),
+        Bound::Excluded(field) => Some(with_prefix(field)),
+        Bound::Included(field) => byte_successor(&with_prefix(field)),
+    };
+    Ok(((!lower.is_empty()).then_some(lower), upper))
+}
+
 /// `(lower, upper)` byte bounds matching exactly the keys whose encoding starts with `prefix`.
-fn prefix_to_bounds(prefix: Vec<u8>) -> (Vec<u8>, Option<Vec<u8>>) {
+pub(crate) fn prefix_to_bounds(prefix: Vec<u8>) -> (Vec<u8>, Option<Vec<u8>>) {
     let upper = byte_successor(&prefix);
     (prefix, upper)
 }
@@ -768,6 +908,55 @@ mod tests {
         assert_eq!(user1, vec![(1, 10), (1, 20)]);
     }
 
+    #[test]
+    fn test_range_within_prefix() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = RocksMap::<(u64, u64, String), ()>::open(temp_dir.path()).unwrap();
+        for user in [6u64, 7, 8] {
+            for ts in [50u64, 100, 150, 200, 250] {
+                for tag in ["a", "b"] {
+                    db.put((user, ts, tag.to_string()), &()).unwrap();
+                }
+            }
+        }
+        let timestamps = |iter: RocksMapIterator<'_, (u64, u64, String), ()>| -> Vec<u64> {
+            iter.map(|r| r.unwrap().0)
+                .inspect(|key| assert_eq!(key.0, 7))
+                .map(|key| key.1)
+                .collect()
+        };
+
+        // The trailing `tag` field must not leak keys past an included end or an excluded start.
+        assert_eq!(
+            timestamps(db.range_within_prefix(&(7u64,), 100u64..200).unwrap()),
+            vec![100, 100, 150, 150]
+        );
+        assert_eq!(
+            timestamps(db.range_within_prefix(&(7u64,), 100u64..=200).unwrap()),
+            vec![100, 100, 150, 150, 200, 200]
+        );
+        assert_eq!(
+            timestamps(
+                db.range_within_prefix(&(7u64,), (Bound::Excluded(100u64), Bound::Unbounded))
+                    .unwrap()
+            ),
+            vec![150, 150, 200, 200, 250, 250]
+        );
+        assert_eq!(
+            timestamps(db.range_within_prefix_rev(&(7u64,), ..=100u64).unwrap()),
+            vec![100, 100, 50, 50]
+        );
+
+        // Two leading fields, then a range over the third; and through a column family view.
+        let tags: Vec<String> = db
+            .with_cf("default")
+            .range_within_prefix(&(7u64, 150u64), "b".to_string()..)
+            .unwrap()
+            .map(|r| r.unwrap().0 .2)
+            .collect();
+        assert_eq!(tags, vec!["b"]);
+    }
+
     #[test]
     fn test_bincode_key_codec_opt_out() {
         // A non-ordered key codec: point ops work; range/prefix are not available (compile-fail
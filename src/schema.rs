@@ -0,0 +1,770 @@
+//! Key/value type fingerprints, recorded in the metadata so a database cannot silently be
+//! opened with the wrong types.
+//!
+//! Every typed open records which `K` and `V` the database was created with and rejects a
+//! different pair with [`Error::FormatMismatch`], instead of decoding garbage (or failing
+//! row by row) later. A fingerprint is the types' shapes as serde reports them — struct, field
+//! and variant names and the primitive types underneath, e.g. `u64 -> UserV1{name:str}` — so it
+//! is stable across compiler versions and module moves, while renaming a type or changing its
+//! fields counts as a change. Types serde cannot describe (those deserialized through
+//! `deserialize_any`) are named by their visitor, e.g. `?ValueVisitor`, and a type that rejects
+//! the placeholder values the trace answers with (a zero for a `NonZeroU32`, an empty string for
+//! a `Uuid`) is traced again with others, so the fields after it are still seen. When a type
+//! change is intentional (a migration, or a refactor that kept the encoding), record the new
+//! types with [`accept_type_change`] before opening.
+
+use crate::error::{Error, Result};
+use crate::format::OpenOptions;
+use crate::meta::{self, KvStore};
+use rocksdb::{ColumnFamilyDescriptor, Options, DB};
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use std::cell::RefCell;
+use std::path::Path;
+
+/// The key and value types a database holds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeFingerprint {
+    /// The key type's shape.
+    pub key: String,
+    /// The value type's shape.
+    pub value: String,
+}
+
+impl TypeFingerprint {
+    /// The fingerprint of a map of `K -> V`.
+    pub fn of<K: DeserializeOwned, V: DeserializeOwned>() -> Self {
+        TypeFingerprint {
+            key: shape::<K>(),
+            value: shape::<V>(),
+        }
+    }
+}
+
+impl std::fmt::Display for TypeFingerprint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} -> {}", self.key, self.value)
+    }
+}
+
+/// The shape of `T` as its `Deserialize` implementation describes it to serde. When `T`
+/// rejects a placeholder the tracer hands it, the trace is run again with the next alternative
+/// for that placeholder; a type that rejects them all is described up to that point, followed
+/// by `!` and the name of the type that stopped the trace.
+pub(crate) fn shape<T: DeserializeOwned>() -> String {
+    let placeholders = RefCell::new(Placeholders::default());
+    let mut furthest = String::new();
+    loop {
+        let mut out = String::new();
+        let traced = T::deserialize(Tracer {
+            out: &mut out,
+            depth: 0,
+            placeholders: &placeholders,
+        });
+        match traced {
+            Ok(_) => return out,
+            Err(e) if !e.noted => {
+                out.push('!');
+                out.push_str(&short_type_name::<T>());
+            }
+            Err(_) => {}
+        }
+        if out.len() > furthest.len() {
+            furthest = out;
+        }
+        if !placeholders.borrow_mut().retry() {
+            return furthest;
+        }
+    }
+}
+
+/// How deeply nested a traced type may be; deeper (recursive) types are cut off there.
+const MAX_DEPTH: usize = 32;
+
+/// The placeholders handed out during a trace, in the order they were asked for, and which
+/// alternative to hand out for each on the next attempt.
+#[derive(Default)]
+struct Placeholders {
+    /// The alternative to hand out for each request; the first one past the end.
+    choices: Vec<usize>,
+    /// Requests made so far in this attempt.
+    requests: usize,
+    /// The latest request and its number of alternatives, unless the trace was cut off since.
+    last: Option<(usize, usize)>,
+}
+
+impl Placeholders {
+    /// Pick one of `alternatives` placeholders for the next request.
+    fn choose(&mut self, alternatives: usize) -> usize {
+        let request = self.requests;
+        self.requests += 1;
+        self.last = Some((request, alternatives));
+        self.choices.get(request).copied().unwrap_or(0)
+    }
+
+    /// Prepare the next attempt after a failed one, blaming the latest placeholder handed out.
+    /// Returns `false` if it has no alternative left to try.
+    fn retry(&mut self) -> bool {
+        let Some((request, alternatives)) = self.last.take() else {
+            return false;
+        };
+        if self.choices.len() <= request {
+            self.choices.resize(request + 1, 0);
+        }
+        self.choices[request] += 1;
+        self.requests = 0;
+        self.choices[request] < alternatives
+    }
+}
+
+/// `T`'s type name without module paths, e.g. `Option<NonZero<u32>>`.
+fn short_type_name<T: ?Sized>() -> String {
+    let mut out = String::new();
+    let mut segment = 0;
+    let mut chars = std::any::type_name::<T>().chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            out.truncate(segment);
+        } else {
+            out.push(c);
+            if !(c.is_alphanumeric() || c == '_') {
+                segment = out.len();
+            }
+        }
+    }
+    out
+}
+
+#[derive(Debug)]
+struct TraceError {
+    message: String,
+    /// Whether the trace already records where it stopped.
+    noted: bool,
+}
+
+impl std::fmt::Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        TraceError {
+            message: msg.to_string(),
+            noted: false,
+        }
+    }
+}
+
+/// A deserializer that writes down each type it is asked for and answers with a placeholder:
+/// zero, empty, the first enum variant, one element per sequence and map.
+struct Tracer<'a> {
+    out: &'a mut String,
+    depth: usize,
+    placeholders: &'a RefCell<Placeholders>,
+}
+
+impl Tracer<'_> {
+    fn nested(&mut self) -> std::result::Result<Tracer<'_>, TraceError> {
+        if self.depth >= MAX_DEPTH {
+            self.out.push_str("...");
+            // A cut-off is not a rejected placeholder: no alternative would get further.
+            self.placeholders.borrow_mut().last = None;
+            return Err(TraceError {
+                message: "type nests too deeply".to_string(),
+                noted: true,
+            });
+        }
+        Ok(Tracer {
+            out: self.out,
+            depth: self.depth + 1,
+            placeholders: self.placeholders,
+        })
+    }
+
+    /// Trace the next element of a compound type with `seed`, at this tracer's depth, noting
+    /// the element's type if it is where the trace stopped.
+    fn element<'de, T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<T::Value, TraceError> {
+        let traced = seed.deserialize(Tracer {
+            out: self.out,
+            depth: self.depth,
+            placeholders: self.placeholders,
+        });
+        traced.map_err(|mut e| {
+            if !e.noted {
+                self.out.push('!');
+                self.out.push_str(&short_type_name::<T::Value>());
+                e.noted = true;
+            }
+            e
+        })
+    }
+
+    fn choose(&self, alternatives: usize) -> usize {
+        self.placeholders.borrow_mut().choose(alternatives)
+    }
+
+    fn elements<'de, V: Visitor<'de>>(
+        mut self,
+        open: &str,
+        count: usize,
+        fields: &'static [&'static str],
+        close: &str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push_str(open);
+        let value = visitor.visit_seq(Elements {
+            tracer: self.nested()?,
+            index: 0,
+            count,
+            fields,
+        })?;
+        self.out.push_str(close);
+        Ok(value)
+    }
+}
+
+macro_rules! trace_primitive {
+    ($($method:ident => $name:literal, $visit:ident($placeholders:expr);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> std::result::Result<V::Value, TraceError> {
+                self.out.push_str($name);
+                let placeholders = $placeholders;
+                visitor.$visit(placeholders[self.choose(placeholders.len())])
+            }
+        )*
+    };
+}
+
+/// Placeholder strings: empty, then the zero values of common textual encodings (a UUID, RFC
+/// 3339 date-times, dates and times, a number).
+const STR_PLACEHOLDERS: [&str; 7] = [
+    "",
+    "00000000-0000-0000-0000-000000000000",
+    "1970-01-01T00:00:00Z",
+    "1970-01-01T00:00:00",
+    "1970-01-01",
+    "00:00:00",
+    "0",
+];
+
+impl<'de> de::Deserializer<'de> for Tracer<'_> {
+    type Error = TraceError;
+
+    trace_primitive! {
+        deserialize_bool => "bool", visit_bool([false, true]);
+        deserialize_i8 => "i8", visit_i8([0, 1]);
+        deserialize_i16 => "i16", visit_i16([0, 1]);
+        deserialize_i32 => "i32", visit_i32([0, 1]);
+        deserialize_i64 => "i64", visit_i64([0, 1]);
+        deserialize_i128 => "i128", visit_i128([0, 1]);
+        deserialize_u8 => "u8", visit_u8([0, 1]);
+        deserialize_u16 => "u16", visit_u16([0, 1]);
+        deserialize_u32 => "u32", visit_u32([0, 1]);
+        deserialize_u64 => "u64", visit_u64([0, 1]);
+        deserialize_u128 => "u128", visit_u128([0, 1]);
+        deserialize_f32 => "f32", visit_f32([0.0, 1.0]);
+        deserialize_f64 => "f64", visit_f64([0.0, 1.0]);
+        deserialize_char => "char", visit_char(['\0', '0']);
+        deserialize_str => "str", visit_str(STR_PLACEHOLDERS);
+        deserialize_string => "str", visit_str(STR_PLACEHOLDERS);
+        deserialize_bytes => "bytes", visit_bytes([&[][..], &[0; 16][..]]);
+        deserialize_byte_buf => "bytes", visit_bytes([&[][..], &[0; 16][..]]);
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push_str("()");
+        visitor.visit_unit()
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        visitor.visit_u32(0)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        visitor.visit_unit()
+    }
+
+    /// A type serde cannot describe is named by its visitor and handed each kind of value in
+    /// turn, until one is accepted.
+    fn deserialize_any<V: Visitor<'de>>(
+        mut self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push('?');
+        self.out.push_str(&short_type_name::<V>());
+        match self.choose(8) {
+            0 => visitor.visit_unit(),
+            1 => visitor.visit_bool(false),
+            2 => visitor.visit_u64(0),
+            3 => visitor.visit_i64(0),
+            4 => visitor.visit_f64(0.0),
+            5 => visitor.visit_str(""),
+            6 => visitor.visit_seq(Elements {
+                tracer: self.nested()?,
+                index: 0,
+                count: 0,
+                fields: &[],
+            }),
+            _ => visitor.visit_map(Entry {
+                tracer: self.nested()?,
+                done: true,
+            }),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        mut self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push_str("option<");
+        let value = visitor.visit_some(self.nested()?)?;
+        self.out.push('>');
+        Ok(value)
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push_str(name);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        mut self,
+        name: &'static str,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push_str(name);
+        self.out.push('(');
+        let value = visitor.visit_newtype_struct(self.nested()?)?;
+        self.out.push(')');
+        Ok(value)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.elements("[", 1, &[], "]", visitor)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.elements("(", len, &[], ")", visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push_str(name);
+        self.elements("(", len, &[], ")", visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(
+        mut self,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push('{');
+        let value = visitor.visit_map(Entry {
+            tracer: self.nested()?,
+            done: false,
+        })?;
+        self.out.push('}');
+        Ok(value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push_str(name);
+        self.elements("{", fields.len(), fields, "}", visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.out.push_str(name);
+        self.out.push('[');
+        self.out.push_str(&variants.join("|"));
+        self.out.push(']');
+        visitor.visit_enum(FirstVariant { tracer: self })
+    }
+}
+
+/// The elements of a sequence, tuple or struct: `count` of them, named by `fields` if any.
+struct Elements<'a> {
+    tracer: Tracer<'a>,
+    index: usize,
+    count: usize,
+    fields: &'static [&'static str],
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_> {
+    type Error = TraceError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> std::result::Result<Option<T::Value>, TraceError> {
+        if self.index == self.count {
+            return Ok(None);
+        }
+        if self.index > 0 {
+            self.tracer.out.push(',');
+        }
+        if let Some(field) = self.fields.get(self.index) {
+            self.tracer.out.push_str(field);
+            self.tracer.out.push(':');
+        }
+        self.index += 1;
+        self.tracer.element(seed).map(Some)
+    }
+}
+
+/// The single entry of a traced map.
+struct Entry<'a> {
+    tracer: Tracer<'a>,
+    done: bool,
+}
+
+impl<'de> de::MapAccess<'de> for Entry<'_> {
+    type Error = TraceError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> std::result::Result<Option<K::Value>, TraceError> {
+        if std::mem::replace(&mut self.done, true) {
+            return Ok(None);
+        }
+        let key = self.tracer.element(seed)?;
+        self.tracer.out.push(':');
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.tracer.element(seed)
+    }
+}
+
+/// An enum traced through its first variant.
+struct FirstVariant<'a> {
+    tracer: Tracer<'a>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for FirstVariant<'a> {
+    type Error = TraceError;
+    type Variant = Self;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> std::result::Result<(S::Value, Self), TraceError> {
+        let variant = seed.deserialize(0u32.into_deserializer())?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for FirstVariant<'_> {
+    type Error = TraceError;
+
+    fn unit_variant(self) -> std::result::Result<(), TraceError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        mut self,
+        seed: T,
+    ) -> std::result::Result<T::Value, TraceError> {
+        self.tracer.out.push('(');
+        let value = seed.deserialize(self.tracer.nested()?)?;
+        self.tracer.out.push(')');
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.tracer.elements("(", len, &[], ")", visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> std::result::Result<V::Value, TraceError> {
+        self.tracer
+            .elements("{", fields.len(), fields, "}", visitor)
+    }
+}
+
+/// Check the recorded types against `K -> V` without writing (`Ok` if none are recorded).
+pub(crate) fn check_types<K: DeserializeOwned, V: DeserializeOwned, S: KvStore>(
+    store: &S,
+) -> Result<()> {
+    let expected = TypeFingerprint::of::<K, V>();
+    match meta::read_types(store)? {
+        Some(recorded) if recorded != expected => Err(Error::FormatMismatch(format!(
+            "database holds `{recorded}` but was opened as `{expected}` \
+             (see `rocksmap::accept_type_change` for intentional type changes)"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Check the recorded types against `K -> V`, recording them if the database has none yet
+/// (unless `open` says to only check).
+pub(crate) fn verify_or_write_types<K: DeserializeOwned, V: DeserializeOwned, S: KvStore>(
+    store: &S,
+    open: &OpenOptions,
+) -> Result<()> {
+    match meta::read_types(store)? {
+        Some(_) => check_types::<K, V, S>(store),
+        None if open.records_types() => meta::write_types(store, &TypeFingerprint::of::<K, V>()),
+        None => Ok(()),
+    }
+}
+
+/// Record `K -> V` as the types of the (closed) database at `path`, replacing whatever was
+/// recorded. This is the explicit override for an intentional type change: the next open with
+/// the new types succeeds. It does not convert any rows.
+pub fn accept_type_change<K, V, P>(path: P) -> Result<()>
+where
+    K: DeserializeOwned,
+    V: DeserializeOwned,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let opts = Options::default();
+    let descriptors: Vec<ColumnFamilyDescriptor> = meta::existing_cfs(&opts, path)
+        .iter()
+        .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+        .collect();
+    let db = DB::open_cf_descriptors(&opts, path, descriptors).map_err(Error::from)?;
+    if db.cf_handle(meta::META_CF).is_none() {
+        return Err(Error::FormatMismatch(
+            "not a rocksmap-managed database (no metadata column family)".to_string(),
+        ));
+    }
+    meta::write_types(&db, &TypeFingerprint::of::<K, V>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IndexedRocksMap, RocksMap, TtlRocksMap};
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct UserV1 {
+        name: String,
+    }
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct OrderV2 {
+        total: u64,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    enum Event {
+        Created { at: u64 },
+        Renamed(String),
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Tree {
+        children: Vec<Tree>,
+    }
+
+    #[test]
+    fn fingerprints_are_serde_shapes() {
+        assert_eq!(
+            TypeFingerprint::of::<u64, UserV1>(),
+            TypeFingerprint {
+                key: "u64".to_string(),
+                value: "UserV1{name:str}".to_string(),
+            }
+        );
+        assert_eq!(shape::<(u32, Option<Vec<String>>)>(), "(u32,option<[str]>)");
+        assert_eq!(
+            shape::<std::collections::BTreeMap<String, Event>>(),
+            "{str:Event[Created|Renamed]{at:u64}}"
+        );
+        // A recursive type is cut off instead of traced forever.
+        assert!(shape::<Tree>().ends_with("..."));
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Ticket {
+        id: std::num::NonZeroU32,
+        owner: String,
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Invoice {
+        id: std::num::NonZeroU32,
+        total: u64,
+    }
+
+    /// A type that only deserializes through `deserialize_any`, from a string.
+    struct Label;
+
+    struct LabelVisitor;
+
+    impl<'de> Visitor<'de> for LabelVisitor {
+        type Value = Label;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.write_str("a label")
+        }
+
+        fn visit_str<E: de::Error>(self, _: &str) -> std::result::Result<Label, E> {
+            Ok(Label)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Label {
+        fn deserialize<D: de::Deserializer<'de>>(d: D) -> std::result::Result<Label, D::Error> {
+            d.deserialize_any(LabelVisitor)
+        }
+    }
+
+    #[derive(Deserialize)]
+    #[allow(dead_code)]
+    struct Tagged {
+        label: Label,
+        weight: u32,
+    }
+
+    #[test]
+    fn rejected_placeholders_are_retried_so_later_fields_are_traced() {
+        assert_eq!(shape::<Ticket>(), "Ticket{id:u32,owner:str}");
+        assert_eq!(shape::<Invoice>(), "Invoice{id:u32,total:u64}");
+        assert_eq!(shape::<Option<std::num::NonZeroU64>>(), "option<u64>");
+    }
+
+    #[test]
+    fn types_that_only_deserialize_any_are_named_by_their_visitor() {
+        assert_eq!(shape::<Tagged>(), "Tagged{label:?LabelVisitor,weight:u32}");
+        assert_ne!(shape::<Label>(), shape::<serde::de::IgnoredAny>());
+    }
+
+    #[test]
+    fn type_name_records_are_replaced() {
+        let dir = TempDir::new().unwrap();
+        {
+            let map = RocksMap::<u64, UserV1>::open(dir.path()).unwrap();
+            let cf = map.db().cf_handle(meta::META_CF).unwrap();
+            map.db().delete_cf(cf, b"type_shapes").unwrap();
+            let legacy = bincode::serialize(&("u64", "app::model::UserV1")).unwrap();
+            map.db().put_cf(cf, b"types", legacy).unwrap();
+        }
+        let map = RocksMap::<u64, UserV1>::open(dir.path()).unwrap();
+        let cf = map.db().cf_handle(meta::META_CF).unwrap();
+        assert_eq!(map.db().get_cf(cf, b"types").unwrap(), None);
+        assert_eq!(
+            meta::read_types(map.db()).unwrap(),
+            Some(TypeFingerprint::of::<u64, UserV1>())
+        );
+    }
+
+    #[test]
+    fn check_types_only_leaves_the_types_unrecorded() {
+        let dir = TempDir::new().unwrap();
+        let check_only = OpenOptions::new().check_types_only();
+        let map = RocksMap::<String, String>::open_with(dir.path(), &check_only).unwrap();
+        assert_eq!(meta::read_types(map.db()).unwrap(), None);
+        drop(map);
+
+        RocksMap::<u64, UserV1>::open(dir.path()).unwrap();
+        assert!(RocksMap::<String, String>::open_with(dir.path(), &check_only).is_err());
+    }
+
+    #[test]
+    fn opening_with_other_types_is_rejected() {
+        let dir = TempDir::new().unwrap();
+        RocksMap::<u64, UserV1>::open(dir.path()).unwrap();
+
+        let err = RocksMap::<u64, OrderV2>::open(dir.path()).err().unwrap();
+        assert!(
+            matches!(err, Error::FormatMismatch(ref m) if m.contains("OrderV2")),
+            "{err}"
+        );
+        assert!(RocksMap::<String, UserV1>::open(dir.path()).is_err());
+        assert!(RocksMap::<u64, UserV1>::open(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn accepting_a_type_change_allows_the_new_types() {
+        let dir = TempDir::new().unwrap();
+        RocksMap::<u64, UserV1>::open(dir.path()).unwrap();
+
+        accept_type_change::<u64, OrderV2, _>(dir.path()).unwrap();
+        assert!(RocksMap::<u64, OrderV2>::open(dir.path()).is_ok());
+        assert!(RocksMap::<u64, UserV1>::open(dir.path()).is_err());
+    }
+
+    #[test]
+    fn ttl_and_indexed_maps_record_their_types() {
+        let dir = TempDir::new().unwrap();
+        let ttl = dir.path().join("ttl");
+        TtlRocksMap::<u64, UserV1>::open(&ttl).unwrap();
+        assert!(TtlRocksMap::<u64, OrderV2>::open(&ttl).is_err());
+
+        let indexed = dir.path().join("indexed");
+        IndexedRocksMap::<u64, UserV1>::builder(&indexed)
+            .open()
+            .unwrap();
+        assert!(IndexedRocksMap::<u64, OrderV2>::builder(&indexed)
+            .open()
+            .is_err());
+        assert_eq!(
+            crate::inspect(&indexed).unwrap().types,
+            Some(TypeFingerprint::of::<u64, UserV1>())
+        );
+    }
+}
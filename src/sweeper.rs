@@ -0,0 +1,284 @@
+//! Background removal of expired [`TtlRocksMap`] entries.
+//!
+//! [`TtlRocksMap::spawn_sweeper`] starts a thread that takes the entries whose deadline has
+//! passed from the map's expiry index in batches, deleting them and handing each one to the
+//! listeners registered on the builder ([`on_expire`](crate::TtlRocksMapBuilder::on_expire),
+//! [`notify_expired`](crate::TtlRocksMapBuilder::notify_expired)). The scan rate is one batch of
+//! [`SweepOptions::batch_size`] entries per [`SweepOptions::interval`]; each batch resumes where
+//! the previous one stopped. Entries RocksDB's own compactions reach first are dropped by the
+//! compaction filter without being reported. With [`SweepOptions::reclaim_every`] the thread
+//! also runs [`TtlRocksMap::compact_expired`] on a schedule, so the space of expired rows is
+//! reclaimed even in ranges nothing writes to; before each such compaction it removes every
+//! entry due, so none of those is dropped unreported. Dropping the returned [`ExpirySweeper`]
+//! stops the thread and waits for it.
+//!
+//! The sweeper reads time from the map's [`Clock`](crate::Clock), so with a
+//! [`ManualClock`](crate::ManualClock) what it removes is deterministic; tests can also call
+//! [`TtlRocksMap::sweep_expired`] directly instead of running a thread.
+
+use crate::error::Result;
+use crate::ordered::OrderedKey;
+use crate::ttl::TtlRocksMap;
+use serde::{de::DeserializeOwned, Serialize};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// How fast a background [`ExpirySweeper`] scans.
+#[derive(Debug, Clone)]
+pub struct SweepOptions {
+    interval: Duration,
+    batch_size: usize,
+    reclaim: Option<(Duration, usize)>,
+}
+
+impl Default for SweepOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            batch_size: 1000,
+            reclaim: None,
+        }
+    }
+}
+
+impl SweepOptions {
+    /// The defaults: 1000 entries examined every second, no scheduled compaction.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait `interval` between batches.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Examine up to `batch_size` entries per batch (at least one).
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Also call [`compact_expired`](TtlRocksMap::compact_expired) with `max_ranges` once every
+    /// `every`, checked between batches. Each call first removes every entry due, whatever the
+    /// batch size, handing each to the expiry listeners.
+    pub fn reclaim_every(mut self, every: Duration, max_ranges: usize) -> Self {
+        self.reclaim = Some((every, max_ranges));
+        self
+    }
+}
+
+/// Handle to a background sweeper started by [`TtlRocksMap::spawn_sweeper`]. Dropping it stops
+/// the sweeper and waits for its current batch to finish.
+pub struct ExpirySweeper {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<Result<()>>>,
+}
+
+impl ExpirySweeper {
+    /// Stop the sweeper and wait for it, returning the error that stopped it early, if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.shutdown()
+    }
+
+    fn shutdown(&mut self) -> Result<()> {
+        // Hanging up the channel wakes the thread out of its wait.
+        self.stop.take();
+        match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ExpirySweeper {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+    }
+}
+
+impl<K, V> TtlRocksMap<K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey + Send + Sync + 'static,
+    V: Serialize + DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    /// Start a background thread that removes expired entries at the rate set by `options`,
+    /// calling the expiry listeners with each one. The thread stops when the returned handle is
+    /// dropped, or on the first storage error (reported by [`ExpirySweeper::stop`]).
+    pub fn spawn_sweeper(self: &Arc<Self>, options: SweepOptions) -> ExpirySweeper {
+        let map = Arc::clone(self);
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let mut cursor = None;
+            let mut reclaimed = Instant::now();
+            loop {
+                match stopped.recv_timeout(options.interval) {
+                    Err(RecvTimeoutError::Timeout) => {
+                        map.sweep_batch(&mut cursor, options.batch_size)?;
+                        if let Some((every, max_ranges)) = options.reclaim {
+                            if reclaimed.elapsed() >= every {
+                                map.sweep_and_compact_expired(max_ranges)?;
+                                reclaimed = Instant::now();
+                            }
+                        }
+                    }
+                    _ => return Ok(()),
+                }
+            }
+        });
+        ExpirySweeper {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    #[test]
+    fn sweep_removes_expired_and_notifies() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let released = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&released);
+        let map = TtlRocksMap::<u32, String>::builder(dir.path())
+            .clock(Arc::new(clock.clone()))
+            .on_expire(move |key, value| log.lock().unwrap().push((key, value)))
+            .open()
+            .unwrap();
+        for i in 0..2500u32 {
+            let ttl = Duration::from_millis(if i % 1000 == 0 { 10 } else { 100 });
+            map.put_with_ttl(i, &format!("lease{i}"), ttl).unwrap();
+        }
+        map.put(9999, &"forever".to_string()).unwrap();
+
+        assert_eq!(map.sweep_expired().unwrap(), 0);
+        clock.set(10);
+        assert_eq!(map.sweep_expired().unwrap(), 3);
+        let mut seen = released.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(
+            seen,
+            vec![
+                (0, "lease0".to_string()),
+                (1000, "lease1000".to_string()),
+                (2000, "lease2000".to_string()),
+            ]
+        );
+
+        clock.set(100);
+        assert_eq!(map.sweep_expired().unwrap(), 2497);
+        assert_eq!(released.lock().unwrap().len(), 2500);
+        assert_eq!(map.db().iterator(rocksdb::IteratorMode::Start).count(), 1);
+        assert_eq!(map.get(&9999).unwrap(), Some("forever".to_string()));
+    }
+
+    #[test]
+    fn background_sweeper_sends_events_and_stops() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let (sender, events) = mpsc::channel();
+        let map = Arc::new(
+            TtlRocksMap::<String, u32>::builder(dir.path())
+                .clock(Arc::new(clock.clone()))
+                .notify_expired(sender)
+                .open()
+                .unwrap(),
+        );
+        map.put_with_ttl("lease".to_string(), &7, Duration::from_millis(50))
+            .unwrap();
+        map.put_with_ttl("renewed".to_string(), &8, Duration::from_millis(50))
+            .unwrap();
+
+        let sweeper = map.spawn_sweeper(
+            SweepOptions::new()
+                .interval(Duration::from_millis(1))
+                .batch_size(1),
+        );
+        map.expire(&"renewed".to_string(), Duration::from_secs(60))
+            .unwrap();
+        clock.set(50);
+        let event = events.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(event, ("lease".to_string(), 7));
+        sweeper.stop().unwrap();
+
+        assert!(events.try_recv().is_err());
+        assert_eq!(map.get(&"renewed".to_string()).unwrap(), Some(8));
+        assert_eq!(map.db().iterator(rocksdb::IteratorMode::Start).count(), 1);
+    }
+
+    #[test]
+    fn background_sweeper_reclaims_expired_rows_on_schedule() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = Arc::new(
+            TtlRocksMap::<u32, u32>::open_with_clock(dir.path(), Arc::new(clock.clone())).unwrap(),
+        );
+        for i in 0..100 {
+            map.put_with_ttl(i, &i, Duration::from_millis(10)).unwrap();
+        }
+        map.db().flush().unwrap();
+        clock.set(10);
+
+        // A zero batch budget is raised to one, so the sweep alone would take 100 rounds.
+        let sweeper = map.spawn_sweeper(
+            SweepOptions::new()
+                .interval(Duration::from_millis(1))
+                .batch_size(0)
+                .reclaim_every(Duration::ZERO, 1),
+        );
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while map.ttl_stats().unwrap().sst_bytes > 0 {
+            assert!(Instant::now() < deadline, "expired rows were not reclaimed");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        sweeper.stop().unwrap();
+        assert_eq!(map.ttl_stats().unwrap().expired_entries, 0);
+    }
+
+    #[test]
+    fn scheduled_reclamation_reports_every_expired_entry() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let (sender, events) = mpsc::channel();
+        let map = Arc::new(
+            TtlRocksMap::<u32, u32>::builder(dir.path())
+                .clock(Arc::new(clock.clone()))
+                .notify_expired(sender)
+                .open()
+                .unwrap(),
+        );
+        for i in 0..100 {
+            map.put_with_ttl(i, &i, Duration::from_millis(10)).unwrap();
+            if i % 25 == 24 {
+                map.db().flush().unwrap();
+            }
+        }
+        clock.set(10);
+
+        let sweeper = map.spawn_sweeper(
+            SweepOptions::new()
+                .interval(Duration::from_millis(1))
+                .batch_size(1)
+                .reclaim_every(Duration::ZERO, 1),
+        );
+        let mut reported: Vec<u32> = Vec::new();
+        while reported.len() < 100 {
+            let (key, _) = events.recv_timeout(Duration::from_secs(10)).unwrap();
+            reported.push(key);
+        }
+        sweeper.stop().unwrap();
+        reported.sort();
+        assert_eq!(reported, (0..100).collect::<Vec<_>>());
+        assert_eq!(map.db().iterator(rocksdb::IteratorMode::Start).count(), 0);
+    }
+}
@@ -9,18 +9,40 @@
 //! tag = 0x01 + u64 (BE) -> expires at that UNIX-millis deadline, then the payload
 //! ```
 //!
-//! Expiry is enforced two ways: the **read path** (`get`/`iter`) treats an expired entry as
-//! absent, so expiry is logically immediate; and a **compaction filter** physically drops
-//! expired entries during background compaction. Deadlines use a wall-clock [`Clock`].
+//! Expiry is enforced two ways: the **read path** (`get`, `iter`, and the range and prefix
+//! scans) treats an expired entry as absent, so expiry is logically immediate; and a
+//! **compaction filter** physically drops expired entries during background compaction. To act
+//! on expiry, register listeners with [`TtlRocksMapBuilder::on_expire`] and remove expired
+//! entries actively with [`TtlRocksMap::sweep_expired`] or a background
+//! [`ExpirySweeper`](crate::ExpirySweeper). Deadlines use a wall-clock [`Clock`].
 
+use crate::batch::TtlRocksMapBatch;
+use crate::cache::{self, CacheDelta, CacheOptions, CacheState};
+use crate::cdc::ChangeStream;
 use crate::clock::{Clock, SystemClock};
 use crate::codec::{BincodeCodec, KeyCodec, ValueCodec};
 use crate::error::{Error, Result};
+use crate::format::{self, OpenOptions};
 use crate::meta;
-use crate::ordered::{OrderedCodec, OrderedKey};
-use rocksdb::{ColumnFamilyDescriptor, CompactionDecision, IteratorMode, Options, DB};
+use crate::ordered::{OrderedCodec, OrderedKey, PrefixKey};
+use crate::rocks_map::{
+    encode_ordered, prefix_range_to_bounds, prefix_to_bounds, range_to_bounds, ByteBounds,
+};
+use crate::schema;
+use crate::verify::{self, VerifyMode, VerifyReport};
+use rocksdb::{
+    ColumnFamily, ColumnFamilyDescriptor, CompactionDecision, IteratorMode, Options, ReadOptions,
+    WriteBatch, DB,
+};
 use serde::{de::DeserializeOwned, Serialize};
-use std::{marker::PhantomData, path::Path, sync::Arc, time::Duration};
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    ops::RangeBounds,
+    path::{Path, PathBuf},
+    sync::{mpsc::Sender, Arc, Mutex, MutexGuard},
+    time::Duration,
+};
 
 const TAG_NO_TTL: u8 = 0;
 const TAG_TTL: u8 = 1;
@@ -45,7 +67,7 @@ fn encode_envelope(expire_at: Option<u64>, payload: &[u8]) -> Vec<u8> {
 }
 
 /// Split an envelope into `(expire_at, payload)`.
-fn decode_envelope(bytes: &[u8]) -> Result<(Option<u64>, &[u8])> {
+pub(crate) fn decode_envelope(bytes: &[u8]) -> Result<(Option<u64>, &[u8])> {
     match bytes.split_first() {
         Some((&TAG_NO_TTL, payload)) => Ok((None, payload)),
         Some((&TAG_TTL, rest)) => {
@@ -62,10 +84,35 @@ fn decode_envelope(bytes: &[u8]) -> Result<(Option<u64>, &[u8])> {
     }
 }
 
-fn is_expired(expire_at: Option<u64>, now: u64) -> bool {
+pub(crate) fn is_expired(expire_at: Option<u64>, now: u64) -> bool {
     matches!(expire_at, Some(deadline) if deadline <= now)
 }
 
+/// The expiry-index key of an entry: its deadline, big-endian so the index sorts by deadline,
+/// then its encoded key.
+pub(crate) fn expiry_index_key(deadline: u64, key_bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + key_bytes.len());
+    out.extend_from_slice(&deadline.to_be_bytes());
+    out.extend_from_slice(key_bytes);
+    out
+}
+
+/// Split an expiry-index key into `(deadline, encoded key)`.
+pub(crate) fn split_expiry_index_key(index_key: &[u8]) -> Result<(u64, &[u8])> {
+    if index_key.len() < 8 {
+        return Err(Error::Deserialization(
+            "truncated expiry index entry".to_string(),
+        ));
+    }
+    let (deadline, key_bytes) = index_key.split_at(8);
+    Ok((u64::from_be_bytes(deadline.try_into().unwrap()), key_bytes))
+}
+
+/// The deadline `ttl` after `now`, saturating rather than wrapping.
+pub(crate) fn deadline_after(now: u64, ttl: Duration) -> u64 {
+    now.saturating_add(ttl.as_millis() as u64)
+}
+
 /// Decode a TTL-map value envelope for tooling that reads TTL databases directly (e.g. the CLI).
 ///
 /// Returns `Ok(None)` if the entry is expired at `now_unix_millis`, `Ok(Some(payload))` with the
@@ -79,18 +126,279 @@ pub fn strip_ttl_envelope(value: &[u8], now_unix_millis: u64) -> Result<Option<V
     }
 }
 
+/// Sliding-expiry settings: reads push a deadline out to `idle_millis` from now, but only once
+/// more than `refresh_after_millis` of that window has elapsed.
+#[derive(Debug, Clone, Copy)]
+struct SlidingExpiry {
+    idle_millis: u64,
+    refresh_after_millis: u64,
+}
+
+impl SlidingExpiry {
+    /// Whether a read at `now` of an entry expiring at `expire_at` should refresh it. Entries
+    /// without an expiry, or with a deadline further out than the idle window, are left alone.
+    fn needs_refresh(&self, expire_at: Option<u64>, now: u64) -> bool {
+        match expire_at {
+            Some(deadline) => {
+                let elapsed = self
+                    .idle_millis
+                    .saturating_sub(deadline.saturating_sub(now));
+                elapsed > self.refresh_after_millis
+            }
+            None => false,
+        }
+    }
+}
+
+/// How long past its deadline an expiry-index entry survives compaction by default.
+const DEFAULT_EXPIRY_INDEX_GRACE: Duration = Duration::from_secs(60 * 60);
+
+/// A callback run with each entry the sweeper removes.
+type ExpiryListener<K, V> = Box<dyn Fn(K, V) + Send + Sync>;
+
+/// Builder for a [`TtlRocksMap`]: clock, default TTL, sliding expiry, expiry listeners, TTL
+/// tables, cache mode, periodic compaction, and RocksDB options.
+pub struct TtlRocksMapBuilder<K, V> {
+    path: PathBuf,
+    options: Options,
+    periodic_compaction: Option<Duration>,
+    index_grace: Duration,
+    clock: Arc<dyn Clock>,
+    default_ttl: Option<Duration>,
+    tables: BTreeMap<String, Option<Duration>>,
+    idle_timeout: Option<Duration>,
+    refresh_percent: u8,
+    listeners: Vec<ExpiryListener<K, V>>,
+    cache: Option<CacheOptions>,
+}
+
+impl<K, V> TtlRocksMapBuilder<K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Serialize + DeserializeOwned + Clone,
+{
+    /// Read "now" from `clock` instead of the system clock (for deterministic testing).
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Make writes that don't specify a TTL expire after `ttl`.
+    pub fn default_ttl(mut self, ttl: Duration) -> Self {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Open the database with RocksDB `options`, which also apply to the column families holding
+    /// TTL data. Each of those gets the TTL compaction filter, replacing any set in `options`.
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Have RocksDB compact every file of the column families holding TTL data at least once per
+    /// `interval` (its `periodic_compaction_seconds`), so the compaction filter eventually
+    /// reaches expired rows in key ranges that are never compacted otherwise. RocksDB's default
+    /// for a column family with a compaction filter is 30 days. See also
+    /// [`TtlRocksMap::compact_expired`], which targets the ranges holding expired rows.
+    pub fn periodic_compaction(mut self, interval: Duration) -> Self {
+        self.periodic_compaction = Some(interval);
+        self
+    }
+
+    /// Let compaction drop an expiry-index entry once its deadline is more than `grace` in the
+    /// past (default one hour). Until then the entry is left for the expiry sweep, which reports
+    /// the entry to the expiry listeners even if compaction already dropped its row; a `grace`
+    /// shorter than the sweeper lags behind loses those reports. Without a sweeper, this is
+    /// what keeps the index from growing without bound.
+    pub fn expiry_index_grace(mut self, grace: Duration) -> Self {
+        self.index_grace = grace;
+        self
+    }
+
+    /// Declare a TTL table: column family `name`, created if missing, holding TTL envelopes and
+    /// dropping expired ones in its own compaction filter, with its own expiry index. Writes to
+    /// it that don't specify a TTL expire after `default_ttl` (no expiry if `None`). Reach it
+    /// with [`TtlRocksMap::table`]. Tables share the map's key and value types, clock, sliding
+    /// expiry, and expiry listeners.
+    pub fn table(mut self, name: &str, default_ttl: Option<Duration>) -> Self {
+        self.tables.insert(name.to_string(), default_ttl);
+        self
+    }
+
+    /// Expire entries `idle_timeout` after they were last read: `get` and iteration push an
+    /// expiring entry's deadline out to `idle_timeout` from now (never pulling it in), while
+    /// [`peek`](TtlRocksMap::peek) and the other introspection methods do not. Writes that
+    /// don't specify a TTL expire after `idle_timeout` unless a [`default_ttl`](Self::default_ttl)
+    /// is also set; entries without an expiry stay that way.
+    pub fn sliding_expiry(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = Some(idle_timeout);
+        self
+    }
+
+    /// Coalesce sliding refreshes: a read rewrites an entry's deadline only once more than
+    /// `percent` of its idle window has elapsed since the last refresh, so hot keys are not
+    /// rewritten on every read. Defaults to 10; values above 100 are treated as 100.
+    pub fn refresh_threshold_percent(mut self, percent: u8) -> Self {
+        self.refresh_percent = percent.min(100);
+        self
+    }
+
+    /// Call `listener` with each entry the expiry sweeper removes (see
+    /// [`TtlRocksMap::sweep_expired`]). Entries that merely read as expired are not reported
+    /// until a sweep removes them.
+    pub fn on_expire<F>(mut self, listener: F) -> Self
+    where
+        F: Fn(K, V) + Send + Sync + 'static,
+    {
+        self.listeners.push(Box::new(listener));
+        self
+    }
+
+    /// Send each entry the expiry sweeper removes to `sender`, like
+    /// [`on_expire`](Self::on_expire). Events are dropped once the receiver hangs up.
+    pub fn notify_expired(self, sender: Sender<(K, V)>) -> Self
+    where
+        K: Send + 'static,
+        V: Send + 'static,
+    {
+        self.on_expire(move |key, value| {
+            let _ = sender.send((key, value));
+        })
+    }
+
+    /// Bound the map's size as a cache: once a write takes it past the budget in `options`,
+    /// expired entries are removed and then the least recently or least frequently used ones
+    /// evicted. Applies to the map itself, not to its [`table`](Self::table)s.
+    pub fn cache(mut self, options: CacheOptions) -> Self {
+        self.cache = Some(options);
+        self
+    }
+
+    /// Open the database.
+    pub fn open(self) -> Result<TtlRocksMap<K, V>> {
+        self.open_with(&OpenOptions::default())
+    }
+
+    /// Like [`open`](Self::open), with rocksmap-level [`OpenOptions`] (e.g. to allow a format
+    /// upgrade).
+    pub fn open_with(self, open: &OpenOptions) -> Result<TtlRocksMap<K, V>> {
+        TtlRocksMap::open_internal(self, open)
+    }
+}
+
+/// Options for a column family holding TTL envelopes: `base` plus the TTL compaction filter,
+/// which drops expired envelopes during compaction and keeps anything it cannot parse as one.
+fn data_options(base: &Options, clock: &Arc<dyn Clock>) -> Options {
+    let clock = clock.clone();
+    let mut opts = base.clone();
+    opts.set_compaction_filter(
+        "rocksmap.ttl",
+        move |_level, _key, value| match decode_envelope(value) {
+            Ok((expire_at, _)) if is_expired(expire_at, clock.now_unix_millis()) => {
+                CompactionDecision::Remove
+            }
+            _ => CompactionDecision::Keep,
+        },
+    );
+    opts
+}
+
+/// Options for an expiry index column family. Its compaction filter drops entries whose deadline
+/// is more than `grace` past, leaving the sweep that long to consume them first.
+fn expiry_index_options(clock: &Arc<dyn Clock>, grace: Duration) -> Options {
+    let clock = clock.clone();
+    let grace = grace.as_millis() as u64;
+    let mut opts = Options::default();
+    opts.set_compaction_filter("rocksmap.ttl_expiry", move |_level, key, _value| {
+        match split_expiry_index_key(key) {
+            Ok((deadline, _))
+                if is_expired(
+                    Some(deadline.saturating_add(grace)),
+                    clock.now_unix_millis(),
+                ) =>
+            {
+                CompactionDecision::Remove
+            }
+            _ => CompactionDecision::Keep,
+        }
+    });
+    opts
+}
+
+/// Index the deadlines of the rows stored before the expiry index existed (databases written by
+/// an earlier version). Idempotent, so an open interrupted part way simply redoes it.
+fn build_expiry_index(db: &DB) -> Result<()> {
+    if meta::is_ttl_expiry_indexed(db)? {
+        return Ok(());
+    }
+    let index = db
+        .cf_handle(meta::TTL_EXPIRY_CF)
+        .ok_or_else(|| Error::ColumnFamilyNotFound(meta::TTL_EXPIRY_CF.to_string()))?;
+    let mut batch = WriteBatch::default();
+    for item in db.iterator(IteratorMode::Start) {
+        let (key_bytes, value_bytes) = item.map_err(Error::from)?;
+        if let Ok((Some(deadline), _)) = decode_envelope(&value_bytes) {
+            batch.put_cf(index, expiry_index_key(deadline, &key_bytes), []);
+        }
+        if batch.len() >= 10_000 {
+            db.write(std::mem::take(&mut batch)).map_err(Error::from)?;
+        }
+    }
+    db.write(batch).map_err(Error::from)?;
+    meta::mark_ttl_expiry_indexed(db)
+}
+
+/// Prepare the TTL table `name` in an opened TTL database: create its column family and expiry
+/// index if missing and record it in the metadata. An existing column family that is not a TTL
+/// table is only taken over if empty (and recreated, to install the compaction filter).
+fn prepare_table(db: &mut DB, name: &str, data_opts: &Options, index_opts: &Options) -> Result<()> {
+    let index_name = meta::ttl_expiry_cf(name);
+    if db.cf_handle(&index_name).is_none() {
+        if let Some(cf) = db.cf_handle(name) {
+            if db.iterator_cf(cf, IteratorMode::Start).next().is_some() {
+                return Err(Error::FormatMismatch(format!(
+                    "column family `{name}` holds data that was not written as a TTL table"
+                )));
+            }
+            db.drop_cf(name).map_err(Error::from)?;
+        }
+    }
+    if db.cf_handle(name).is_none() {
+        db.create_cf(name, data_opts).map_err(Error::from)?;
+    }
+    if db.cf_handle(&index_name).is_none() {
+        db.create_cf(&index_name, index_opts).map_err(Error::from)?;
+    }
+    meta::mark_ttl_table(&*db, name)
+}
+
 /// A typed map whose entries can carry per-key time-to-live.
 ///
-/// Stored on the default column family. Distinct from [`RocksMap`](crate::RocksMap): opening
-/// the same database the other way fails via the persisted format tag.
+/// Stored on the default column family, with an internal expiry index (`__ttl_expiry`) that
+/// orders the expiring keys by deadline. Further TTL tables, declared with
+/// [`TtlRocksMapBuilder::table`], live in column families of their own in the same database;
+/// [`table`](Self::table) returns a handle to one. Distinct from [`RocksMap`](crate::RocksMap):
+/// opening the same database the other way fails via the persisted format tag.
 pub struct TtlRocksMap<K, V>
 where
     K: Serialize + DeserializeOwned + Clone + OrderedKey,
     V: Serialize + DeserializeOwned + Clone,
 {
-    db: DB,
+    // Shared by the handles of every table in the database.
+    db: Arc<DB>,
+    // The column family this handle reads and writes; `None` for the default one.
+    table: Option<String>,
+    // The default TTL declared for each table besides the default column family.
+    tables: Arc<BTreeMap<String, Option<Duration>>>,
     clock: Arc<dyn Clock>,
     default_ttl: Option<Duration>,
+    sliding: Option<SlidingExpiry>,
+    listeners: Arc<Vec<ExpiryListener<K, V>>>,
+    // Access metadata and counters, if this handle is in cache mode.
+    cache: Option<Arc<CacheState>>,
+    // Serializes writes with the expiry rewrites, so a header update never overwrites a newer put.
+    write_lock: Arc<Mutex<()>>,
     _marker: PhantomData<(K, V)>,
 }
 
@@ -101,17 +409,17 @@ where
 {
     /// Open a TTL map at `path` using the system clock and no default TTL.
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        Self::open_internal(path, Arc::new(SystemClock), None)
+        Self::builder(path).open()
     }
 
     /// Open a TTL map where writes that don't specify a TTL expire after `default_ttl`.
     pub fn open_with_default_ttl<P: AsRef<Path>>(path: P, default_ttl: Duration) -> Result<Self> {
-        Self::open_internal(path, Arc::new(SystemClock), Some(default_ttl))
+        Self::builder(path).default_ttl(default_ttl).open()
     }
 
     /// Open a TTL map with an injected clock (for deterministic testing).
     pub fn open_with_clock<P: AsRef<Path>>(path: P, clock: Arc<dyn Clock>) -> Result<Self> {
-        Self::open_internal(path, clock, None)
+        Self::builder(path).clock(clock).open()
     }
 
     /// Open a TTL map with both an injected clock and a default TTL.
@@ -120,47 +428,91 @@ where
         clock: Arc<dyn Clock>,
         default_ttl: Duration,
     ) -> Result<Self> {
-        Self::open_internal(path, clock, Some(default_ttl))
+        Self::builder(path)
+            .clock(clock)
+            .default_ttl(default_ttl)
+            .open()
     }
 
-    fn open_internal<P: AsRef<Path>>(
-        path: P,
-        clock: Arc<dyn Clock>,
-        default_ttl: Option<Duration>,
-    ) -> Result<Self> {
-        let path = path.as_ref().to_path_buf();
-        if !path.exists() {
-            std::fs::create_dir_all(&path).map_err(|_| Error::InvalidPath(path.clone()))?;
+    /// Open a TTL map with custom RocksDB options. See [`TtlRocksMapBuilder::options`].
+    pub fn open_with_options<P: AsRef<Path>>(path: P, options: Options) -> Result<Self> {
+        Self::builder(path).options(options).open()
+    }
+
+    /// Open a TTL map with rocksmap-level [`OpenOptions`] (e.g. to allow a format upgrade).
+    pub fn open_with<P: AsRef<Path>>(path: P, open: &OpenOptions) -> Result<Self> {
+        Self::builder(path).open_with(open)
+    }
+
+    /// Start building a TTL map at `path`, e.g. to enable sliding expiry.
+    pub fn builder<P: AsRef<Path>>(path: P) -> TtlRocksMapBuilder<K, V> {
+        TtlRocksMapBuilder {
+            path: path.as_ref().to_path_buf(),
+            options: Options::default(),
+            periodic_compaction: None,
+            index_grace: DEFAULT_EXPIRY_INDEX_GRACE,
+            clock: Arc::new(SystemClock),
+            default_ttl: None,
+            tables: BTreeMap::new(),
+            idle_timeout: None,
+            refresh_percent: 10,
+            listeners: Vec::new(),
+            cache: None,
         }
+    }
 
-        // The default column family carries the TTL compaction filter; the filter drops
-        // expired envelopes during compaction and keeps anything it cannot parse as one.
-        let mut data_opts = Options::default();
-        data_opts.create_if_missing(true);
-        data_opts.create_missing_column_families(true);
-        let filter_clock = clock.clone();
-        data_opts.set_compaction_filter("rocksmap.ttl", move |_level, _key, value| {
-            match decode_envelope(value) {
-                Ok((expire_at, _)) if is_expired(expire_at, filter_clock.now_unix_millis()) => {
-                    CompactionDecision::Remove
-                }
-                _ => CompactionDecision::Keep,
+    fn open_internal(builder: TtlRocksMapBuilder<K, V>, open: &OpenOptions) -> Result<Self> {
+        let TtlRocksMapBuilder {
+            path,
+            options,
+            periodic_compaction,
+            index_grace,
+            clock,
+            default_ttl,
+            tables,
+            idle_timeout,
+            refresh_percent,
+            listeners,
+            cache,
+        } = builder;
+        if let Some(name) = tables
+            .keys()
+            .find(|name| *name == "default" || meta::is_internal_cf(name))
+        {
+            return Err(Error::Other(format!(
+                "`{name}` is reserved and cannot name a TTL table"
+            )));
+        }
+        let sliding = idle_timeout.map(|idle| {
+            let idle_millis = idle.as_millis() as u64;
+            let share = u128::from(idle_millis) * u128::from(refresh_percent) / 100;
+            SlidingExpiry {
+                idle_millis,
+                refresh_after_millis: share as u64,
             }
         });
+        if !path.exists() {
+            std::fs::create_dir_all(&path).map_err(|_| Error::InvalidPath(path.clone()))?;
+        }
 
-        let mut db_opts = Options::default();
+        let mut db_opts = options.clone();
         db_opts.create_if_missing(true);
         db_opts.create_missing_column_families(true);
 
-        // The metadata CF (and any others) must not carry the envelope filter; only the
-        // default CF, which holds the TTL data, does. `data_opts` is moved in exactly once.
+        // Only the column families holding TTL data carry the envelope filter: the default one
+        // and every existing table, known by its expiry index. The metadata CF (and any others)
+        // must not. Expiry indexes and new tables are only created once the database is known
+        // to be a TTL map.
         let names = meta::all_cf_names(&db_opts, &path, &[]);
-        let mut data_opts = Some(data_opts);
         let descriptors: Vec<ColumnFamilyDescriptor> = names
             .iter()
             .map(|name| {
-                let opts = if name == "default" {
-                    data_opts.take().expect("exactly one default column family")
+                let opts = if name.starts_with(meta::TTL_EXPIRY_CF) {
+                    expiry_index_options(&clock, index_grace)
+                } else if meta::is_internal_cf(name) {
+                    Options::default()
+                } else if name == "default" || names.contains(&meta::ttl_expiry_cf(name)) {
+                    data_options(&options, &clock)
                 } else {
                     Options::default()
                 };
@@ -168,39 +520,233 @@ where
             })
             .collect();
 
-        let db = DB::open_cf_descriptors(&db_opts, &path, descriptors).map_err(Error::from)?;
+        let mut db = DB::open_cf_descriptors(&db_opts, &path, descriptors).map_err(Error::from)?;
+        if let Some(mark) = meta::read_clock_high_water(&db)? {
+            clock.restore_high_water_mark(mark);
+        }
+        format::prepare(&db, open)?;
         meta::verify_or_write_kind(&db, meta::MapKind::Ttl)?;
+        schema::verify_or_write_types::<K, V, _>(&db, open)?;
+        if db.cf_handle(meta::TTL_EXPIRY_CF).is_none() {
+            db.create_cf(
+                meta::TTL_EXPIRY_CF,
+                &expiry_index_options(&clock, index_grace),
+            )
+            .map_err(Error::from)?;
+        }
+        build_expiry_index(&db)?;
+        let data_opts = data_options(&options, &clock);
+        let index_opts = expiry_index_options(&clock, index_grace);
+        for name in tables.keys() {
+            prepare_table(&mut db, name, &data_opts, &index_opts)?;
+        }
+        // Tables created by an earlier open stay reachable, without a default TTL.
+        let mut tables = tables;
+        for name in meta::read_ttl_tables(&db)? {
+            tables.entry(name).or_insert(None);
+        }
+        let cache = cache::open_cache(&mut db, cache)?;
+        if let Some(interval) = periodic_compaction {
+            let seconds = interval.as_secs().max(1).to_string();
+            for name in std::iter::once("default").chain(tables.keys().map(String::as_str)) {
+                let cf = db
+                    .cf_handle(name)
+                    .ok_or_else(|| Error::ColumnFamilyNotFound(name.to_string()))?;
+                db.set_options_cf(cf, &[("periodic_compaction_seconds", &seconds)])
+                    .map_err(Error::from)?;
+            }
+        }
 
         Ok(Self {
-            db,
+            db: Arc::new(db),
+            table: None,
+            tables: Arc::new(tables),
             clock,
-            default_ttl,
+            default_ttl: default_ttl.or(idle_timeout),
+            sliding,
+            listeners: Arc::new(listeners),
+            cache: cache.map(Arc::new),
+            write_lock: Arc::new(Mutex::new(())),
             _marker: PhantomData,
         })
     }
 
-    fn now(&self) -> u64 {
+    /// A handle to the TTL table `name`, declared with [`TtlRocksMapBuilder::table`] (or by an
+    /// earlier open of the database, in which case it has no default TTL). The handle shares
+    /// this map's database, clock, sliding expiry, and expiry listeners, but not its cache mode.
+    pub fn table(&self, name: &str) -> Result<Self> {
+        let default_ttl = *self
+            .tables
+            .get(name)
+            .ok_or_else(|| Error::ColumnFamilyNotFound(name.to_string()))?;
+        let idle = self
+            .sliding
+            .map(|sliding| Duration::from_millis(sliding.idle_millis));
+        Ok(Self {
+            db: Arc::clone(&self.db),
+            table: Some(name.to_string()),
+            tables: Arc::clone(&self.tables),
+            clock: Arc::clone(&self.clock),
+            default_ttl: default_ttl.or(idle),
+            sliding: self.sliding,
+            listeners: Arc::clone(&self.listeners),
+            cache: None,
+            write_lock: Arc::clone(&self.write_lock),
+            _marker: PhantomData,
+        })
+    }
+
+    /// The column family this map reads and writes: `"default"`, or the name of a TTL table.
+    pub fn table_name(&self) -> &str {
+        self.table.as_deref().unwrap_or("default")
+    }
+
+    pub(crate) fn now(&self) -> u64 {
         self.clock.now_unix_millis()
     }
 
+    pub(crate) fn lock(&self) -> MutexGuard<'_, ()> {
+        self.write_lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn cache(&self) -> Option<&CacheState> {
+        self.cache.as_deref()
+    }
+
     fn store(&self, key: &K, value: &V, expire_at: Option<u64>) -> Result<()> {
         let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
         let payload = <BincodeCodec<V> as ValueCodec<V>>::encode(value)?;
-        let envelope = encode_envelope(expire_at, &payload);
-        self.db.put(key_bytes, envelope).map_err(Error::from)
+        {
+            let _guard = self.lock();
+            let previous = self.stored_expiry(&key_bytes)?;
+            self.write(&key_bytes, previous, Some((expire_at, &payload)))?;
+        }
+        self.enforce_capacity(&[&key_bytes])
+    }
+
+    /// The deadline of the row stored under `key_bytes`, expired or not (`None` if there is no
+    /// row, it never expires, or it is not a valid envelope).
+    pub(crate) fn stored_expiry(&self, key_bytes: &[u8]) -> Result<Option<u64>> {
+        Ok(
+            match self
+                .db
+                .get_cf(self.data_cf(), key_bytes)
+                .map_err(Error::from)?
+            {
+                Some(envelope) => decode_envelope(&envelope)
+                    .ok()
+                    .and_then(|(expire_at, _)| expire_at),
+                None => None,
+            },
+        )
+    }
+
+    /// Replace the row under `key_bytes`, whose deadline was `previous`, with `entry` (an
+    /// expiry and payload), or delete it if `None`, moving its expiry-index entry in the same
+    /// atomic write. The caller holds the write lock.
+    pub(crate) fn write(
+        &self,
+        key_bytes: &[u8],
+        previous: Option<u64>,
+        entry: Option<(Option<u64>, &[u8])>,
+    ) -> Result<()> {
+        let mut batch = WriteBatch::default();
+        let delta = self.stage(&mut batch, key_bytes, previous, entry)?;
+        self.stage_clock_mark(&mut batch);
+        self.db.write(batch).map_err(Error::from)?;
+        self.commit_cache_delta(delta);
+        Ok(())
+    }
+
+    /// Add the clock's high-water mark, if it keeps one (see
+    /// [`MonotonicGuardClock`](crate::MonotonicGuardClock)), to `batch`, so it is persisted with
+    /// the writes made against it.
+    pub(crate) fn stage_clock_mark(&self, batch: &mut WriteBatch) {
+        if let Some(mark) = self.clock.high_water_mark() {
+            let meta_cf = self
+                .db
+                .cf_handle(meta::META_CF)
+                .expect("the metadata column family is opened with the database");
+            let (key, value) = meta::clock_high_water_record(mark);
+            batch.put_cf(meta_cf, key, value);
+        }
+    }
+
+    /// Add the writes of [`write`](Self::write) to `batch`, returning the change to the cache
+    /// totals to commit once it is written.
+    pub(crate) fn stage(
+        &self,
+        batch: &mut WriteBatch,
+        key_bytes: &[u8],
+        previous: Option<u64>,
+        entry: Option<(Option<u64>, &[u8])>,
+    ) -> Result<CacheDelta> {
+        let index = self.expiry_index();
+        let (expire_at, size) = match entry {
+            Some((expire_at, payload)) => {
+                let envelope = encode_envelope(expire_at, payload);
+                let size = (key_bytes.len() + envelope.len()) as u64;
+                batch.put_cf(self.data_cf(), key_bytes, envelope);
+                (expire_at, Some(size))
+            }
+            None => {
+                batch.delete_cf(self.data_cf(), key_bytes);
+                (None, None)
+            }
+        };
+        if let Some(deadline) = previous.filter(|&deadline| Some(deadline) != expire_at) {
+            batch.delete_cf(index, expiry_index_key(deadline, key_bytes));
+        }
+        if let Some(deadline) = expire_at {
+            batch.put_cf(index, expiry_index_key(deadline, key_bytes), []);
+        }
+        self.stage_cache(batch, key_bytes, size)
+    }
+
+    /// Add to `batch` the cache metadata changes of the row under `key_bytes` becoming `size`
+    /// bytes long, or being gone if `None` (as when the compaction filter dropped it).
+    pub(crate) fn stage_cache(
+        &self,
+        batch: &mut WriteBatch,
+        key_bytes: &[u8],
+        size: Option<u64>,
+    ) -> Result<CacheDelta> {
+        match self.cache() {
+            Some(cache) => cache.stage(&self.db, batch, key_bytes, size, self.now()),
+            None => Ok(CacheDelta::default()),
+        }
+    }
+
+    pub(crate) fn data_cf(&self) -> &ColumnFamily {
+        self.db
+            .cf_handle(self.table_name())
+            .expect("TTL tables are created at open")
+    }
+
+    pub(crate) fn expiry_index(&self) -> &ColumnFamily {
+        self.db
+            .cf_handle(&meta::ttl_expiry_cf(self.table_name()))
+            .expect("the expiry index is created at open")
+    }
+
+    /// The deadline of a write at `now` that doesn't specify a TTL.
+    pub(crate) fn default_expiry(&self, now: u64) -> Option<u64> {
+        self.default_ttl.map(|ttl| deadline_after(now, ttl))
     }
 
     /// Store a value using the map's `default_ttl` (no expiry if none was configured).
     pub fn put(&self, key: K, value: &V) -> Result<()> {
-        let expire_at = self
-            .default_ttl
-            .map(|ttl| self.now().saturating_add(ttl.as_millis() as u64));
-        self.store(&key, value, expire_at)
+        self.store(&key, value, self.default_expiry(self.now()))
+    }
+
+    /// Start an atomic batch of writes. See [`TtlRocksMapBatch`].
+    pub fn batch(&self) -> TtlRocksMapBatch<'_, K, V> {
+        TtlRocksMapBatch::new(self)
     }
 
     /// Store a value that expires `ttl` from now.
     pub fn put_with_ttl(&self, key: K, value: &V, ttl: Duration) -> Result<()> {
-        let expire_at = self.now().saturating_add(ttl.as_millis() as u64);
+        let expire_at = deadline_after(self.now(), ttl);
         self.store(&key, value, Some(expire_at))
     }
 
@@ -209,36 +755,168 @@ where
         self.store(&key, value, Some(expire_at_unix_millis))
     }
 
-    /// Retrieve a value, treating an expired entry as absent.
+    /// Retrieve a value, treating an expired entry as absent. With sliding expiry this counts
+    /// as an access and may push the entry's deadline out; see [`peek`](Self::peek). In cache
+    /// mode it counts as a use for eviction, and as a hit or a miss.
     pub fn get(&self, key: &K) -> Result<Option<V>> {
         let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
-        match self.db.get(key_bytes).map_err(Error::from)? {
+        let now = self.now();
+        match self.live_envelope(&key_bytes, now)? {
+            None => {
+                self.note_read(&key_bytes, false, now)?;
+                Ok(None)
+            }
+            Some(envelope) => {
+                let (expire_at, payload) = decode_envelope(&envelope)?;
+                let value = <BincodeCodec<V> as ValueCodec<V>>::decode(payload)?;
+                self.refresh(&key_bytes, expire_at, now)?;
+                self.note_read(&key_bytes, true, now)?;
+                Ok(Some(value))
+            }
+        }
+    }
+
+    /// Retrieve a value without refreshing a sliding deadline.
+    pub fn peek(&self, key: &K) -> Result<Option<V>> {
+        Ok(self.get_with_expiry(key)?.map(|(value, _)| value))
+    }
+
+    /// Push the deadline of an entry just read at `now` out by the sliding idle timeout, if
+    /// sliding expiry is enabled and enough of its window has elapsed.
+    fn refresh(&self, key_bytes: &[u8], expire_at: Option<u64>, now: u64) -> Result<()> {
+        match self.sliding {
+            Some(sliding) if sliding.needs_refresh(expire_at, now) => {
+                let idle = Duration::from_millis(sliding.idle_millis);
+                self.update_expiry_bytes(key_bytes, |expire_at, now| {
+                    expire_at.map(|deadline| deadline.max(deadline_after(now, idle)))
+                })?;
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Retrieve a value together with its expiry deadline (UNIX millis, `None` if it never
+    /// expires), treating an expired entry as absent. Does not refresh a sliding deadline.
+    pub fn get_with_expiry(&self, key: &K) -> Result<Option<(V, Option<u64>)>> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        match self.live_envelope(&key_bytes, self.now())? {
             None => Ok(None),
             Some(envelope) => {
                 let (expire_at, payload) = decode_envelope(&envelope)?;
-                if is_expired(expire_at, self.now()) {
-                    Ok(None)
-                } else {
-                    Ok(Some(<BincodeCodec<V> as ValueCodec<V>>::decode(payload)?))
-                }
+                let value = <BincodeCodec<V> as ValueCodec<V>>::decode(payload)?;
+                Ok(Some((value, expire_at)))
             }
         }
     }
 
+    /// The envelope stored under `key_bytes`, unless it is absent or expired at `now`.
+    fn live_envelope(&self, key_bytes: &[u8], now: u64) -> Result<Option<Vec<u8>>> {
+        match self
+            .db
+            .get_cf(self.data_cf(), key_bytes)
+            .map_err(Error::from)?
+        {
+            Some(envelope) if !is_expired(decode_envelope(&envelope)?.0, now) => Ok(Some(envelope)),
+            _ => Ok(None),
+        }
+    }
+
+    /// The expiry deadline of `key` in UNIX millis, or `None` if the key is absent, expired, or
+    /// never expires. Use [`get_with_expiry`](Self::get_with_expiry) to tell those apart.
+    pub fn expires_at(&self, key: &K) -> Result<Option<u64>> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        match self.live_envelope(&key_bytes, self.now())? {
+            Some(envelope) => Ok(decode_envelope(&envelope)?.0),
+            None => Ok(None),
+        }
+    }
+
+    /// The time `key` has left to live (Redis `PTTL`), or `None` if it is absent, expired, or
+    /// never expires.
+    pub fn ttl(&self, key: &K) -> Result<Option<Duration>> {
+        let now = self.now();
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        match self.live_envelope(&key_bytes, now)? {
+            Some(envelope) => Ok(decode_envelope(&envelope)?
+                .0
+                .map(|deadline| Duration::from_millis(deadline - now))),
+            None => Ok(None),
+        }
+    }
+
+    /// Set `key` to expire `ttl` from now, adding an expiry or replacing the current one (Redis
+    /// `PEXPIRE`). Returns `false`, changing nothing, if the key is absent or already expired.
+    pub fn expire(&self, key: &K, ttl: Duration) -> Result<bool> {
+        let previous = self.update_expiry(key, |_, now| Some(deadline_after(now, ttl)))?;
+        Ok(previous.is_some())
+    }
+
+    /// Extend the deadline of an expiring `key` to at least `ttl` from now; a deadline already
+    /// further out, or a key that never expires, is left as is. Returns `false` if the key is
+    /// absent or already expired.
+    pub fn touch(&self, key: &K, ttl: Duration) -> Result<bool> {
+        let previous = self.update_expiry(key, |expire_at, now| {
+            expire_at.map(|deadline| deadline.max(deadline_after(now, ttl)))
+        })?;
+        Ok(previous.is_some())
+    }
+
+    /// Remove the expiry of `key` so it lives until deleted (Redis `PERSIST`). Returns `true`
+    /// only if the key was live and had an expiry.
+    pub fn persist(&self, key: &K) -> Result<bool> {
+        let previous = self.update_expiry(key, |_, _| None)?;
+        Ok(matches!(previous, Some(Some(_))))
+    }
+
+    /// Replace the expiry of a live `key` with `update(current, now)`, rewriting only the
+    /// envelope header; the payload bytes are kept as stored. Holds the write lock across the
+    /// read and the write, so a concurrent put is never overwritten with the old value.
+    /// Returns the previous expiry, or `None` if the key was absent or expired.
+    fn update_expiry(
+        &self,
+        key: &K,
+        update: impl FnOnce(Option<u64>, u64) -> Option<u64>,
+    ) -> Result<Option<Option<u64>>> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        self.update_expiry_bytes(&key_bytes, update)
+    }
+
+    fn update_expiry_bytes(
+        &self,
+        key_bytes: &[u8],
+        update: impl FnOnce(Option<u64>, u64) -> Option<u64>,
+    ) -> Result<Option<Option<u64>>> {
+        let _guard = self.lock();
+        let now = self.now();
+        let Some(envelope) = self.live_envelope(key_bytes, now)? else {
+            return Ok(None);
+        };
+        let (expire_at, payload) = decode_envelope(&envelope)?;
+        let updated = update(expire_at, now);
+        if updated != expire_at {
+            self.write(key_bytes, expire_at, Some((updated, payload)))?;
+        }
+        Ok(Some(expire_at))
+    }
+
     /// Delete a key-value pair.
     pub fn delete(&self, key: &K) -> Result<()> {
         let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
-        self.db.delete(key_bytes).map_err(Error::from)
+        let _guard = self.lock();
+        let previous = self.stored_expiry(&key_bytes)?;
+        self.write(&key_bytes, previous, None)
     }
 
-    /// Returns `true` if a non-expired value exists for `key`.
+    /// Returns `true` if a non-expired value exists for `key`. Does not refresh a sliding
+    /// deadline.
     pub fn contains(&self, key: &K) -> Result<bool> {
-        Ok(self.get(key)?.is_some())
+        Ok(self.peek(key)?.is_some())
     }
 
     /// Returns `true` if the map has no non-expired entries.
     pub fn is_empty(&self) -> Result<bool> {
-        match self.iter().next() {
+        match self.scan_with((None, None), false, false).next() {
             None => Ok(true),
             Some(Ok(_)) => Ok(false),
             Some(Err(e)) => Err(e),
@@ -248,7 +926,7 @@ where
     /// Number of non-expired entries. **O(n)** — performs a full scan, skipping expired keys.
     pub fn count(&self) -> Result<usize> {
         let mut count = 0;
-        for item in self.iter() {
+        for item in self.scan_with((None, None), false, false) {
             item?;
             count += 1;
         }
@@ -256,18 +934,250 @@ where
     }
 
     /// Iterate non-expired key-value pairs in ascending key order. Expiry is evaluated against
-    /// the clock at the moment iteration begins.
+    /// the clock at the moment iteration begins. With sliding expiry, each yielded entry counts
+    /// as an access, as for [`get`](Self::get).
     pub fn iter(&self) -> TtlIterator<'_, K, V> {
+        self.scan((None, None), false)
+    }
+
+    /// Iterate the non-expired pairs whose keys fall in `range`, in ascending key order. See
+    /// [`RocksMap::range`](crate::RocksMap::range).
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> Result<TtlIterator<'_, K, V>> {
+        Ok(self.scan(range_to_bounds(&range)?, false))
+    }
+
+    /// Like [`range`](Self::range) but yields pairs in descending key order.
+    pub fn range_rev<R: RangeBounds<K>>(&self, range: R) -> Result<TtlIterator<'_, K, V>> {
+        Ok(self.scan(range_to_bounds(&range)?, true))
+    }
+
+    /// Iterate the non-expired pairs whose (byte-string) key begins with `prefix`.
+    pub fn scan_prefix(&self, prefix: &<K as PrefixKey>::Prefix) -> Result<TtlIterator<'_, K, V>>
+    where
+        K: PrefixKey,
+    {
+        let (lower, upper) = prefix_to_bounds(<K as PrefixKey>::encode_prefix(prefix));
+        Ok(self.scan((Some(lower), upper), false))
+    }
+
+    /// Iterate the non-expired pairs whose composite key begins with the leading fields
+    /// `prefix`. See [`RocksMap::scan_prefix_fields`](crate::RocksMap::scan_prefix_fields).
+    pub fn scan_prefix_fields<P: OrderedKey>(&self, prefix: &P) -> Result<TtlIterator<'_, K, V>> {
+        let (lower, upper) = prefix_to_bounds(encode_ordered(prefix)?);
+        Ok(self.scan((Some(lower), upper), false))
+    }
+
+    /// Iterate the non-expired pairs whose composite key begins with the leading fields
+    /// `prefix` and whose next field falls in `range`, in ascending key order. See
+    /// [`RocksMap::range_within_prefix`](crate::RocksMap::range_within_prefix).
+    pub fn range_within_prefix<P, N, R>(
+        &self,
+        prefix: &P,
+        range: R,
+    ) -> Result<TtlIterator<'_, K, V>>
+    where
+        P: OrderedKey,
+        N: OrderedKey,
+        R: RangeBounds<N>,
+    {
+        let bounds = prefix_range_to_bounds(&encode_ordered(prefix)?, &range)?;
+        Ok(self.scan(bounds, false))
+    }
+
+    /// Like [`range_within_prefix`](Self::range_within_prefix) but yields pairs in descending
+    /// key order.
+    pub fn range_within_prefix_rev<P, N, R>(
+        &self,
+        prefix: &P,
+        range: R,
+    ) -> Result<TtlIterator<'_, K, V>>
+    where
+        P: OrderedKey,
+        N: OrderedKey,
+        R: RangeBounds<N>,
+    {
+        let bounds = prefix_range_to_bounds(&encode_ordered(prefix)?, &range)?;
+        Ok(self.scan(bounds, true))
+    }
+
+    /// Iterate the entries within byte `bounds` (bounded by RocksDB itself), in either
+    /// direction, skipping those expired at the moment the scan starts.
+    fn scan(&self, bounds: ByteBounds, reverse: bool) -> TtlIterator<'_, K, V> {
+        self.scan_with(bounds, reverse, true)
+    }
+
+    /// [`scan`](Self::scan), refreshing sliding deadlines of yielded entries only if `access`.
+    fn scan_with(
+        &self,
+        (lower, upper): ByteBounds,
+        reverse: bool,
+        access: bool,
+    ) -> TtlIterator<'_, K, V> {
+        let mut readopts = ReadOptions::default();
+        if let Some(lower) = lower {
+            readopts.set_iterate_lower_bound(lower);
+        }
+        if let Some(upper) = upper {
+            readopts.set_iterate_upper_bound(upper);
+        }
+        let mode = if reverse {
+            IteratorMode::End
+        } else {
+            IteratorMode::Start
+        };
         TtlIterator {
-            inner: self.db.iterator(IteratorMode::Start),
+            inner: self.db.iterator_cf_opt(self.data_cf(), readopts, mode),
             now: self.now(),
+            refresh_from: access.then_some(self),
+        }
+    }
+
+    /// Sequence number of the most recent write. See
+    /// [`RocksMap::latest_sequence_number`](crate::RocksMap::latest_sequence_number).
+    pub fn latest_sequence_number(&self) -> u64 {
+        self.db.latest_sequence_number()
+    }
+
+    /// Typed changes written after sequence number `since`, with TTL envelopes unwrapped: each
+    /// put carries its payload and expiry deadline. Expired entries are reported as written;
+    /// the consumer decides what expiry means downstream.
+    pub fn changes_since(&self, since: u64) -> Result<ChangeStream<'_, K, V>> {
+        ChangeStream::new(&self.db, since, true)
+    }
+
+    /// Delete the entries that have expired, calling the expiry listeners registered on the
+    /// builder with each one, and return how many were removed. Runs in the calling thread; see
+    /// [`spawn_sweeper`](Self::spawn_sweeper) for a background sweeper.
+    ///
+    /// Entries whose key or value no longer decode are left for the compaction filter and
+    /// [`verify`](Self::verify).
+    pub fn sweep_expired(&self) -> Result<usize> {
+        let mut cursor = None;
+        let mut removed = 0;
+        loop {
+            let (swept, done) = self.sweep_batch(&mut cursor, 1024)?;
+            removed += swept;
+            if done {
+                return Ok(removed);
+            }
+        }
+    }
+
+    /// Take up to `limit` expiry-index entries after `cursor` (from the start if `None`) whose
+    /// deadline has passed, removing their rows, and advance the cursor. Returns the number
+    /// removed and whether no expired entries remain past the cursor, in which case the cursor
+    /// is reset.
+    pub(crate) fn sweep_batch(
+        &self,
+        cursor: &mut Option<Vec<u8>>,
+        limit: usize,
+    ) -> Result<(usize, bool)> {
+        let now = self.now();
+        let mut readopts = ReadOptions::default();
+        if let Some(mut after) = cursor.take() {
+            after.push(0); // the smallest key greater than the cursor
+            readopts.set_iterate_lower_bound(after);
+        }
+        // Every index key with a deadline at or before `now` sorts below this bound.
+        readopts.set_iterate_upper_bound(now.saturating_add(1).to_be_bytes());
+        let mut examined = 0;
+        let mut due = Vec::new();
+        for item in self
+            .db
+            .iterator_cf_opt(self.expiry_index(), readopts, IteratorMode::Start)
+            .take(limit)
+        {
+            let (index_key, _) = item.map_err(Error::from)?;
+            examined += 1;
+            due.push(index_key.into_vec());
+        }
+        if examined == limit {
+            *cursor = due.last().cloned();
+        }
+
+        let mut removed = 0;
+        for index_key in due {
+            if let Some((key, value)) = self.remove_expired(&index_key)? {
+                removed += 1;
+                for listener in self.listeners.iter() {
+                    listener(key.clone(), value.clone());
+                }
+            }
+        }
+        Ok((removed, cursor.is_none()))
+    }
+
+    /// Delete the row an expiry-index entry points to if it is still expired, returning it.
+    /// Holds the write lock across the check and the delete, so a row rewritten in between is
+    /// kept. An index entry left behind by a row the compaction filter already dropped is
+    /// removed.
+    fn remove_expired(&self, index_key: &[u8]) -> Result<Option<(K, V)>> {
+        let (deadline, key_bytes) = split_expiry_index_key(index_key)?;
+        let _guard = self.lock();
+        let Some(envelope) = self
+            .db
+            .get_cf(self.data_cf(), key_bytes)
+            .map_err(Error::from)?
+        else {
+            // The compaction filter dropped the row: only its index entry and cache metadata
+            // are left.
+            let mut batch = WriteBatch::default();
+            batch.delete_cf(self.expiry_index(), index_key);
+            let delta = self.stage_cache(&mut batch, key_bytes, None)?;
+            self.db.write(batch).map_err(Error::from)?;
+            self.commit_cache_delta(delta);
+            return Ok(None);
+        };
+        let Ok((expire_at, payload)) = decode_envelope(&envelope) else {
+            return Ok(None);
+        };
+        if expire_at != Some(deadline) || !is_expired(expire_at, self.now()) {
+            return Ok(None);
+        }
+        let key = <OrderedCodec<K> as KeyCodec<K>>::decode(key_bytes);
+        let value = <BincodeCodec<V> as ValueCodec<V>>::decode(payload);
+        let (Ok(key), Ok(value)) = (key, value) else {
+            return Ok(None);
+        };
+        self.write(key_bytes, expire_at, None)?;
+        Ok(Some((key, value)))
+    }
+
+    /// The live keys due to expire before `deadline_unix_millis`, in deadline order, with their
+    /// deadlines. Read from the expiry index, so the cost is that of the keys returned rather
+    /// than of the map. Keys that have already expired are not included.
+    pub fn expiring_before(&self, deadline_unix_millis: u64) -> ExpiringIterator<'_, K> {
+        let mut readopts = ReadOptions::default();
+        readopts.set_iterate_lower_bound(self.now().saturating_add(1).to_be_bytes());
+        readopts.set_iterate_upper_bound(deadline_unix_millis.to_be_bytes());
+        ExpiringIterator {
+            inner: self
+                .db
+                .iterator_cf_opt(self.expiry_index(), readopts, IteratorMode::Start),
             marker: PhantomData,
         }
     }
 
-    /// Trigger a full compaction, which physically removes already-expired entries.
+    /// The live key that expires next and its deadline, or `None` if no live key expires. A
+    /// single seek in the expiry index.
+    pub fn next_expiry(&self) -> Result<Option<(K, u64)>> {
+        self.expiring_before(u64::MAX).next().transpose()
+    }
+
+    /// Trigger a full compaction of this table and its expiry index, which physically removes
+    /// already-expired entries. Their expiry-index entries are left for the sweep until they are
+    /// more than the [expiry index grace](TtlRocksMapBuilder::expiry_index_grace) past due.
     pub fn compact(&self) {
-        self.db.compact_range::<&[u8], &[u8]>(None, None);
+        self.db
+            .compact_range_cf::<&[u8], &[u8]>(self.data_cf(), None, None);
+        self.db
+            .compact_range_cf::<&[u8], &[u8]>(self.expiry_index(), None, None);
+    }
+
+    /// Check that every entry of every TTL table is a valid TTL envelope around a `V`, optionally
+    /// quarantining those that are not. See [`verify`](crate::verify) for what is checked.
+    pub fn verify(&self, mode: VerifyMode) -> Result<VerifyReport> {
+        verify::verify_ttl::<K, V, OrderedCodec<K>>(&self.db, mode, self.now())
     }
 
     /// Flush and fsync the write-ahead log, making prior writes durable against OS/power loss.
@@ -283,20 +1193,25 @@ where
 }
 
 /// Iterator over non-expired entries of a [`TtlRocksMap`].
+///
+/// Like [`RocksMapIterator`](crate::RocksMapIterator), the key range is bounded by RocksDB;
+/// this iterator decodes and skips expired envelopes, in whichever direction it runs, and
+/// refreshes sliding deadlines of the entries it yields.
 pub struct TtlIterator<'a, K, V>
 where
-    K: Serialize + DeserializeOwned + OrderedKey,
-    V: Serialize + DeserializeOwned,
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Serialize + DeserializeOwned + Clone,
 {
     inner: rocksdb::DBIterator<'a>,
     now: u64,
-    marker: PhantomData<(K, V)>,
+    // The map to refresh sliding deadlines in, if this scan counts as an access.
+    refresh_from: Option<&'a TtlRocksMap<K, V>>,
 }
 
 impl<'a, K, V> Iterator for TtlIterator<'a, K, V>
 where
-    K: Serialize + DeserializeOwned + OrderedKey,
-    V: Serialize + DeserializeOwned,
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Serialize + DeserializeOwned + Clone,
 {
     type Item = Result<(K, V)>;
 
@@ -314,6 +1229,9 @@ where
                 }
                 let key = <OrderedCodec<K> as KeyCodec<K>>::decode(&key_bytes)?;
                 let value = <BincodeCodec<V> as ValueCodec<V>>::decode(payload)?;
+                if let Some(map) = self.refresh_from {
+                    map.refresh(&key_bytes, expire_at, self.now)?;
+                }
                 Ok(Some((key, value)))
             })();
 
@@ -326,6 +1244,28 @@ where
     }
 }
 
+/// Iterator over the keys of a [`TtlRocksMap`] in deadline order, from
+/// [`TtlRocksMap::expiring_before`].
+pub struct ExpiringIterator<'a, K> {
+    inner: rocksdb::DBIterator<'a>,
+    marker: PhantomData<K>,
+}
+
+impl<K: OrderedKey> Iterator for ExpiringIterator<'_, K> {
+    type Item = Result<(K, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index_key = match self.inner.next()? {
+            Ok((index_key, _)) => index_key,
+            Err(e) => return Some(Err(Error::from(e))),
+        };
+        Some((|| {
+            let (deadline, key_bytes) = split_expiry_index_key(&index_key)?;
+            Ok((OrderedCodec::<K>::decode(key_bytes)?, deadline))
+        })())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -440,6 +1380,260 @@ mod tests {
         );
     }
 
+    #[test]
+    fn expiry_introspection_and_mutation() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(1_000);
+        let map =
+            TtlRocksMap::<String, String>::open_with_clock(dir.path(), Arc::new(clock.clone()))
+                .unwrap();
+        let key = |k: &str| k.to_string();
+        map.put_with_ttl(key("s"), &"v".to_string(), Duration::from_millis(100))
+            .unwrap();
+        map.put(key("p"), &"forever".to_string()).unwrap();
+
+        assert_eq!(map.expires_at(&key("s")).unwrap(), Some(1_100));
+        assert_eq!(
+            map.get_with_expiry(&key("s")).unwrap(),
+            Some(("v".to_string(), Some(1_100)))
+        );
+        assert_eq!(
+            map.get_with_expiry(&key("p")).unwrap(),
+            Some(("forever".to_string(), None))
+        );
+        assert_eq!(map.get_with_expiry(&key("missing")).unwrap(), None);
+        clock.advance(40);
+        assert_eq!(map.ttl(&key("s")).unwrap(), Some(Duration::from_millis(60)));
+        assert_eq!(map.ttl(&key("p")).unwrap(), None);
+
+        // touch only ever extends, and leaves a persistent key persistent.
+        assert!(map.touch(&key("s"), Duration::from_millis(200)).unwrap());
+        assert_eq!(map.expires_at(&key("s")).unwrap(), Some(1_240));
+        assert!(map.touch(&key("s"), Duration::from_millis(10)).unwrap());
+        assert_eq!(map.expires_at(&key("s")).unwrap(), Some(1_240));
+        assert!(map.touch(&key("p"), Duration::from_millis(10)).unwrap());
+        assert_eq!(map.get_with_expiry(&key("p")).unwrap().unwrap().1, None);
+
+        // expire adds or shortens; persist removes.
+        assert!(map.expire(&key("p"), Duration::from_millis(5)).unwrap());
+        assert_eq!(map.expires_at(&key("p")).unwrap(), Some(1_045));
+        assert!(map.expire(&key("s"), Duration::from_millis(1)).unwrap());
+        assert_eq!(map.expires_at(&key("s")).unwrap(), Some(1_041));
+        assert!(map.persist(&key("s")).unwrap());
+        assert!(!map.persist(&key("s")).unwrap(), "already persistent");
+        clock.advance(10_000);
+        assert_eq!(map.get(&key("s")).unwrap(), Some("v".to_string()));
+
+        // An expired or missing key cannot be revived.
+        assert!(!map.expire(&key("p"), Duration::from_secs(60)).unwrap());
+        assert!(!map.touch(&key("p"), Duration::from_secs(60)).unwrap());
+        assert!(!map.persist(&key("p")).unwrap());
+        assert!(!map
+            .expire(&key("missing"), Duration::from_secs(60))
+            .unwrap());
+        assert_eq!(map.get(&key("p")).unwrap(), None);
+    }
+
+    #[test]
+    fn expiry_rewrites_do_not_lose_concurrent_puts() {
+        let dir = TempDir::new().unwrap();
+        let map = TtlRocksMap::<u8, u32>::open(dir.path()).unwrap();
+        map.put(0, &0).unwrap();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=500 {
+                    map.put(0, &i).unwrap();
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..500 {
+                    map.expire(&0, Duration::from_secs(3600)).unwrap();
+                    map.persist(&0).unwrap();
+                }
+            });
+        });
+        assert_eq!(map.get(&0).unwrap(), Some(500));
+    }
+
+    #[test]
+    fn sliding_expiry_refreshes_on_access() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = TtlRocksMap::<String, u32>::builder(dir.path())
+            .clock(Arc::new(clock.clone()))
+            .sliding_expiry(Duration::from_millis(1_000))
+            .refresh_threshold_percent(20)
+            .open()
+            .unwrap();
+        let key = |k: &str| k.to_string();
+        map.put(key("a"), &1).unwrap();
+        map.put(key("b"), &2).unwrap();
+        map.put_with_ttl(key("long"), &3, Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(map.expires_at(&key("a")).unwrap(), Some(1_000));
+
+        // Within the first 20% of the window a read does not rewrite the deadline.
+        clock.set(150);
+        assert_eq!(map.get(&key("a")).unwrap(), Some(1));
+        assert_eq!(map.expires_at(&key("a")).unwrap(), Some(1_000));
+        clock.set(600);
+        assert_eq!(map.get(&key("a")).unwrap(), Some(1));
+        assert_eq!(map.expires_at(&key("a")).unwrap(), Some(1_600));
+
+        // `peek` and the introspection methods are not accesses.
+        assert_eq!(map.peek(&key("b")).unwrap(), Some(2));
+        assert!(map.contains(&key("b")).unwrap());
+        assert_eq!(map.count().unwrap(), 3);
+        assert_eq!(map.expires_at(&key("b")).unwrap(), Some(1_000));
+
+        // Iteration is an access; a deadline beyond the window is never pulled in.
+        clock.set(700);
+        assert_eq!(map.iter().count(), 3);
+        assert_eq!(map.expires_at(&key("a")).unwrap(), Some(1_600));
+        assert_eq!(map.expires_at(&key("b")).unwrap(), Some(1_700));
+        assert_eq!(map.expires_at(&key("long")).unwrap(), Some(60_000));
+
+        clock.set(1_650);
+        assert_eq!(map.get(&key("a")).unwrap(), None);
+        assert_eq!(map.get(&key("b")).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn expiry_index_tracks_writes() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = TtlRocksMap::<String, u32>::open_with_clock(dir.path(), Arc::new(clock.clone()))
+            .unwrap();
+        let key = |k: &str| k.to_string();
+        map.put_with_expiry(key("c"), &3, 300).unwrap();
+        map.put_with_expiry(key("a"), &1, 100).unwrap();
+        map.put_with_expiry(key("b"), &2, 200).unwrap();
+        map.put(key("forever"), &0).unwrap();
+        assert_eq!(map.next_expiry().unwrap(), Some((key("a"), 100)));
+
+        // Rewrites move the index entry; deletes and persist remove it.
+        map.put_with_expiry(key("a"), &1, 400).unwrap();
+        map.expire(&key("forever"), Duration::from_millis(250))
+            .unwrap();
+        map.delete(&key("b")).unwrap();
+        map.persist(&key("c")).unwrap();
+        let due: Vec<(String, u64)> = map.expiring_before(1_000).map(|r| r.unwrap()).collect();
+        assert_eq!(due, vec![(key("forever"), 250), (key("a"), 400)]);
+        assert_eq!(map.expiring_before(400).count(), 1);
+        let index = map.db().cf_handle(meta::TTL_EXPIRY_CF).unwrap();
+        assert_eq!(map.db().iterator_cf(index, IteratorMode::Start).count(), 2);
+
+        // Expired keys drop out of the queries; the sweeper consumes their index entries.
+        clock.set(250);
+        assert_eq!(map.next_expiry().unwrap(), Some((key("a"), 400)));
+        assert_eq!(map.sweep_expired().unwrap(), 1);
+        assert_eq!(map.db().iterator_cf(index, IteratorMode::Start).count(), 1);
+        clock.set(400);
+        assert_eq!(map.next_expiry().unwrap(), None);
+    }
+
+    #[test]
+    fn compacting_the_expiry_index_keeps_rows_it_still_points_to_sweepable() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let (sender, events) = std::sync::mpsc::channel();
+        let map = TtlRocksMap::<u32, u32>::builder(dir.path())
+            .clock(Arc::new(clock.clone()))
+            .notify_expired(sender)
+            .open()
+            .unwrap();
+        for i in 0..3 {
+            map.put_with_expiry(i, &i, 50).unwrap();
+        }
+        map.db().flush().unwrap();
+        clock.set(50);
+
+        // The data column family is compacted on its own schedule: the index alone goes first.
+        let index = map.db().cf_handle(meta::TTL_EXPIRY_CF).unwrap();
+        map.db().flush_cf(index).unwrap();
+        map.db().compact_range_cf::<&[u8], &[u8]>(index, None, None);
+        assert_eq!(map.ttl_stats().unwrap().expired_entries, 3);
+        assert_eq!(map.sweep_expired().unwrap(), 3);
+        assert_eq!(events.try_iter().count(), 3);
+        assert_eq!(map.db().iterator(IteratorMode::Start).count(), 0);
+    }
+
+    #[test]
+    fn compaction_empties_the_expiry_index_without_a_sweeper() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = TtlRocksMap::<u32, u32>::builder(dir.path())
+            .clock(Arc::new(clock.clone()))
+            .expiry_index_grace(Duration::from_millis(1_000))
+            .table("sessions", None)
+            .open()
+            .unwrap();
+        let sessions = map.table("sessions").unwrap();
+        for i in 0..3 {
+            map.put_with_expiry(i, &i, 50).unwrap();
+            sessions.put_with_expiry(i, &i, 50).unwrap();
+        }
+        map.db().flush().unwrap();
+
+        let indexed = || {
+            [
+                meta::TTL_EXPIRY_CF.to_string(),
+                meta::ttl_expiry_cf("sessions"),
+            ]
+            .map(|name| {
+                let index = map.db().cf_handle(&name).unwrap();
+                map.db().iterator_cf(index, IteratorMode::Start).count()
+            })
+        };
+
+        // Within the grace period the entries are left for a sweep.
+        clock.set(500);
+        map.compact();
+        sessions.compact();
+        assert_eq!(indexed(), [3, 3]);
+
+        clock.set(1_051);
+        map.compact();
+        sessions.compact();
+        assert_eq!(indexed(), [0, 0]);
+    }
+
+    #[test]
+    fn expiry_index_is_built_for_existing_rows_and_compacted() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        {
+            let map = TtlRocksMap::<u32, u32>::open_with_clock(dir.path(), Arc::new(clock.clone()))
+                .unwrap();
+            map.put_with_expiry(1, &1, 100).unwrap();
+            map.put_with_expiry(2, &2, 50).unwrap();
+            map.put(3, &3).unwrap();
+        }
+        // Simulate a database written before the index existed.
+        {
+            let mut db = meta::open_managed(dir.path(), false).unwrap();
+            db.drop_cf(meta::TTL_EXPIRY_CF).unwrap();
+            let meta_cf = db.cf_handle(meta::META_CF).unwrap();
+            db.delete_cf(meta_cf, b"ttl_expiry_indexed").unwrap();
+        }
+        let map =
+            TtlRocksMap::<u32, u32>::open_with_clock(dir.path(), Arc::new(clock.clone())).unwrap();
+        assert!(crate::inspect(dir.path()).unwrap().ttl_expiry_index);
+        let due: Vec<(u32, u64)> = map.expiring_before(u64::MAX).map(|r| r.unwrap()).collect();
+        assert_eq!(due, vec![(2, 50), (1, 100)]);
+
+        // Compaction drops expired rows but keeps their index entries, which the sweep consumes.
+        clock.set(60);
+        map.compact();
+        let index = map.db().cf_handle(meta::TTL_EXPIRY_CF).unwrap();
+        assert_eq!(map.db().iterator_cf(index, IteratorMode::Start).count(), 2);
+        let row = <OrderedCodec<u32> as KeyCodec<u32>>::encode(&2).unwrap();
+        assert_eq!(map.db().get(row).unwrap(), None);
+        assert_eq!(map.next_expiry().unwrap(), Some((1, 100)));
+        assert_eq!(map.sweep_expired().unwrap(), 0);
+        assert_eq!(map.db().iterator_cf(index, IteratorMode::Start).count(), 1);
+    }
+
     #[test]
     fn default_ttl_applies_to_plain_put() {
         let dir = TempDir::new().unwrap();
@@ -457,6 +1651,94 @@ mod tests {
         assert_eq!(map.get(&"k".to_string()).unwrap(), None);
     }
 
+    #[test]
+    fn range_within_prefix_skips_expired_in_both_directions() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map =
+            TtlRocksMap::<(u32, u64), String>::open_with_clock(dir.path(), Arc::new(clock.clone()))
+                .unwrap();
+        for ts in [5u64, 10, 15, 20] {
+            map.put((1, ts), &format!("a{ts}")).unwrap();
+        }
+        map.put_with_ttl((1, 12), &"gone".to_string(), Duration::from_millis(1))
+            .unwrap();
+        map.put((2, 10), &"other".to_string()).unwrap();
+        clock.advance(5);
+
+        let keys = |iter: TtlIterator<'_, (u32, u64), String>| -> Vec<u64> {
+            iter.map(|r| r.unwrap().0 .1).collect()
+        };
+        assert_eq!(
+            keys(map.range_within_prefix(&(1u32,), 10u64..=15).unwrap()),
+            vec![10, 15]
+        );
+        assert_eq!(
+            keys(map.range_within_prefix_rev(&(1u32,), 6u64..).unwrap()),
+            vec![20, 15, 10]
+        );
+    }
+
+    #[test]
+    fn ordered_scans_skip_expired_against_one_now() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map = TtlRocksMap::<String, u32>::open_with_clock(dir.path(), Arc::new(clock.clone()))
+            .unwrap();
+        for (i, name) in ["s:a", "s:b", "s:c", "s:d", "t:a"].iter().enumerate() {
+            map.put_with_ttl(name.to_string(), &(i as u32), Duration::from_millis(100))
+                .unwrap();
+        }
+        map.put_with_ttl("s:b".to_string(), &9, Duration::from_millis(10))
+            .unwrap();
+        clock.advance(10);
+
+        let names = |iter: TtlIterator<'_, String, u32>| -> Vec<String> {
+            iter.map(|r| r.unwrap().0).collect()
+        };
+        assert_eq!(
+            names(map.range("s:a".to_string()..="s:c".to_string()).unwrap()),
+            vec!["s:a", "s:c"]
+        );
+        assert_eq!(
+            names(map.range_rev(.."t".to_string()).unwrap()),
+            vec!["s:d", "s:c", "s:a"]
+        );
+        assert_eq!(
+            names(map.scan_prefix("s:").unwrap()),
+            vec!["s:a", "s:c", "s:d"]
+        );
+
+        // Entries that expire mid-scan are still yielded: `now` is fixed when the scan starts.
+        let mut scan = map.scan_prefix("s:").unwrap();
+        assert_eq!(scan.next().unwrap().unwrap().0, "s:a");
+        clock.advance(1_000);
+        assert_eq!(names(scan), vec!["s:c", "s:d"]);
+        assert!(map.scan_prefix("s:").unwrap().next().is_none());
+    }
+
+    #[test]
+    fn scan_prefix_fields_on_composite_keys() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let map =
+            TtlRocksMap::<(u32, u64), ()>::open_with_clock(dir.path(), Arc::new(clock.clone()))
+                .unwrap();
+        for bucket in [1u32, 2] {
+            for ts in [1u64, 2, 3] {
+                map.put_with_ttl((bucket, ts), &(), Duration::from_millis(ts * 10))
+                    .unwrap();
+            }
+        }
+        clock.advance(15);
+        let keys: Vec<(u32, u64)> = map
+            .scan_prefix_fields(&(2u32,))
+            .unwrap()
+            .map(|r| r.unwrap().0)
+            .collect();
+        assert_eq!(keys, vec![(2, 2), (2, 3)]);
+    }
+
     #[test]
     fn opening_ttl_db_as_plain_map_fails() {
         let dir = TempDir::new().unwrap();
@@ -478,4 +1760,205 @@ mod tests {
         let err = TtlRocksMap::<String, String>::open(dir.path());
         assert!(matches!(err, Err(Error::FormatMismatch(_))));
     }
+
+    #[test]
+    fn ttl_tables_keep_their_own_default_ttl_and_filter() {
+        let dir = TempDir::new().unwrap();
+        let clock = ManualClock::new(0);
+        let mut options = Options::default();
+        options.set_max_background_jobs(2);
+        let builder = || {
+            TtlRocksMap::<String, String>::builder(dir.path())
+                .clock(Arc::new(clock.clone()))
+                .options(options.clone())
+        };
+        let map = builder()
+            .table("sessions", Some(Duration::from_millis(30)))
+            .table("tokens", Some(Duration::from_millis(1000)))
+            .open()
+            .unwrap();
+        let sessions = map.table("sessions").unwrap();
+        let tokens = map.table("tokens").unwrap();
+        assert!(matches!(
+            map.table("nope"),
+            Err(Error::ColumnFamilyNotFound(_))
+        ));
+        let key = |k: &str| k.to_string();
+
+        map.put(key("k"), &key("main")).unwrap();
+        sessions.put(key("k"), &key("session")).unwrap();
+        tokens.put(key("k"), &key("token")).unwrap();
+        assert_eq!(map.expires_at(&key("k")).unwrap(), None);
+        assert_eq!(sessions.expires_at(&key("k")).unwrap(), Some(30));
+        assert_eq!(tokens.expires_at(&key("k")).unwrap(), Some(1000));
+        assert_eq!(tokens.next_expiry().unwrap(), Some((key("k"), 1000)));
+
+        clock.set(30);
+        assert_eq!(sessions.get(&key("k")).unwrap(), None);
+        assert_eq!(tokens.get(&key("k")).unwrap(), Some(key("token")));
+        assert_eq!(map.get(&key("k")).unwrap(), Some(key("main")));
+        assert_eq!(sessions.count().unwrap(), 0);
+
+        // The table's own compaction filter drops its expired rows.
+        let sessions_cf = map.db().cf_handle("sessions").unwrap();
+        map.db().flush_cf(sessions_cf).unwrap();
+        sessions.compact();
+        assert_eq!(
+            map.db()
+                .iterator_cf(sessions_cf, IteratorMode::Start)
+                .count(),
+            0
+        );
+        assert!(sessions.verify(VerifyMode::Check).unwrap().is_clean());
+        drop((sessions, tokens, map));
+
+        // Tables stay recorded, and reachable without being declared again.
+        let info = crate::inspect(dir.path()).unwrap();
+        assert_eq!(info.ttl_tables, vec![key("sessions"), key("tokens")]);
+        let map = builder().open().unwrap();
+        let tokens = map.table("tokens").unwrap();
+        assert_eq!(tokens.get(&key("k")).unwrap(), Some(key("token")));
+        tokens.put(key("later"), &key("kept")).unwrap();
+        assert_eq!(tokens.expires_at(&key("later")).unwrap(), None);
+    }
+
+    #[test]
+    fn ttl_table_refuses_a_column_family_holding_other_data() {
+        let dir = TempDir::new().unwrap();
+        drop(TtlRocksMap::<String, String>::open(dir.path()).unwrap());
+        {
+            let names = DB::list_cf(&Options::default(), dir.path()).unwrap();
+            let mut db = DB::open_cf(&Options::default(), dir.path(), names).unwrap();
+            db.create_cf("raw", &Options::default()).unwrap();
+            db.create_cf("empty", &Options::default()).unwrap();
+            db.put_cf(db.cf_handle("raw").unwrap(), b"k", b"v").unwrap();
+        }
+
+        let open = |table: &str| {
+            TtlRocksMap::<String, String>::builder(dir.path())
+                .table(table, None)
+                .open()
+        };
+        assert!(matches!(open("raw"), Err(Error::FormatMismatch(_))));
+        assert!(matches!(open(meta::TTL_EXPIRY_CF), Err(Error::Other(_))));
+        let map = open("empty").unwrap();
+        let empty = map.table("empty").unwrap();
+        empty.put("k".to_string(), &"v".to_string()).unwrap();
+        assert_eq!(empty.get(&"k".to_string()).unwrap(), Some("v".to_string()));
+    }
+
+    #[test]
+    fn guarded_clock_keeps_expired_entries_expired_across_backward_steps() {
+        use crate::clock::{ClockSkew, MonotonicGuardClock};
+
+        let dir = TempDir::new().unwrap();
+        let wall = ManualClock::new(1_000);
+        let skews = Arc::new(Mutex::new(Vec::new()));
+        let guarded = |wall: &ManualClock| {
+            let log = Arc::clone(&skews);
+            Arc::new(
+                MonotonicGuardClock::new(Arc::new(wall.clone()))
+                    .on_skew(move |skew| log.lock().unwrap().push(skew)),
+            )
+        };
+        let key = |k: &str| k.to_string();
+        let map = TtlRocksMap::<String, u32>::open_with_clock(dir.path(), guarded(&wall)).unwrap();
+        map.put_with_ttl(key("short"), &1, Duration::from_millis(100))
+            .unwrap();
+        map.put_with_ttl(key("long"), &2, Duration::from_secs(10))
+            .unwrap();
+        wall.set(1_200);
+        map.put(key("forever"), &3).unwrap();
+        map.db().flush().unwrap();
+
+        // Stepping back does not resurrect the expired entry, on the read path or in compaction.
+        wall.set(0);
+        assert_eq!(map.get(&key("short")).unwrap(), None);
+        map.compact();
+        assert_eq!(map.db().iterator(IteratorMode::Start).count(), 2);
+        drop(map);
+
+        // Nor does a restart with the clock still behind: the persisted mark holds.
+        let map = TtlRocksMap::<String, u32>::open_with_clock(dir.path(), guarded(&wall)).unwrap();
+        map.put_with_ttl(key("fresh"), &4, Duration::from_millis(100))
+            .unwrap();
+        assert_eq!(map.expires_at(&key("fresh")).unwrap(), Some(1_300));
+        assert_eq!(map.get(&key("long")).unwrap(), Some(2));
+        assert_eq!(
+            *skews.lock().unwrap(),
+            vec![
+                ClockSkew::Backward { by_millis: 1_200 },
+                ClockSkew::Backward { by_millis: 1_200 },
+            ]
+        );
+    }
+
+    #[test]
+    fn forward_steps_within_tolerance_are_not_reported() {
+        use crate::clock::MonotonicGuardClock;
+
+        let dir = TempDir::new().unwrap();
+        let wall = ManualClock::new(1_000);
+        let skews = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&skews);
+        let guard = MonotonicGuardClock::new(Arc::new(wall.clone()))
+            .forward_tolerance(Duration::from_secs(10))
+            .on_skew(move |skew| log.lock().unwrap().push(skew));
+        let map = TtlRocksMap::<String, u32>::open_with_clock(dir.path(), Arc::new(guard)).unwrap();
+        map.put_with_ttl("k".to_string(), &1, Duration::from_secs(60))
+            .unwrap();
+        map.db().flush().unwrap();
+
+        wall.set(9_000);
+        // Concurrent readers each compare their reading with a matching earlier pair.
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..1_000 {
+                        map.get(&"k".to_string()).unwrap();
+                    }
+                });
+            }
+        });
+        map.compact();
+        assert_eq!(map.get(&"k".to_string()).unwrap(), Some(1));
+        assert_eq!(map.db().iterator(IteratorMode::Start).count(), 1);
+        assert!(skews.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn forward_steps_are_reported_or_ignored() {
+        use crate::clock::{ClockSkew, HybridClock, MonotonicGuardClock};
+
+        // The guard follows a forward step, but reports it.
+        let dir = TempDir::new().unwrap();
+        let wall = ManualClock::new(1_000);
+        let skews = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&skews);
+        let guard = MonotonicGuardClock::new(Arc::new(wall.clone()))
+            .on_skew(move |skew| log.lock().unwrap().push(skew));
+        let map = TtlRocksMap::<String, u32>::open_with_clock(dir.path(), Arc::new(guard)).unwrap();
+        map.put_with_ttl("k".to_string(), &1, Duration::from_secs(60))
+            .unwrap();
+        wall.set(3_601_000);
+        assert_eq!(map.get(&"k".to_string()).unwrap(), None);
+        assert!(matches!(
+            skews.lock().unwrap()[..],
+            [ClockSkew::Forward { by_millis }] if by_millis >= 3_599_000
+        ));
+
+        // A hybrid clock ignores it: the entry stays live for reads and for the compaction filter.
+        let dir = TempDir::new().unwrap();
+        let wall = ManualClock::new(1_000);
+        let hybrid = HybridClock::anchored_to(Arc::new(wall.clone()));
+        let map =
+            TtlRocksMap::<String, u32>::open_with_clock(dir.path(), Arc::new(hybrid)).unwrap();
+        map.put_with_ttl("k".to_string(), &1, Duration::from_secs(60))
+            .unwrap();
+        map.db().flush().unwrap();
+        wall.set(3_601_000);
+        assert_eq!(map.get(&"k".to_string()).unwrap(), Some(1));
+        map.compact();
+        assert_eq!(map.db().iterator(IteratorMode::Start).count(), 1);
+    }
 }
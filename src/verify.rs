@@ -0,0 +1,862 @@
+//! Integrity checking ("fsck") for rocksmap databases of every kind.
+//!
+//! [`verify`] (by path) and the typed `verify` methods on [`RocksMap`](crate::RocksMap),
+//! [`TtlRocksMap`](crate::TtlRocksMap) and [`IndexedRocksMap`](crate::IndexedRocksMap) walk
+//! every column family and report rows that no longer decode, instead of letting a scan trip
+//! over them later:
+//!
+//! - each key decodes with the recorded key codec, and each value decodes as `V` (strictly: a
+//!   value with trailing bytes is reported, since it was written as some other type);
+//! - TTL values parse as TTL envelopes before their payload is decoded, and each TTL table's
+//!   expiry index holds exactly one entry per expiring row, at its deadline (entries already
+//!   past their deadline are left to the sweep and the compaction filter);
+//! - versioned values carry a version header no newer than the recorded schema version (only
+//!   rows at the current version are decoded as `V`; older rows need their migrations);
+//! - index entries point at live rows, and (typed check only, which knows the extractors) every
+//!   row has exactly the index entries it should, with unique indexes holding no duplicates.
+//!
+//! With [`VerifyMode::Quarantine`], undecodable rows and dangling index entries are moved out of
+//! their column family into the metadata column family, where [`quarantined`] lists them; a
+//! quarantined TTL row takes its expiry-index entry with it. Index entries that are *missing*
+//! are only reported: `IndexedRocksMap::rebuild_all` restores them.
+
+use crate::clock::{Clock, SystemClock};
+use crate::codec::KeyCodec;
+use crate::error::{Error, Result};
+use crate::index;
+pub use crate::meta::QuarantinedRow;
+use crate::meta::{self, KvStore, MapKind};
+use crate::ttl::{decode_envelope, expiry_index_key, split_expiry_index_key};
+use crate::versioned::decode_version_envelope;
+use bincode::Options as _;
+use rocksdb::{ColumnFamilyDescriptor, Options, DB};
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+/// How values are wrapped in a column family being checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RowLayout {
+    /// The bare encoded value.
+    Bare,
+    /// A TTL envelope around the value.
+    Ttl,
+    /// A version header; rows at the given (current) version hold a `V`.
+    Versioned(u16),
+}
+
+/// Whether a verification pass only reports problems or also quarantines bad rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VerifyMode {
+    /// Report problems without modifying the database.
+    Check,
+    /// Report problems, and move undecodable rows and dangling index entries into quarantine.
+    Quarantine,
+}
+
+/// What is wrong with a row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProblemKind {
+    /// The database metadata disagrees with how it is being verified (e.g. another key codec).
+    MetadataMismatch(String),
+    /// The key does not decode with the recorded key codec.
+    UndecodableKey(String),
+    /// The value does not decode as `V`.
+    UndecodableValue(String),
+    /// The value of a TTL map is not a valid TTL envelope.
+    InvalidTtlEnvelope(String),
+    /// The value of a versioned map has no valid version header, or one newer than the
+    /// database's schema version.
+    InvalidVersionEnvelope(String),
+    /// An index entry that refers to no live row (or to the wrong one).
+    DanglingIndexEntry,
+    /// A row lacking the entry it should have in `index`.
+    MissingIndexEntry {
+        /// Index name.
+        index: String,
+    },
+    /// A row whose secondary key in unique `index` is already taken by another row.
+    DuplicateUniqueKey {
+        /// Index name.
+        index: String,
+    },
+}
+
+/// One problem found by a verification pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyProblem {
+    /// The column family holding the row (or index entry).
+    pub column_family: String,
+    /// The row's raw key bytes (empty for database-wide problems).
+    pub key: Vec<u8>,
+    /// What is wrong.
+    pub kind: ProblemKind,
+    /// Whether the row was moved into quarantine.
+    pub quarantined: bool,
+}
+
+/// The outcome of a verification pass.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    /// The recorded map kind.
+    pub kind: MapKind,
+    /// Column families that were checked.
+    pub column_families: Vec<String>,
+    /// Rows (including index entries) examined.
+    pub rows_checked: u64,
+    /// Every problem found, in scan order.
+    pub problems: Vec<VerifyProblem>,
+}
+
+impl VerifyReport {
+    /// `true` if no problems were found.
+    pub fn is_clean(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// Number of rows moved into quarantine.
+    pub fn quarantined(&self) -> usize {
+        self.problems.iter().filter(|p| p.quarantined).count()
+    }
+}
+
+/// An index entry a data row should have: `entry -> value` in the index column family, produced
+/// by the row with primary key `pk`.
+pub(crate) struct ExpectedEntry {
+    pub(crate) entry: Vec<u8>,
+    pub(crate) value: Vec<u8>,
+    pub(crate) pk: Vec<u8>,
+}
+
+/// Decode `bytes` as `V`, rejecting trailing bytes (which plain reads would silently ignore).
+pub(crate) fn decode_value_strict<V: DeserializeOwned>(bytes: &[u8]) -> Result<V> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(bytes)
+        .map_err(|e| Error::Deserialization(e.to_string()))
+}
+
+/// Accumulates a [`VerifyReport`] over one database, quarantining rows if asked to.
+pub(crate) struct Verifier<'a, S: KvStore> {
+    store: &'a S,
+    mode: VerifyMode,
+    report: VerifyReport,
+}
+
+impl<'a, S: KvStore> Verifier<'a, S> {
+    pub(crate) fn new(store: &'a S, mode: VerifyMode, kind: MapKind) -> Self {
+        Verifier {
+            store,
+            mode,
+            report: VerifyReport {
+                kind,
+                column_families: Vec::new(),
+                rows_checked: 0,
+                problems: Vec::new(),
+            },
+        }
+    }
+
+    pub(crate) fn finish(self) -> VerifyReport {
+        self.report
+    }
+
+    /// Record a problem; `quarantine` is the row's value if it should be quarantined.
+    pub(crate) fn problem(
+        &mut self,
+        cf_name: &str,
+        key: &[u8],
+        kind: ProblemKind,
+        quarantine: Option<&[u8]>,
+    ) -> Result<()> {
+        self.problem_with(cf_name, key, kind, quarantine, &[])
+    }
+
+    /// [`problem`](Self::problem), also deleting the `(column family, key)` rows in
+    /// `also_delete` in the same write if the row is quarantined.
+    fn problem_with(
+        &mut self,
+        cf_name: &str,
+        key: &[u8],
+        kind: ProblemKind,
+        quarantine: Option<&[u8]>,
+        also_delete: &[(&str, &[u8])],
+    ) -> Result<()> {
+        let quarantined = match quarantine {
+            Some(value) if self.mode == VerifyMode::Quarantine => {
+                meta::quarantine_row(self.store, cf_name, key, value, also_delete)?;
+                true
+            }
+            _ => false,
+        };
+        self.report.problems.push(VerifyProblem {
+            column_family: cf_name.to_string(),
+            key: key.to_vec(),
+            kind,
+            quarantined,
+        });
+        Ok(())
+    }
+
+    /// Check the recorded key codec against `KC`. Returns `false` (after recording the problem)
+    /// on a mismatch, as no key would then decode.
+    pub(crate) fn check_key_codec<K, KC: KeyCodec<K>>(&mut self) -> Result<bool> {
+        match meta::read_key_codec(self.store)? {
+            Some(id) if id != KC::ID => {
+                let msg = format!(
+                    "database uses key codec id {id}, verified with id {}",
+                    KC::ID
+                );
+                self.problem(meta::META_CF, &[], ProblemKind::MetadataMismatch(msg), None)?;
+                Ok(false)
+            }
+            _ => Ok(true),
+        }
+    }
+
+    /// The rows of `cf_name`. Iteration reads from an implicit snapshot, so quarantining rows
+    /// while scanning is safe.
+    fn scan(&mut self, cf_name: &str) -> Result<meta::RawScan<'a>> {
+        let store = self.store;
+        let cf = store
+            .cf(cf_name)
+            .ok_or_else(|| Error::ColumnFamilyNotFound(cf_name.to_string()))?;
+        self.report.column_families.push(cf_name.to_string());
+        Ok(store.scan_raw(cf, &[]))
+    }
+
+    /// Check that every row of `cf_name` decodes as `K -> V` (inside the envelope `layout`
+    /// describes), passing each row that does to `on_row` along with its raw key.
+    pub(crate) fn check_rows<K, V, KC>(
+        &mut self,
+        cf_name: &str,
+        layout: RowLayout,
+        mut on_row: impl FnMut(&[u8], V) -> Result<()>,
+    ) -> Result<()>
+    where
+        KC: KeyCodec<K>,
+        V: DeserializeOwned,
+    {
+        let index_cf = meta::ttl_expiry_cf(cf_name);
+        for item in self.scan(cf_name)? {
+            let (key, value) = item?;
+            self.report.rows_checked += 1;
+            // A quarantined TTL row takes its expiry-index entry with it.
+            let index_entry = match (layout, decode_envelope(&value)) {
+                (RowLayout::Ttl, Ok((Some(deadline), _))) => Some(expiry_index_key(deadline, &key)),
+                _ => None,
+            };
+            let also_delete: Vec<(&str, &[u8])> = index_entry
+                .iter()
+                .map(|entry| (index_cf.as_str(), entry.as_slice()))
+                .collect();
+            if let Err(e) = KC::decode(&key) {
+                let kind = ProblemKind::UndecodableKey(e.to_string());
+                self.problem_with(cf_name, &key, kind, Some(&value), &also_delete)?;
+                continue;
+            }
+            let payload = match layout {
+                RowLayout::Bare => &value[..],
+                RowLayout::Ttl => match decode_envelope(&value) {
+                    Ok((_, payload)) => payload,
+                    Err(e) => {
+                        let kind = ProblemKind::InvalidTtlEnvelope(e.to_string());
+                        self.problem(cf_name, &key, kind, Some(&value))?;
+                        continue;
+                    }
+                },
+                RowLayout::Versioned(current) => match decode_version_envelope(&value) {
+                    Ok((version, payload)) if version == current => payload,
+                    Ok((version, _)) if version < current => continue,
+                    Ok((version, _)) => {
+                        let msg = format!("row at version {version} is newer than {current}");
+                        let kind = ProblemKind::InvalidVersionEnvelope(msg);
+                        self.problem(cf_name, &key, kind, Some(&value))?;
+                        continue;
+                    }
+                    Err(e) => {
+                        let kind = ProblemKind::InvalidVersionEnvelope(e.to_string());
+                        self.problem(cf_name, &key, kind, Some(&value))?;
+                        continue;
+                    }
+                },
+            };
+            match decode_value_strict::<V>(payload) {
+                Ok(decoded) => on_row(&key, decoded)?,
+                Err(e) => {
+                    let kind = ProblemKind::UndecodableValue(e.to_string());
+                    self.problem_with(cf_name, &key, kind, Some(&value), &also_delete)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the expiry index of TTL table `table` both ways against its rows: each entry must
+    /// name a row expiring at the entry's deadline, and each expiring row needs its entry.
+    /// Entries whose deadline is at or before `now` are not checked, as the row they name may
+    /// be gone or rewritten already, leaving them for the sweep and the compaction filter.
+    pub(crate) fn check_expiry_index(&mut self, table: &str, now: u64) -> Result<()> {
+        let index_cf = meta::ttl_expiry_cf(table);
+        let store = self.store;
+        let (Some(data), Some(index)) = (store.cf(table), store.cf(&index_cf)) else {
+            let msg = format!("TTL table `{table}` has no `{index_cf}` column family");
+            return self.problem(meta::META_CF, &[], ProblemKind::MetadataMismatch(msg), None);
+        };
+        for item in self.scan(&index_cf)? {
+            let (entry, value) = item?;
+            self.report.rows_checked += 1;
+            let ok = match split_expiry_index_key(&entry) {
+                Ok((deadline, _)) if deadline <= now => true,
+                Ok((deadline, key)) => match store.get_raw(data, key)? {
+                    Some(row) => {
+                        matches!(decode_envelope(&row), Ok((Some(at), _)) if at == deadline)
+                    }
+                    None => false,
+                },
+                Err(_) => false,
+            };
+            if !ok {
+                let kind = ProblemKind::DanglingIndexEntry;
+                self.problem(&index_cf, &entry, kind, Some(&value))?;
+            }
+        }
+
+        for item in store.scan_raw(data, &[]) {
+            let (key, row) = item?;
+            let Ok((Some(deadline), _)) = decode_envelope(&row) else {
+                continue;
+            };
+            if store
+                .get_raw(index, &expiry_index_key(deadline, &key))?
+                .is_none()
+            {
+                let kind = ProblemKind::MissingIndexEntry {
+                    index: index_cf.clone(),
+                };
+                self.problem(table, &key, kind, None)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check index `name` against the data rows without knowing its extractor: a unique entry
+    /// (`sk -> pk`) must name a live row, and so must the primary key of a non-unique entry
+    /// (`sk ++ pk -> len(sk)`). `unique` lists the unique indexes; `None` means the entries
+    /// predate that record, and cannot be split until the map is opened and rebuilds them.
+    pub(crate) fn check_index_structure<K, KC: KeyCodec<K>>(
+        &mut self,
+        name: &str,
+        unique: Option<&[String]>,
+        data_cf: &str,
+    ) -> Result<()> {
+        let cf_name = format!("__idx_{name}");
+        if self.store.cf(&cf_name).is_none() {
+            let msg = format!("declared index `{name}` has no `{cf_name}` column family");
+            return self.problem(meta::META_CF, &[], ProblemKind::MetadataMismatch(msg), None);
+        }
+        let Some(unique) = unique else {
+            let msg = format!(
+                "entries of index `{name}` predate the unique-index record; \
+                 open the map once to rebuild them"
+            );
+            return self.problem(meta::META_CF, &[], ProblemKind::MetadataMismatch(msg), None);
+        };
+        let unique = unique.iter().any(|u| u == name);
+        let store = self.store;
+        let data = store
+            .cf(data_cf)
+            .ok_or_else(|| Error::ColumnFamilyNotFound(data_cf.to_string()))?;
+        let live = |pk: &[u8]| -> Result<bool> {
+            Ok(KC::decode(pk).is_ok() && store.get_raw(data, pk)?.is_some())
+        };
+        for item in self.scan(&cf_name)? {
+            let (entry, value) = item?;
+            self.report.rows_checked += 1;
+            let pk = if unique {
+                Some(&value[..])
+            } else {
+                index::entry_primary_key(&entry, &value)
+            };
+            let ok = match pk {
+                Some(pk) => live(pk)?,
+                None => false,
+            };
+            if !ok {
+                self.problem(
+                    &cf_name,
+                    &entry,
+                    ProblemKind::DanglingIndexEntry,
+                    Some(&value),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check index `name` both ways against `expected`, the entries the data rows produce under
+    /// its extractor.
+    pub(crate) fn check_index_entries(
+        &mut self,
+        name: &str,
+        unique: bool,
+        expected: Vec<ExpectedEntry>,
+    ) -> Result<()> {
+        let cf_name = format!("__idx_{name}");
+        let data_cf = "default";
+        let mut wanted = std::collections::HashMap::with_capacity(expected.len());
+        for ExpectedEntry { entry, value, pk } in expected {
+            if unique && wanted.contains_key(&entry) {
+                let kind = ProblemKind::DuplicateUniqueKey {
+                    index: name.to_string(),
+                };
+                self.problem(data_cf, &pk, kind, None)?;
+                continue;
+            }
+            wanted.insert(entry, (value, pk));
+        }
+
+        for item in self.scan(&cf_name)? {
+            let (entry, value) = item?;
+            self.report.rows_checked += 1;
+            match wanted.get(&entry[..]) {
+                Some((want, _)) if want[..] == value[..] => {
+                    wanted.remove(&entry[..]);
+                }
+                _ => self.problem(
+                    &cf_name,
+                    &entry,
+                    ProblemKind::DanglingIndexEntry,
+                    Some(&value),
+                )?,
+            }
+        }
+
+        let mut missing: Vec<Vec<u8>> = wanted.into_values().map(|(_, pk)| pk).collect();
+        missing.sort();
+        for pk in missing {
+            let kind = ProblemKind::MissingIndexEntry {
+                index: name.to_string(),
+            };
+            self.problem(data_cf, &pk, kind, None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Verify the database at `path` as a map of `K -> V` with key codec `KC`, whatever its kind.
+///
+/// [`VerifyMode::Check`] opens the database read-only; [`VerifyMode::Quarantine`] needs it
+/// opened for writing, so no other process may have it open. Index entries are checked
+/// structurally (each must point at a live row); the typed
+/// [`IndexedRocksMap::verify`](crate::IndexedRocksMap::verify) also checks that every row has
+/// its entries. Fails (rather than reporting) if `path` is not a rocksmap database.
+pub fn verify<K, V, KC, P>(path: P, mode: VerifyMode) -> Result<VerifyReport>
+where
+    V: DeserializeOwned,
+    KC: KeyCodec<K>,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let opts = Options::default();
+    let descriptors: Vec<ColumnFamilyDescriptor> = meta::existing_cfs(&opts, path)
+        .iter()
+        .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+        .collect();
+    let db = match mode {
+        VerifyMode::Check => DB::open_cf_descriptors_read_only(&opts, path, descriptors, false),
+        VerifyMode::Quarantine => DB::open_cf_descriptors(&opts, path, descriptors),
+    }
+    .map_err(Error::from)?;
+    if db.cf_handle(meta::META_CF).is_none() {
+        return Err(Error::FormatMismatch(
+            "not a rocksmap-managed database (no metadata column family)".to_string(),
+        ));
+    }
+    let kind = meta::read_kind(&db)?.ok_or_else(|| {
+        Error::FormatMismatch("metadata present but no schema record".to_string())
+    })?;
+
+    match kind {
+        MapKind::Plain => verify_plain::<K, V, KC>(&db, mode),
+        MapKind::Ttl => verify_ttl::<K, V, KC>(&db, mode, SystemClock.now_unix_millis()),
+        MapKind::Versioned => verify_versioned::<K, V, KC>(&db, mode),
+        MapKind::Indexed => {
+            let mut verifier = Verifier::new(&db, mode, kind);
+            if verifier.check_key_codec::<K, KC>()? {
+                verifier.check_rows::<K, V, KC>("default", RowLayout::Bare, |_, _| Ok(()))?;
+                let unique = meta::read_unique_indexes(&db)?;
+                for name in meta::read_indexes(&db)? {
+                    verifier.check_index_structure::<K, KC>(&name, unique.as_deref(), "default")?;
+                }
+            }
+            Ok(verifier.finish())
+        }
+    }
+}
+
+/// Verify a plain map: every user column family holds `K -> V` rows.
+pub(crate) fn verify_plain<K, V, KC>(db: &DB, mode: VerifyMode) -> Result<VerifyReport>
+where
+    V: DeserializeOwned,
+    KC: KeyCodec<K>,
+{
+    let mut verifier = Verifier::new(db, mode, MapKind::Plain);
+    if verifier.check_key_codec::<K, KC>()? {
+        let cfs = meta::existing_cfs(&Options::default(), db.path());
+        for cf in cfs.iter().filter(|cf| !meta::is_internal_cf(cf)) {
+            verifier.check_rows::<K, V, KC>(cf, RowLayout::Bare, |_, _| Ok(()))?;
+        }
+    }
+    Ok(verifier.finish())
+}
+
+/// Verify a TTL map: the default column family and every recorded TTL table hold enveloped
+/// `K -> V` rows, and agree with their expiry indexes as of `now`.
+pub(crate) fn verify_ttl<K, V, KC>(db: &DB, mode: VerifyMode, now: u64) -> Result<VerifyReport>
+where
+    V: DeserializeOwned,
+    KC: KeyCodec<K>,
+{
+    let mut verifier = Verifier::new(db, mode, MapKind::Ttl);
+    if verifier.check_key_codec::<K, KC>()? {
+        let mut tables = vec!["default".to_string()];
+        tables.extend(meta::read_ttl_tables(db)?);
+        for table in &tables {
+            verifier.check_rows::<K, V, KC>(table, RowLayout::Ttl, |_, _| Ok(()))?;
+            verifier.check_expiry_index(table, now)?;
+        }
+    }
+    Ok(verifier.finish())
+}
+
+/// Verify a versioned map: the default column family holds version-tagged `K -> V` rows.
+pub(crate) fn verify_versioned<K, V, KC>(db: &DB, mode: VerifyMode) -> Result<VerifyReport>
+where
+    V: DeserializeOwned,
+    KC: KeyCodec<K>,
+{
+    let mut verifier = Verifier::new(db, mode, MapKind::Versioned);
+    if verifier.check_key_codec::<K, KC>()? {
+        match meta::read_value_version(db)? {
+            Some(current) => {
+                let layout = RowLayout::Versioned(current);
+                verifier.check_rows::<K, V, KC>("default", layout, |_, _| Ok(()))?;
+            }
+            None => {
+                let msg = "versioned map has no recorded value version".to_string();
+                verifier.problem(meta::META_CF, &[], ProblemKind::MetadataMismatch(msg), None)?;
+            }
+        }
+    }
+    Ok(verifier.finish())
+}
+
+/// Rows previously moved aside by [`VerifyMode::Quarantine`] in the database at `path` (opened
+/// read-only).
+pub fn quarantined<P: AsRef<Path>>(path: P) -> Result<Vec<QuarantinedRow>> {
+    let path = path.as_ref();
+    let opts = Options::default();
+    let descriptors: Vec<ColumnFamilyDescriptor> = meta::existing_cfs(&opts, path)
+        .iter()
+        .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+        .collect();
+    let db =
+        DB::open_cf_descriptors_read_only(&opts, path, descriptors, false).map_err(Error::from)?;
+    meta::quarantined_rows(&db)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IndexedRocksMap, OrderedCodec, RocksMap, TtlRocksMap};
+    use serde::{Deserialize, Serialize};
+    use tempfile::TempDir;
+
+    type U64 = OrderedCodec<u64>;
+
+    #[test]
+    fn clean_plain_map_verifies() {
+        let dir = TempDir::new().unwrap();
+        let map = RocksMap::<u64, String>::open_with_cfs(dir.path(), Options::default(), &["side"])
+            .unwrap();
+        map.put(1, &"a".to_string()).unwrap();
+        map.with_cf("side").put(2, &"b".to_string()).unwrap();
+
+        let report = map.verify(VerifyMode::Check).unwrap();
+        assert!(report.is_clean(), "{report:?}");
+        assert_eq!(report.rows_checked, 2);
+        assert_eq!(report.column_families, vec!["default", "side"]);
+    }
+
+    #[test]
+    fn bad_rows_are_reported_and_quarantined() {
+        let dir = TempDir::new().unwrap();
+        let good_key = <U64 as KeyCodec<u64>>::encode(&1).unwrap();
+        {
+            let map = RocksMap::<u64, (u64, u64)>::open(dir.path()).unwrap();
+            map.put(1, &(1, 1)).unwrap();
+            map.db()
+                .put(b"not-a-u64", bincode::serialize(&(2u64, 2u64)).unwrap())
+                .unwrap();
+            // Written as a narrower type: a plain read would decode garbage or fail later.
+            let key = <U64 as KeyCodec<u64>>::encode(&3).unwrap();
+            map.db()
+                .put(&key, bincode::serialize(&3u64).unwrap())
+                .unwrap();
+            let key = <U64 as KeyCodec<u64>>::encode(&4).unwrap();
+            map.db()
+                .put(&key, bincode::serialize(&(4u64, 4u64, 4u64)).unwrap())
+                .unwrap();
+        }
+
+        let report = verify::<u64, (u64, u64), U64, _>(dir.path(), VerifyMode::Check).unwrap();
+        assert_eq!(report.kind, MapKind::Plain);
+        assert_eq!(report.problems.len(), 3);
+        assert!(report.problems.iter().all(|p| !p.quarantined));
+        assert!(matches!(
+            report
+                .problems
+                .iter()
+                .find(|p| p.key == b"not-a-u64")
+                .unwrap()
+                .kind,
+            ProblemKind::UndecodableKey(_)
+        ));
+        assert!(report.problems.iter().all(|p| p.key != good_key));
+
+        let report = verify::<u64, (u64, u64), U64, _>(dir.path(), VerifyMode::Quarantine).unwrap();
+        assert_eq!(report.quarantined(), 3);
+        let rows = quarantined(dir.path()).unwrap();
+        assert_eq!(rows.len(), 3);
+        assert!(rows
+            .iter()
+            .any(|r| r.column_family == "default" && r.key == b"not-a-u64"));
+
+        let map = RocksMap::<u64, (u64, u64)>::open(dir.path()).unwrap();
+        assert!(map.verify(VerifyMode::Check).unwrap().is_clean());
+        assert_eq!(map.count().unwrap(), 1);
+    }
+
+    #[test]
+    fn key_codec_mismatch_is_reported_once() {
+        let dir = TempDir::new().unwrap();
+        {
+            let map = RocksMap::<u64, u64>::open(dir.path()).unwrap();
+            map.put(1, &1).unwrap();
+        }
+        let report =
+            verify::<u64, u64, crate::BincodeCodec<u64>, _>(dir.path(), VerifyMode::Check).unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert!(matches!(
+            report.problems[0].kind,
+            ProblemKind::MetadataMismatch(_)
+        ));
+    }
+
+    #[test]
+    fn ttl_envelopes_are_checked() {
+        let dir = TempDir::new().unwrap();
+        let map = TtlRocksMap::<u64, String>::open(dir.path()).unwrap();
+        map.put(1, &"ok".to_string()).unwrap();
+        let key = <U64 as KeyCodec<u64>>::encode(&2).unwrap();
+        map.db().put(&key, [0x07, 1, 2]).unwrap();
+
+        let report = map.verify(VerifyMode::Check).unwrap();
+        assert_eq!(report.kind, MapKind::Ttl);
+        assert_eq!(report.problems.len(), 1);
+        assert_eq!(report.problems[0].key, key);
+        assert!(matches!(
+            report.problems[0].kind,
+            ProblemKind::InvalidTtlEnvelope(_)
+        ));
+    }
+
+    #[test]
+    fn expiry_index_is_checked_against_the_rows() {
+        let dir = TempDir::new().unwrap();
+        let clock = crate::ManualClock::new(0);
+        let map =
+            TtlRocksMap::<u64, String>::open_with_clock(dir.path(), std::sync::Arc::new(clock))
+                .unwrap();
+        map.put_with_expiry(1, &"a".to_string(), 100).unwrap();
+        map.put_with_expiry(2, &"b".to_string(), 200).unwrap();
+        let index = map.db().cf_handle(meta::TTL_EXPIRY_CF).unwrap();
+        let key = |k: u64| <U64 as KeyCodec<u64>>::encode(&k).unwrap();
+        // An entry for a row that does not exist, and row 2's entry moved to a wrong deadline.
+        let dangling = expiry_index_key(300, &key(3));
+        map.db().put_cf(index, &dangling, []).unwrap();
+        map.db()
+            .delete_cf(index, expiry_index_key(200, &key(2)))
+            .unwrap();
+        let wrong = expiry_index_key(250, &key(2));
+        map.db().put_cf(index, &wrong, []).unwrap();
+
+        let report = map.verify(VerifyMode::Check).unwrap();
+        let mut found: Vec<(&str, &[u8], bool)> = report
+            .problems
+            .iter()
+            .map(|p| {
+                let dangling = matches!(p.kind, ProblemKind::DanglingIndexEntry);
+                (p.column_family.as_str(), &p.key[..], dangling)
+            })
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                (meta::TTL_EXPIRY_CF, &wrong[..], true),
+                (meta::TTL_EXPIRY_CF, &dangling[..], true),
+                ("default", &key(2)[..], false),
+            ]
+        );
+
+        let report = map.verify(VerifyMode::Quarantine).unwrap();
+        assert_eq!(report.quarantined(), 2);
+        map.db()
+            .put_cf(index, expiry_index_key(200, &key(2)), [])
+            .unwrap();
+        assert!(map.verify(VerifyMode::Check).unwrap().is_clean());
+    }
+
+    #[test]
+    fn quarantining_a_ttl_row_removes_its_expiry_index_entry() {
+        let dir = TempDir::new().unwrap();
+        let clock = crate::ManualClock::new(0);
+        let map =
+            TtlRocksMap::<u64, (u64, u64)>::open_with_clock(dir.path(), std::sync::Arc::new(clock))
+                .unwrap();
+        map.put_with_expiry(1, &(1, 1), 100).unwrap();
+        // Row 2 expires at 200 but its payload is too narrow for `(u64, u64)`.
+        let key = <U64 as KeyCodec<u64>>::encode(&2).unwrap();
+        let mut row = vec![0x01];
+        row.extend_from_slice(&200u64.to_be_bytes());
+        row.extend(bincode::serialize(&2u64).unwrap());
+        let index = map.db().cf_handle(meta::TTL_EXPIRY_CF).unwrap();
+        map.db().put(&key, &row).unwrap();
+        map.db()
+            .put_cf(index, expiry_index_key(200, &key), [])
+            .unwrap();
+
+        let report = map.verify(VerifyMode::Quarantine).unwrap();
+        assert_eq!(report.quarantined(), 1);
+        assert!(matches!(
+            report.problems[0].kind,
+            ProblemKind::UndecodableValue(_)
+        ));
+        assert_eq!(
+            map.db()
+                .iterator_cf(index, rocksdb::IteratorMode::Start)
+                .count(),
+            1
+        );
+        assert!(map.verify(VerifyMode::Check).unwrap().is_clean());
+    }
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct User {
+        email: String,
+        org: String,
+    }
+
+    fn user(email: &str, org: &str) -> User {
+        User {
+            email: email.to_string(),
+            org: org.to_string(),
+        }
+    }
+
+    fn open_indexed(path: &Path) -> IndexedRocksMap<u64, User> {
+        let mut builder = IndexedRocksMap::<u64, User>::builder(path);
+        builder.unique_index("by_email", |u: &User| Some(u.email.clone()));
+        builder.index("by_org", |u: &User| Some(u.org.clone()));
+        builder.open().unwrap()
+    }
+
+    #[test]
+    fn index_disagreements_are_found_both_ways() {
+        let dir = TempDir::new().unwrap();
+        let map = open_indexed(dir.path());
+        map.put(1, &user("a@x", "acme")).unwrap();
+        map.put(2, &user("b@x", "acme")).unwrap();
+        assert!(map.verify(VerifyMode::Check).unwrap().is_clean());
+
+        let db = map.db();
+        let org_cf = db.cf_handle("__idx_by_org").unwrap();
+        let email_cf = db.cf_handle("__idx_by_email").unwrap();
+        let pk = |k: u64| <U64 as KeyCodec<u64>>::encode(&k).unwrap();
+        let sk =
+            |s: &str| <OrderedCodec<String> as KeyCodec<String>>::encode(&s.to_string()).unwrap();
+
+        // Dangling: an entry for a row that does not exist.
+        let mut dangling = sk("acme");
+        dangling.extend_from_slice(&pk(9));
+        db.put_cf(org_cf, &dangling, b"").unwrap();
+        // Missing: row 2 loses its by_email entry.
+        db.delete_cf(email_cf, sk("b@x")).unwrap();
+        // Duplicate: row 3 written behind the index's back with row 1's email.
+        let data = db.cf_handle("default").unwrap();
+        db.put_cf(
+            data,
+            pk(3),
+            bincode::serialize(&user("a@x", "other")).unwrap(),
+        )
+        .unwrap();
+
+        let report = map.verify(VerifyMode::Check).unwrap();
+        let kinds: Vec<_> = report
+            .problems
+            .iter()
+            .map(|p| (p.column_family.as_str(), p.kind.clone()))
+            .collect();
+        assert!(kinds.contains(&(
+            "default",
+            ProblemKind::DuplicateUniqueKey {
+                index: "by_email".to_string()
+            }
+        )));
+        assert!(kinds.contains(&(
+            "default",
+            ProblemKind::MissingIndexEntry {
+                index: "by_email".to_string()
+            }
+        )));
+        assert!(kinds.contains(&("__idx_by_org", ProblemKind::DanglingIndexEntry)));
+        // Row 3's by_org entry is missing too.
+        assert_eq!(report.problems.len(), 4, "{kinds:?}");
+
+        // The path-based check sees the dangling entry without knowing the extractors.
+        drop(map);
+        let report = verify::<u64, User, U64, _>(dir.path(), VerifyMode::Quarantine).unwrap();
+        assert_eq!(report.problems.len(), 1, "{report:?}");
+        assert_eq!(report.problems[0].key, dangling);
+        assert_eq!(report.quarantined(), 1);
+    }
+
+    #[test]
+    fn a_dangling_entry_whose_suffix_names_a_live_row_is_still_dangling() {
+        let dir = TempDir::new().unwrap();
+        let mut builder = IndexedRocksMap::<String, User>::builder(dir.path());
+        builder.index("by_org", |u: &User| Some(u.org.clone()));
+        let map = builder.open().unwrap();
+        map.put("b".to_string(), &user("b@x", "acme")).unwrap();
+
+        // `sk ++ "ab"`: the encoding of the live key "b" is a suffix of the dead key "ab".
+        let enc = |s: &str| <OrderedCodec<String> as KeyCodec<String>>::encode(&s.to_string());
+        let sk = enc("acme").unwrap();
+        let mut dangling = sk.clone();
+        dangling.extend_from_slice(&enc("ab").unwrap());
+        let org_cf = map.db().cf_handle("__idx_by_org").unwrap();
+        map.db()
+            .put_cf(org_cf, &dangling, index::entry_value(&sk))
+            .unwrap();
+        drop(map);
+
+        let report =
+            verify::<String, User, OrderedCodec<String>, _>(dir.path(), VerifyMode::Check).unwrap();
+        assert_eq!(report.problems.len(), 1, "{report:?}");
+        assert_eq!(report.problems[0].key, dangling);
+        assert_eq!(report.problems[0].kind, ProblemKind::DanglingIndexEntry);
+    }
+}
@@ -0,0 +1,633 @@
+//! Schema-versioned values with read-time migrations.
+//!
+//! [`VersionedRocksMap`] stores each value behind a small header naming the schema version it
+//! was written at:
+//!
+//! ```text
+//! u16 (BE) version -> followed by the bincode payload of the value type at that version
+//! ```
+//!
+//! The value type declares its current version through [`Versioned`]. Migrations from older
+//! versions are registered on the [`VersionedRocksMapBuilder`] at open time; each one decodes a
+//! row as the old type and converts it straight to the current type. Reads upgrade old rows on
+//! the fly and leave them on disk as they are; [`VersionedRocksMap::upgrade_rows`] (or its
+//! background variant, [`VersionedRocksMap::spawn_upgrade`]) rewrites them at the current
+//! version.
+//!
+//! ```no_run
+//! use rocksmap::{Versioned, VersionedRocksMap};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Clone, Serialize, Deserialize)]
+//! struct UserV1 { name: String }
+//! impl Versioned for UserV1 { const VERSION: u16 = 1; }
+//!
+//! #[derive(Clone, Serialize, Deserialize)]
+//! struct UserV2 { name: String, email: Option<String> }
+//! impl Versioned for UserV2 { const VERSION: u16 = 2; }
+//!
+//! let users = VersionedRocksMap::<u64, UserV2>::builder("./users.db")
+//!     .migrate(|old: UserV1| UserV2 { name: old.name, email: None })
+//!     .open()?;
+//! # Ok::<(), rocksmap::Error>(())
+//! ```
+//!
+//! The versions rows may be stored at are recorded in the metadata, and only narrowed to the
+//! current one once `upgrade_rows` has rewritten every row: opening a database holding rows
+//! at a newer version, or at an older one with no migration registered for it, fails with
+//! [`Error::FormatMismatch`]. Registering a migration from a type also accepts it as the
+//! previously recorded value type (see [`accept_type_change`](crate::accept_type_change)).
+
+use crate::codec::{BincodeCodec, KeyCodec, ValueCodec};
+use crate::error::{Error, Result};
+use crate::format::{self, OpenOptions};
+use crate::meta::{self, MapKind};
+use crate::ordered::{OrderedCodec, OrderedKey};
+use crate::schema::{self, TypeFingerprint};
+use crate::verify::{self, VerifyMode, VerifyReport};
+use rocksdb::{ColumnFamilyDescriptor, IteratorMode, Options, DB};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+/// A value type with a schema version, for use in a [`VersionedRocksMap`].
+///
+/// Bump `VERSION` whenever the serialized shape changes, and keep the previous type around to
+/// register a migration from it.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// The schema version this type is written at.
+    const VERSION: u16;
+}
+
+/// Prefix a payload with its version header.
+fn encode_version_envelope(version: u16, payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(2 + payload.len());
+    out.extend_from_slice(&version.to_be_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Split a versioned value into `(version, payload)`.
+pub(crate) fn decode_version_envelope(bytes: &[u8]) -> Result<(u16, &[u8])> {
+    if bytes.len() < 2 {
+        return Err(Error::Deserialization(
+            "truncated version header".to_string(),
+        ));
+    }
+    let (version, payload) = bytes.split_at(2);
+    Ok((u16::from_be_bytes([version[0], version[1]]), payload))
+}
+
+/// Decode a versioned-map value header for tooling that reads versioned databases directly
+/// (e.g. the CLI), returning the schema version the row was written at and its payload bytes.
+pub fn strip_version_envelope(value: &[u8]) -> Result<(u16, Vec<u8>)> {
+    let (version, payload) = decode_version_envelope(value)?;
+    Ok((version, payload.to_vec()))
+}
+
+/// The value versions the rows of `db` are stored at. **O(n)** — performs a full scan.
+fn scan_versions(db: &DB) -> Result<BTreeSet<u16>> {
+    let mut versions = BTreeSet::new();
+    for item in db.iterator(IteratorMode::Start) {
+        let (_, value) = item.map_err(Error::from)?;
+        versions.insert(decode_version_envelope(&value)?.0);
+    }
+    Ok(versions)
+}
+
+type Upgrade<V> = Box<dyn Fn(&[u8]) -> Result<V> + Send + Sync>;
+
+struct Migration<V> {
+    from: u16,
+    shape: String,
+    upgrade: Upgrade<V>,
+}
+
+/// Builder for a [`VersionedRocksMap`]: registers migrations from older value versions.
+pub struct VersionedRocksMapBuilder<K, V> {
+    path: PathBuf,
+    migrations: Vec<Migration<V>>,
+    _marker: PhantomData<K>,
+}
+
+impl<K, V> VersionedRocksMapBuilder<K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Versioned + Clone + 'static,
+{
+    /// Register how to upgrade a row written as `Old` (at `Old::VERSION`) to the current `V`.
+    pub fn migrate<Old, F>(mut self, upgrade: F) -> Self
+    where
+        Old: Versioned,
+        F: Fn(Old) -> V + Send + Sync + 'static,
+    {
+        self.migrations.push(Migration {
+            from: Old::VERSION,
+            shape: schema::shape::<Old>(),
+            upgrade: Box::new(move |payload| {
+                Ok(upgrade(<BincodeCodec<Old> as ValueCodec<Old>>::decode(
+                    payload,
+                )?))
+            }),
+        });
+        self
+    }
+
+    /// Open the database, checking the recorded value version against `V` and the registered
+    /// migrations.
+    pub fn open(self) -> Result<VersionedRocksMap<K, V>> {
+        VersionedRocksMap::open_internal(self.path, self.migrations, &OpenOptions::default())
+    }
+
+    /// Like [`open`](Self::open), with rocksmap-level [`OpenOptions`] (e.g. to allow a format
+    /// upgrade).
+    pub fn open_with(self, open: &OpenOptions) -> Result<VersionedRocksMap<K, V>> {
+        VersionedRocksMap::open_internal(self.path, self.migrations, open)
+    }
+}
+
+/// A typed map whose values carry a schema version and are migrated to the current one on read.
+///
+/// Stored on the default column family. Distinct from [`RocksMap`](crate::RocksMap): opening
+/// the same database the other way fails via the persisted format tag.
+pub struct VersionedRocksMap<K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Versioned + Clone,
+{
+    db: DB,
+    migrations: BTreeMap<u16, Upgrade<V>>,
+    // Serializes writes with the row rewrite, so an upgrade never overwrites a newer put.
+    write_lock: Mutex<()>,
+    _marker: PhantomData<K>,
+}
+
+impl<K, V> VersionedRocksMap<K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Versioned + Clone + 'static,
+{
+    /// Open a versioned map at `path` with no migrations registered.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::builder(path).open()
+    }
+
+    /// Start building a versioned map at `path`.
+    pub fn builder<P: AsRef<Path>>(path: P) -> VersionedRocksMapBuilder<K, V> {
+        VersionedRocksMapBuilder {
+            path: path.as_ref().to_path_buf(),
+            migrations: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn open_internal(
+        path: PathBuf,
+        migrations: Vec<Migration<V>>,
+        open: &OpenOptions,
+    ) -> Result<Self> {
+        let mut by_version = BTreeMap::new();
+        let mut old_types = Vec::new();
+        for migration in migrations {
+            if migration.from >= V::VERSION {
+                return Err(Error::Other(format!(
+                    "migration from version {} is not older than the current version {}",
+                    migration.from,
+                    V::VERSION
+                )));
+            }
+            if by_version.contains_key(&migration.from) {
+                return Err(Error::Other(format!(
+                    "more than one migration registered from version {}",
+                    migration.from
+                )));
+            }
+            old_types.push(migration.shape);
+            by_version.insert(migration.from, migration.upgrade);
+        }
+
+        if !path.exists() {
+            std::fs::create_dir_all(&path).map_err(|_| Error::InvalidPath(path.clone()))?;
+        }
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let descriptors: Vec<ColumnFamilyDescriptor> = meta::all_cf_names(&opts, &path, &[])
+            .iter()
+            .map(|name| ColumnFamilyDescriptor::new(name, Options::default()))
+            .collect();
+        let db = DB::open_cf_descriptors(&opts, &path, descriptors).map_err(Error::from)?;
+        format::prepare(&db, open)?;
+        meta::verify_or_write_kind(&db, MapKind::Versioned)?;
+
+        // Every version rows may still be stored at must be readable by this build. Databases
+        // from before the record only know the version they were last opened with, which says
+        // nothing of older rows left behind, so their rows are looked at instead.
+        let mut stored = match meta::read_stored_versions(&db)? {
+            Some(stored) => stored,
+            None => scan_versions(&db)?,
+        };
+        for &version in &stored {
+            if version > V::VERSION {
+                return Err(Error::FormatMismatch(format!(
+                    "database holds values at version {version}, newer than this build's {}",
+                    V::VERSION
+                )));
+            }
+            if version < V::VERSION && !by_version.contains_key(&version) {
+                return Err(Error::FormatMismatch(format!(
+                    "database holds values at version {version} but no migration from it to \
+                     version {} is registered",
+                    V::VERSION
+                )));
+            }
+        }
+
+        // A migration from the recorded value type is an explicit statement that the type
+        // changed on purpose.
+        if let Some(recorded) = meta::read_types(&db)? {
+            let expected = TypeFingerprint::of::<K, V>();
+            if recorded.key == expected.key && old_types.contains(&recorded.value) {
+                meta::write_types(&db, &expected)?;
+            }
+        }
+        schema::verify_or_write_types::<K, V, _>(&db, open)?;
+        meta::write_value_version(&db, V::VERSION)?;
+        if stored.insert(V::VERSION) || meta::read_stored_versions(&db)?.is_none() {
+            meta::write_stored_versions(&db, &stored)?;
+        }
+
+        Ok(Self {
+            db,
+            migrations: by_version,
+            write_lock: Mutex::new(()),
+            _marker: PhantomData,
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.write_lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Decode a stored row, migrating it if it was written at an older version.
+    fn decode_value(&self, bytes: &[u8]) -> Result<V> {
+        let (version, payload) = decode_version_envelope(bytes)?;
+        if version == V::VERSION {
+            return <BincodeCodec<V> as ValueCodec<V>>::decode(payload);
+        }
+        match self.migrations.get(&version) {
+            Some(upgrade) => upgrade(payload),
+            None => Err(Error::FormatMismatch(format!(
+                "no migration registered from value version {version} to {}",
+                V::VERSION
+            ))),
+        }
+    }
+
+    /// Store a value at the current version.
+    pub fn put(&self, key: K, value: &V) -> Result<()> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(&key)?;
+        let payload = <BincodeCodec<V> as ValueCodec<V>>::encode(value)?;
+        let _guard = self.lock();
+        self.db
+            .put(key_bytes, encode_version_envelope(V::VERSION, &payload))
+            .map_err(Error::from)
+    }
+
+    /// Retrieve a value, upgrading it to the current version if it was stored at an older one.
+    pub fn get(&self, key: &K) -> Result<Option<V>> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        match self.db.get(key_bytes).map_err(Error::from)? {
+            Some(bytes) => Ok(Some(self.decode_value(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Delete a key-value pair.
+    pub fn delete(&self, key: &K) -> Result<()> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        let _guard = self.lock();
+        self.db.delete(key_bytes).map_err(Error::from)
+    }
+
+    /// Returns `true` if a value exists for `key`.
+    pub fn contains(&self, key: &K) -> Result<bool> {
+        let key_bytes = <OrderedCodec<K> as KeyCodec<K>>::encode(key)?;
+        Ok(self
+            .db
+            .get_pinned(key_bytes)
+            .map_err(Error::from)?
+            .is_some())
+    }
+
+    /// Returns `true` if the map has no entries.
+    pub fn is_empty(&self) -> Result<bool> {
+        match self.db.iterator(IteratorMode::Start).next() {
+            None => Ok(true),
+            Some(Ok(_)) => Ok(false),
+            Some(Err(e)) => Err(Error::from(e)),
+        }
+    }
+
+    /// Number of entries. **O(n)** — performs a full scan.
+    pub fn count(&self) -> Result<usize> {
+        let mut count = 0;
+        for item in self.db.iterator(IteratorMode::Start) {
+            item.map_err(Error::from)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Iterate key-value pairs in ascending key order, upgrading old rows as they are read.
+    pub fn iter(&self) -> VersionedIterator<'_, K, V> {
+        VersionedIterator {
+            inner: self.db.iterator(IteratorMode::Start),
+            map: self,
+        }
+    }
+
+    /// How many rows are stored at each schema version. **O(n)** — performs a full scan.
+    pub fn rows_by_version(&self) -> Result<BTreeMap<u16, u64>> {
+        let mut counts = BTreeMap::new();
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (_, value) = item.map_err(Error::from)?;
+            let (version, _) = decode_version_envelope(&value)?;
+            *counts.entry(version).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+
+    /// Rewrite every row stored at an older version at the current one, returning how many
+    /// were rewritten. Safe to run alongside other reads and writes: a row changed since the
+    /// scan saw it is left alone.
+    pub fn upgrade_rows(&self) -> Result<u64> {
+        let mut rewritten = 0;
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, value) = item.map_err(Error::from)?;
+            if decode_version_envelope(&value)?.0 == V::VERSION {
+                continue;
+            }
+            let _guard = self.lock();
+            let Some(current) = self.db.get(&key).map_err(Error::from)? else {
+                continue;
+            };
+            if decode_version_envelope(&current)?.0 == V::VERSION {
+                continue;
+            }
+            let payload =
+                <BincodeCodec<V> as ValueCodec<V>>::encode(&self.decode_value(&current)?)?;
+            self.db
+                .put(&key, encode_version_envelope(V::VERSION, &payload))
+                .map_err(Error::from)?;
+            rewritten += 1;
+        }
+        // Only a full pass shows no older rows are left; until then a reopen still needs their
+        // migrations.
+        meta::write_stored_versions(&self.db, &BTreeSet::from([V::VERSION]))?;
+        Ok(rewritten)
+    }
+
+    /// Run [`upgrade_rows`](Self::upgrade_rows) on a background thread. Join the handle for the
+    /// number of rows rewritten.
+    pub fn spawn_upgrade(self: &Arc<Self>) -> JoinHandle<Result<u64>>
+    where
+        K: Send + Sync + 'static,
+        V: Send + Sync,
+    {
+        let map = Arc::clone(self);
+        std::thread::spawn(move || map.upgrade_rows())
+    }
+
+    /// Check that every entry carries a valid version header and that rows at the current
+    /// version decode as `V`. See [`verify`](crate::verify) for what is checked.
+    pub fn verify(&self, mode: VerifyMode) -> Result<VerifyReport> {
+        verify::verify_versioned::<K, V, OrderedCodec<K>>(&self.db, mode)
+    }
+
+    /// Flush and fsync the write-ahead log, making prior writes durable against OS/power loss.
+    /// See [`RocksMap::sync_wal`](crate::RocksMap::sync_wal) for the durability model.
+    pub fn sync_wal(&self) -> Result<()> {
+        self.db.flush_wal(true).map_err(Error::from)
+    }
+
+    /// Access the underlying RocksDB handle.
+    pub fn db(&self) -> &DB {
+        &self.db
+    }
+}
+
+/// Iterator over the entries of a [`VersionedRocksMap`], upgrading old rows as it goes.
+pub struct VersionedIterator<'a, K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Versioned + Clone,
+{
+    inner: rocksdb::DBIterator<'a>,
+    map: &'a VersionedRocksMap<K, V>,
+}
+
+impl<'a, K, V> Iterator for VersionedIterator<'a, K, V>
+where
+    K: Serialize + DeserializeOwned + Clone + OrderedKey,
+    V: Versioned + Clone + 'static,
+{
+    type Item = Result<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (key_bytes, value_bytes) = match self.inner.next()? {
+            Ok(pair) => pair,
+            Err(e) => return Some(Err(Error::from(e))),
+        };
+        Some((|| {
+            let key = <OrderedCodec<K> as KeyCodec<K>>::decode(&key_bytes)?;
+            Ok((key, self.map.decode_value(&value_bytes)?))
+        })())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RocksMap;
+    use serde::Deserialize;
+    use tempfile::TempDir;
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct UserV1 {
+        name: String,
+    }
+
+    impl Versioned for UserV1 {
+        const VERSION: u16 = 1;
+    }
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct UserV2 {
+        name: String,
+        admin: bool,
+    }
+
+    impl Versioned for UserV2 {
+        const VERSION: u16 = 2;
+    }
+
+    fn user_v1(name: &str) -> UserV1 {
+        UserV1 {
+            name: name.to_string(),
+        }
+    }
+
+    fn upgrade(old: UserV1) -> UserV2 {
+        UserV2 {
+            name: old.name,
+            admin: false,
+        }
+    }
+
+    fn seed_v1(path: &Path) {
+        let map = VersionedRocksMap::<u64, UserV1>::open(path).unwrap();
+        map.put(1, &user_v1("ann")).unwrap();
+        map.put(2, &user_v1("bob")).unwrap();
+    }
+
+    #[test]
+    fn old_rows_are_migrated_on_read() {
+        let dir = TempDir::new().unwrap();
+        seed_v1(dir.path());
+
+        let map = VersionedRocksMap::<u64, UserV2>::builder(dir.path())
+            .migrate(upgrade)
+            .open()
+            .unwrap();
+        map.put(
+            3,
+            &UserV2 {
+                name: "cy".to_string(),
+                admin: true,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(map.get(&1).unwrap().unwrap(), upgrade(user_v1("ann")));
+        let names: Vec<(u64, bool)> = map
+            .iter()
+            .map(|item| item.map(|(k, v)| (k, v.admin)))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(names, vec![(1, false), (2, false), (3, true)]);
+        // Reads leave rows as they were.
+        assert_eq!(
+            map.rows_by_version().unwrap(),
+            BTreeMap::from([(1, 2), (2, 1)])
+        );
+        assert_eq!(meta::read_value_version(map.db()).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn versions_without_a_migration_are_rejected() {
+        let dir = TempDir::new().unwrap();
+        seed_v1(dir.path());
+
+        let err = VersionedRocksMap::<u64, UserV2>::open(dir.path())
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, Error::FormatMismatch(ref m) if m.contains("no migration")),
+            "{err}"
+        );
+
+        VersionedRocksMap::<u64, UserV2>::builder(dir.path())
+            .migrate(upgrade)
+            .open()
+            .unwrap();
+        let err = VersionedRocksMap::<u64, UserV1>::open(dir.path())
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, Error::FormatMismatch(ref m) if m.contains("newer")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn migrations_stay_required_until_every_row_is_upgraded() {
+        let dir = TempDir::new().unwrap();
+        seed_v1(dir.path());
+        let map = VersionedRocksMap::<u64, UserV2>::builder(dir.path())
+            .migrate(upgrade)
+            .open()
+            .unwrap();
+        assert_eq!(
+            meta::read_stored_versions(map.db()).unwrap(),
+            Some(BTreeSet::from([1, 2]))
+        );
+        drop(map);
+
+        let err = VersionedRocksMap::<u64, UserV2>::open(dir.path())
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, Error::FormatMismatch(ref m) if m.contains("version 1")),
+            "{err}"
+        );
+
+        // Without the record (an older database), the rows themselves are checked.
+        let db = DB::open_cf(&Options::default(), dir.path(), [meta::META_CF]).unwrap();
+        db.delete_cf(db.cf_handle(meta::META_CF).unwrap(), b"stored_versions")
+            .unwrap();
+        drop(db);
+        assert!(VersionedRocksMap::<u64, UserV2>::open(dir.path()).is_err());
+    }
+
+    #[test]
+    fn upgrade_rewrites_old_rows_in_the_background() {
+        let dir = TempDir::new().unwrap();
+        seed_v1(dir.path());
+
+        let map = Arc::new(
+            VersionedRocksMap::<u64, UserV2>::builder(dir.path())
+                .migrate(upgrade)
+                .open()
+                .unwrap(),
+        );
+        assert_eq!(map.spawn_upgrade().join().unwrap().unwrap(), 2);
+        assert_eq!(map.rows_by_version().unwrap(), BTreeMap::from([(2, 2)]));
+        assert_eq!(map.upgrade_rows().unwrap(), 0);
+        drop(map);
+
+        // Every row is at the current version now, so no migration is needed.
+        let map = VersionedRocksMap::<u64, UserV2>::open(dir.path()).unwrap();
+        assert_eq!(map.get(&2).unwrap().unwrap().name, "bob");
+        assert!(map.verify(VerifyMode::Check).unwrap().is_clean());
+    }
+
+    #[test]
+    fn versioned_maps_are_a_distinct_kind() {
+        let dir = TempDir::new().unwrap();
+        seed_v1(dir.path());
+        assert!(RocksMap::<u64, UserV1>::open(dir.path()).is_err());
+        assert!(VersionedRocksMap::<u64, UserV1>::open(dir.path()).is_ok());
+    }
+
+    #[test]
+    fn verify_flags_rows_from_the_future() {
+        let dir = TempDir::new().unwrap();
+        let map = VersionedRocksMap::<u64, UserV1>::open(dir.path()).unwrap();
+        map.put(1, &user_v1("ann")).unwrap();
+        let key = <OrderedCodec<u64> as KeyCodec<u64>>::encode(&2).unwrap();
+        map.db()
+            .put(key, encode_version_envelope(9, b"??"))
+            .unwrap();
+
+        let report = map.verify(VerifyMode::Check).unwrap();
+        assert_eq!(report.problems.len(), 1);
+        assert!(matches!(
+            report.problems[0].kind,
+            crate::ProblemKind::InvalidVersionEnvelope(_)
+        ));
+    }
+}